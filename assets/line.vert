@@ -0,0 +1,13 @@
+#version 330 core
+
+uniform mat4 view;
+uniform mat4 projection;
+
+in vec3 position;
+in vec3 color;
+out vec3 LineColor;
+
+void main() {
+    gl_Position = projection * view * vec4(position, 1.0);
+    LineColor = color;
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+#version 330 core
+
+uniform sampler2D atlas;
+out vec4 FragColor;
+in vec2 TexCoords;
+in vec4 OverlayColor;
+
+void main() {
+    float coverage = texture(atlas, TexCoords).r;
+    FragColor = vec4(OverlayColor.rgb, OverlayColor.a * coverage);
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+#version 330 core
+
+uniform vec2 window_size;
+
+in vec2 position;
+in vec2 tex_coords;
+in vec4 color;
+out vec2 TexCoords;
+out vec4 OverlayColor;
+
+void main() {
+    vec2 ndc = vec2(position.x / window_size.x * 2.0 - 1.0, 1.0 - position.y / window_size.y * 2.0);
+    gl_Position = vec4(ndc, 0.0, 1.0);
+    TexCoords = tex_coords;
+    OverlayColor = color;
+}
\ No newline at end of file
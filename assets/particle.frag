@@ -0,0 +1,10 @@
+#version 330 core
+
+out vec4 FragColor;
+in vec2 Corner;
+in vec4 ParticleColor;
+
+void main() {
+    float falloff = 1.0 - clamp(length(Corner) * 2.0, 0.0, 1.0);
+    FragColor = vec4(ParticleColor.rgb, ParticleColor.a * falloff * falloff);
+}
\ No newline at end of file
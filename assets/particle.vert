@@ -0,0 +1,21 @@
+#version 330 core
+
+uniform mat4 view;
+uniform mat4 projection;
+
+in vec2 corner;
+in vec3 center;
+in vec4 color;
+in float size;
+out vec2 Corner;
+out vec4 ParticleColor;
+
+void main() {
+    // The rows of the view rotation are the camera axes in world space, so the quad always faces the camera.
+    vec3 right = vec3(view[0][0], view[1][0], view[2][0]);
+    vec3 up    = vec3(view[0][1], view[1][1], view[2][1]);
+    vec3 world = center + (right * corner.x + up * corner.y) * size;
+    gl_Position = projection * view * vec4(world, 1.0);
+    Corner = corner;
+    ParticleColor = color;
+}
\ No newline at end of file
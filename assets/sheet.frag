@@ -8,6 +8,9 @@ in vec3 Normal;
 uniform vec3 objectColor;
 uniform vec3 lightColor;
 uniform vec3 lightPos;
+uniform vec3 viewPos;
+uniform float specularStrength;
+uniform float shininess;
 
 void main() {
     vec3 norm = normalize(Normal);
@@ -17,6 +20,12 @@ void main() {
     float ambientStrength = 0.001;
     vec3 ambient = ambientStrength * lightColor;
 
-    vec3 result = (ambient + diffuse) * objectColor;
+    // Blinn-Phong highlight; a strength of zero turns it off.
+    vec3 viewDir = normalize(viewPos - FragPos);
+    vec3 halfway = normalize(lightDir + viewDir);
+    float spec = diff > 0.0 ? pow(max(dot(norm, halfway), 0.0), shininess) : 0.0;
+    vec3 specular = specularStrength * spec * lightColor;
+
+    vec3 result = (ambient + diffuse) * objectColor + specular;
     FragColor = vec4(result, 1.0);
 }
\ No newline at end of file
@@ -1,28 +1,74 @@
 #version 330 core
 
+#define MAX_SOURCES 16
+#define POINT 0
+#define PLANE 1
+#define DIRECTIONAL 2
+
+struct WaveSource {
+    int kind;
+    vec2 position;
+    vec2 direction;
+    float amplitude;
+    float frequency;
+    float phase;
+    float decay;
+};
+
 uniform mat4 model;
 uniform mat4 view;
 uniform mat4 projection;
-uniform float t1;
-uniform float t2;
+uniform float time;
+uniform float wavenumber;
+// Multiplies every source's amplitude, e.g. with the music's loudness.
+uniform float amplitude_scale;
+uniform WaveSource sources[MAX_SOURCES];
+uniform int source_count;
 
 in vec3 position;
-in vec3 normal;
 out vec3 FragPos;
 out vec3 Normal;
 
-layout(location = 1) in vec3 colors;
-
-float hypot(float p1, float p2) {
-    return sqrt(pow(p1, 2.0) + pow(p2, 2.0));
+// Height of one source's ripple at p, followed by its x and y derivatives.
+vec3 source_wave(WaveSource source, vec2 p) {
+    vec2 offset = p - source.position;
+    float distance;
+    vec2 distance_gradient;
+    float weight = 1.0;
+    vec2 weight_gradient = vec2(0.0);
+    if (source.kind == PLANE) {
+        distance = dot(offset, source.direction);
+        distance_gradient = source.direction;
+    }
+    else {
+        distance = length(offset);
+        distance_gradient = distance > 0.0 ? offset / distance : vec2(0.0);
+        if (source.kind == DIRECTIONAL && distance > 0.0) {
+            float facing = dot(distance_gradient, source.direction);
+            if (facing > 0.0) {
+                weight = facing * facing;
+                weight_gradient = 2.0 * facing * (source.direction - facing * distance_gradient) / distance;
+            }
+            else {
+                weight = 0.0;
+            }
+        }
+    }
+    float envelope = amplitude_scale * source.amplitude * exp(-source.decay * abs(distance));
+    float angle = wavenumber * distance - source.frequency * time + source.phase;
+    float wave = envelope * sin(angle);
+    float slope = envelope * (wavenumber * cos(angle) - source.decay * sign(distance) * sin(angle));
+    return vec3(weight * wave, weight * slope * distance_gradient + wave * weight_gradient);
 }
 
 void main() {
-    float z1 = 0.01*sin(50.0*hypot(position.x, position.y+0.15)-t1);
-    float z2 = 0.01*sin(50.0*hypot(position.x, position.y-0.15)+t1);
-    float z = z1+z2;
-    vec3 n_pos = vec3(position.xy,  z);
+    vec3 z = vec3(0.0);
+    for (int i = 0; i < source_count; i++) {
+        z += source_wave(sources[i], position.xy);
+    }
+    vec3 n_pos = vec3(position.xy,  z.x);
+    vec3 n_normal = normalize(vec3(-z.yz, 1.0));
     gl_Position = projection * view * model * vec4(n_pos, 1.0);
     FragPos = vec3(model * vec4(n_pos, 1.0));
-    Normal = mat3(transpose(inverse(model))) * normal;
+    Normal = mat3(transpose(inverse(model))) * n_normal;
 }
\ No newline at end of file
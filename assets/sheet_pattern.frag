@@ -0,0 +1,30 @@
+#version 330 core
+
+out vec4 FragColor;
+in vec3 FragPos;
+in vec3 Normal;
+in vec2 TexCoords;
+
+uniform sampler2D pattern;
+uniform vec3 lightColor;
+uniform vec3 lightPos;
+uniform vec3 viewPos;
+uniform float specularStrength;
+uniform float shininess;
+
+void main() {
+    vec3 objectColor = texture(pattern, TexCoords).rgb;
+    vec3 norm = normalize(Normal);
+    vec3 lightDir = normalize(lightPos - FragPos);
+    float diff = max(dot(norm, lightDir), 0.0);
+    vec3 diffuse = diff * lightColor;
+    vec3 ambient = 0.2 * lightColor;
+
+    vec3 viewDir = normalize(viewPos - FragPos);
+    vec3 halfway = normalize(lightDir + viewDir);
+    float spec = diff > 0.0 ? pow(max(dot(norm, halfway), 0.0), shininess) : 0.0;
+    vec3 specular = specularStrength * spec * lightColor;
+
+    vec3 result = (ambient + diffuse) * objectColor + specular;
+    FragColor = vec4(result, 1.0);
+}
\ No newline at end of file
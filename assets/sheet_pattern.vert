@@ -0,0 +1,29 @@
+#version 330 core
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+uniform sampler2D pattern;
+uniform float displacement;
+
+in vec3 position;
+in vec2 tex_coords;
+out vec3 FragPos;
+out vec3 Normal;
+out vec2 TexCoords;
+
+float height_at(vec2 uv) {
+    return texture(pattern, uv).a * displacement;
+}
+
+void main() {
+    // The sheet spans one unit, so a texel is also 1/size along x and y.
+    vec2 texel = 1.0 / vec2(textureSize(pattern, 0));
+    float dx = (height_at(tex_coords + vec2(texel.x, 0.0)) - height_at(tex_coords - vec2(texel.x, 0.0))) / (2.0 * texel.x);
+    float dy = (height_at(tex_coords + vec2(0.0, texel.y)) - height_at(tex_coords - vec2(0.0, texel.y))) / (2.0 * texel.y);
+    vec3 n_pos = vec3(position.xy, height_at(tex_coords));
+    gl_Position = projection * view * model * vec4(n_pos, 1.0);
+    FragPos = vec3(model * vec4(n_pos, 1.0));
+    Normal = mat3(transpose(inverse(model))) * normalize(vec3(-dx, -dy, 1.0));
+    TexCoords = tex_coords;
+}
\ No newline at end of file
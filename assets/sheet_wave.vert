@@ -0,0 +1,18 @@
+#version 330 core
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+in vec3 position;
+in float height;
+in vec2 gradient;
+out vec3 FragPos;
+out vec3 Normal;
+
+void main() {
+    vec3 n_pos = vec3(position.xy, height);
+    gl_Position = projection * view * model * vec4(n_pos, 1.0);
+    FragPos = vec3(model * vec4(n_pos, 1.0));
+    Normal = mat3(transpose(inverse(model))) * normalize(vec3(-gradient, 1.0));
+}
\ No newline at end of file
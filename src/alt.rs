@@ -5,36 +5,59 @@ extern crate glutin_window;
 extern crate gl;
 extern crate nalgebra as na;
 extern crate nalgebra_glm as glm;
+extern crate nphysics3d;
+extern crate hound;
+extern crate rodio;
+extern crate toml;
 
 mod vertex;
+mod mesh;
 mod input;
 mod shapes;
+mod overlay;
+mod ui;
+mod rng;
+mod particles;
+mod wave;
+mod reaction_diffusion;
+mod audio;
+mod scene_file;
 
 use glium::*;
-use na::{Matrix4, geometry, Vector3, Vector2, Isometry3};
+use na::{Matrix4, geometry, Vector3, Vector2, Isometry3, Point2, Point3};
 use glm::*;
 use std::f32;
 
 use shapes::*;
+use mesh::Mesh;
 use input::*;
+use overlay::*;
+use ui::*;
+use particles::*;
+use vertex::SheetHeight;
+use wave::*;
+use reaction_diffusion::GrayScott;
+use rng::Rng;
+use audio::*;
+use scene_file::*;
+
+const SHEET_RES: usize = 60;
+/// Radius, in sheet widths, of the bump a click leaves and of the nodes each source holds.
+const POKE_RADIUS: f32 = 0.03;
+const POKE_HEIGHT: f32 = 0.05;
+const SOURCE_RADIUS: f32 = 0.02;
+const PATTERN_RES: usize = 128;
+const PATTERN_COLOR: [f32; 3] = [1.0, 0.85, 0.3];
+const SEED_RADIUS: f32 = 0.02;
+/// How much louder than its own amplitude the bass can make the ripple.
+const AUDIO_BOOST: f32 = 3.0;
 
 fn main() {
     use glium::{glutin, Surface};
-    let mut dimensions: [f32; 2] = [800.0, 600.0];
-    let mut event_loop = glutin::EventsLoop::new();
-
-     // let monitor = event_loop.get_available_monitors().nth(1);
-
-    let mut window = glutin::WindowBuilder::new();//.with_fullscreen(monitor);
-    window.window.dimensions = Some(glutin::dpi::LogicalSize::new(dimensions[0] as f64, dimensions[1] as f64));
-    let context = glutin::ContextBuilder::new().with_depth_buffer(24);
-    let display: Display = glium::Display::new(window, context, &event_loop).unwrap();
-    display.gl_window().hide_cursor(true);
-    //display.gl_window().grab_cursor(true);
+    let mut render = RenderSettings::default();
+    let mut camera = CameraSettings::new([0.0, 0.0, 2.0], -88.0, 0.0, 0.2);
+    let mut light = LightSettings::new((0.0, 20.0, -9.0), [1.0, 1.0, 1.0]);
 
-    let light_position: (f32, f32, f32) = (0.0, 20.0, -9.0);
-    let light_color:  [f32; 3] = [1.0, 1.0, 1.0];
-    
     let location: Vector3<f32> = Vector3::new(0.0,  0.0, -10.0);
     let pos = Isometry3::new(location, na::zero());
     let mut sheets: Vec<Sheet> = Vec::new();
@@ -45,13 +68,59 @@ fn main() {
             20.0
         )
     );
-    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+    // The two counter-phased sources the sheet has always had.
+    for &(y, frequency) in &[(-0.15, 1.0), (0.15, -1.0)] {
+        let mut source = WaveSource::new(WaveSourceKind::Point, Point2::new(0.0, y));
+        source.frequency = frequency;
+        sheets[0].add_source(source);
+    }
+
+    read_scene_from_args(|scene| {
+        scene.read_view(&mut render, &mut camera, &mut light)?;
+        let root = scene.get_root();
+        if let Some(tables) = root.get_tables("sheets")? {
+            if tables.is_empty() {
+                return Err(root.error("sheets", String::from("needs at least one sheet")));
+            }
+            sheets = tables.iter().map(Sheet::read).collect::<Result<_, _>>()?;
+        }
+        Ok(())
+    });
+    let mut dimensions: [f32; 2] = [render.width, render.height];
+    let mut event_loop = glutin::EventsLoop::new();
+
+     // let monitor = event_loop.get_available_monitors().nth(1);
+
+    let mut window = glutin::WindowBuilder::new();//.with_fullscreen(monitor);
+    window.window.dimensions = Some(glutin::dpi::LogicalSize::new(dimensions[0] as f64, dimensions[1] as f64));
+    let context = glutin::ContextBuilder::new().with_depth_buffer(24);
+    let display: Display = glium::Display::new(window, context, &event_loop).unwrap();
+    display.gl_window().hide_cursor(true);
+    //display.gl_window().grab_cursor(true);
+
+    let light_position: (f32, f32, f32) = light.position;
+    let light_color:  [f32; 3] = light.color;
 
     let sheet_vertex_shader_src   = include_str!("../assets/sheet.vert");
     let sheet_fragment_shader_src = include_str!("../assets/sheet.frag");
     let sheet_program: Program = glium::Program::from_source(&display, sheet_vertex_shader_src, sheet_fragment_shader_src, None).unwrap();
 
-    let projection = geometry::Perspective3::new(dimensions[0]/dimensions[1], f32::consts::PI/2.0, 0.1, 1000.0);
+    let wave_vertex_shader_src = include_str!("../assets/sheet_wave.vert");
+    let wave_program: Program = glium::Program::from_source(&display, wave_vertex_shader_src, sheet_fragment_shader_src, None).unwrap();
+
+    let pattern_vertex_shader_src   = include_str!("../assets/sheet_pattern.vert");
+    let pattern_fragment_shader_src = include_str!("../assets/sheet_pattern.frag");
+    let pattern_program: Program = glium::Program::from_source(&display, pattern_vertex_shader_src, pattern_fragment_shader_src, None).unwrap();
+
+    let particle_vertex_shader_src   = include_str!("../assets/particle.vert");
+    let particle_fragment_shader_src = include_str!("../assets/particle.frag");
+    let particle_program: Program = glium::Program::from_source(&display, particle_vertex_shader_src, particle_fragment_shader_src, None).unwrap();
+
+    let overlay_vertex_shader_src   = include_str!("../assets/overlay.vert");
+    let overlay_fragment_shader_src = include_str!("../assets/overlay.frag");
+    let overlay_program: Program = glium::Program::from_source(&display, overlay_vertex_shader_src, overlay_fragment_shader_src, None).unwrap();
+
+    let projection = geometry::Perspective3::new(render.get_aspect(), radianize(&render.fov), render.near, render.far);
 
     let mut closed = false;
 
@@ -65,23 +134,67 @@ fn main() {
     };
     let mut rotate_cubes: bool = false;
     let mut mouse_offset: Vector2<f32> = Vector2::new(0.0, 0.0);
-    let mouse_sensitivity: f32 = 0.5;
-    let mut pitch: f32 = 0.0;
-    let mut yaw: f32 = -88.0;
+    let mut mouse_sensitivity: f32 = 0.5;
+    let mut pitch: f32 = camera.pitch;
+    let mut yaw: f32 = camera.yaw;
 
-    let camera_speed: f32 = 0.2;
-    let mut camera_pos = glm::vec3(0.0, 0.0, 2.0);
+    let mut camera_speed: f32 = camera.speed;
+    let mut camera_pos = glm::vec3(camera.position[0], camera.position[1], camera.position[2]);
     let camera_up  = glm::vec3(0.0, 1.0, 0.0);
 
     let mut input_holder: Input = Input::new();
-    let sheet_verts = glium::VertexBuffer::new(&display, &shapes::get_sheet_verts(60)).unwrap();
+    let sheet_mesh = Mesh::rect(1.0, 1.0, SHEET_RES, SHEET_RES);
+    let sheet_verts = sheet_mesh.vertex_buffer(&display);
+    let indices = sheet_mesh.index_buffer(&display);
     let mut t1: f32 = 0.0;
-    let mut t2: f32 = 0.0;
+    let mut wave_speed: f32 = 0.01;
+    let mut wavenumber: f32 = 50.0;
+    let mut specular_strength: f32 = 0.5;
+    let mut shininess: f32 = 32.0;
+    let mut new_source_kind: WaveSourceKind = WaveSourceKind::Point;
+    let mut remove_source: bool = false;
+
+    let mut overlay: Overlay = Overlay::new(&display);
+    let mut ui: Ui = Ui::new();
+
+    // A fountain over each of the first two wave sources.
+    let mut particles: ParticleSystem = ParticleSystem::new(&display);
+    let fountains = [
+        particles.add_emitter(EmitterConfig::fountain(), Point3::origin()),
+        particles.add_emitter(EmitterConfig::fountain(), Point3::origin())
+    ];
+    let mut fountain_rate: f32 = EmitterConfig::fountain().rate;
+
+    // The simulated heightfield replaces the analytic ripple in `SheetMode::Waves`.
+    let mut solver: WaveSolver = WaveSolver::new(SHEET_RES);
+    let height_nodes: Vec<usize> = sheet_mesh.vertices.iter()
+        .map(|vertex| {
+            let (x, y, _) = vertex.get_position();
+            solver.node_at(x, y)
+        })
+        .collect();
+    let mut heights: Vec<SheetHeight> = vec![SheetHeight::new(0.0, (0.0, 0.0)); height_nodes.len()];
+    let height_buffer = glium::VertexBuffer::dynamic(&display, &heights).unwrap();
+    let mut sheet_mode: SheetMode = SheetMode::Ripple;
+    let mut wave_time: f32 = 0.0;
+    let mut poke: bool = false;
+    let mut cycle_boundary: bool = false;
+
+    // Reaction-diffusion colours and lifts the sheet in `SheetMode::Pattern`.
+    let mut pattern: GrayScott = GrayScott::new(PATTERN_RES);
+    let mut pattern_rng: Rng = Rng::new(7);
+    pattern.seed_random(&mut pattern_rng, 12, SEED_RADIUS);
+    let pattern_texture = texture::Texture2d::empty(&display, PATTERN_RES as u32, PATTERN_RES as u32).unwrap();
+    let mut pattern_displacement: f32 = 0.02;
+
+    let mut audio: Option<AudioPlayer> = audio_from_args();
+    let mut toggle_audio: bool = false;
+    let mut audio_seek: f32 = 0.0;
 
     while !closed {
 
         let mut target = display.draw();
-        target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+        target.clear_color_and_depth(render.get_clear_color(), 1.0);
 
         yaw   += mouse_offset.x;
         pitch += mouse_offset.y;
@@ -106,7 +219,72 @@ fn main() {
             &camera_up
         );
 
+        let amplitude_scale = match audio {
+            Some(ref audio) => 1.0 + AUDIO_BOOST * audio.get_bass(),
+            None => 1.0
+        };
+        if sheet_mode == SheetMode::Pattern {
+            pattern.update();
+            let low = match sheets.first() {
+                Some(sheet) => sheet.get_color(),
+                None => [0.0, 0.0, 0.0]
+            };
+            let image = texture::RawImage2d::from_raw_rgba(pattern.get_image([low[0] * 0.2, low[1] * 0.2, low[2] * 0.2], PATTERN_COLOR), (PATTERN_RES as u32, PATTERN_RES as u32));
+            pattern_texture.write(Rect { left: 0, bottom: 0, width: PATTERN_RES as u32, height: PATTERN_RES as u32 }, image);
+        }
+        if sheet_mode == SheetMode::Waves {
+            // Each source oscillates in place; a frequency of 1 gives the analytic wavenumber.
+            if let Some(sheet) = sheets.first() {
+                for source in sheet.get_sources() {
+                    let drive = amplitude_scale * source.amplitude * f32::sin(source.phase - source.frequency * wavenumber * solver.wave_speed * wave_time);
+                    solver.drive(source.position.x, source.position.y, SOURCE_RADIUS, drive);
+                }
+            }
+            solver.step(1.0 / 60.0);
+            wave_time += 1.0 / 60.0;
+            let nodes = solver.get_heights();
+            let gradients = solver.get_gradients();
+            for (height, node) in heights.iter_mut().zip(height_nodes.iter()) {
+                *height = SheetHeight::new(nodes[*node], gradients[*node]);
+            }
+            height_buffer.write(&heights);
+        }
+
         for sheet in sheets.iter_mut() {
+            if sheet_mode == SheetMode::Pattern {
+                let uniforms = uniform!{
+                    lightColor:  light_color,
+                    lightPos:    light_position,
+                    model:       na4_to_gl4(&sheet.get_model_transform()),
+                    view:        na4_to_gl4(&view),
+                    projection:  na4_to_gl4(&projection.as_matrix()),
+                    viewPos:     [camera_pos.x, camera_pos.y, camera_pos.z],
+                    specularStrength: specular_strength,
+                    shininess:   shininess,
+                    pattern:     pattern_texture.sampled()
+                        .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+                        .wrap_function(glium::uniforms::SamplerWrapFunction::Repeat),
+                    displacement: pattern_displacement
+                };
+                target.draw(&sheet_verts, &indices, &pattern_program, &uniforms, &params).unwrap();
+                continue;
+            }
+            if sheet_mode == SheetMode::Waves {
+                let uniforms = uniform!{
+                    window_size: dimensions,
+                    lightColor:  light_color,
+                    lightPos:    light_position,
+                    model:       na4_to_gl4(&sheet.get_model_transform()),
+                    view:        na4_to_gl4(&view),
+                    projection:  na4_to_gl4(&projection.as_matrix()),
+                    objectColor: sheet.get_color(),
+                    viewPos:     [camera_pos.x, camera_pos.y, camera_pos.z],
+                    specularStrength: specular_strength,
+                    shininess:   shininess
+                };
+                target.draw((&sheet_verts, &height_buffer), &indices, &wave_program, &uniforms, &params).unwrap();
+                continue;
+            }
             let uniforms = uniform!{
                 window_size: dimensions,
                 lightColor:  light_color,
@@ -115,12 +293,78 @@ fn main() {
                 view:        na4_to_gl4(&view),
                 projection:  na4_to_gl4(&projection.as_matrix()),
                 objectColor: sheet.get_color(),
-                t1: t1,
-                t2: t2
+                viewPos:     [camera_pos.x, camera_pos.y, camera_pos.z],
+                specularStrength: specular_strength,
+                shininess:   shininess,
+                time: t1,
+                wavenumber: wavenumber,
+                amplitude_scale: amplitude_scale
             };
-            target.draw(&sheet_verts, &indices, &sheet_program, &uniforms, &params).unwrap();
+            target.draw(&sheet_verts, &indices, &sheet_program, &sheet.source_uniforms(uniforms), &params).unwrap();
         }
-        
+
+        if let Some(sheet) = sheets.first() {
+            let model = sheet.get_model_transform();
+            for (i, fountain) in fountains.iter().enumerate() {
+                if let Some(emitter) = particles.get_emitter_mut(*fountain) {
+                    match sheet.get_sources().get(i) {
+                        Some(source) => {
+                            emitter.position = model.transform_point(&Point3::new(source.position.x, source.position.y, 0.0));
+                            emitter.active = true;
+                        },
+                        None => emitter.active = false
+                    }
+                    emitter.config.rate = fountain_rate;
+                }
+            }
+        }
+        particles.update(1.0 / 60.0);
+        particles.draw(&display, &mut target, &particle_program, na4_to_gl4(&view), na4_to_gl4(&projection.as_matrix()));
+
+        overlay.clear();
+        if ui.visible {
+            ui.begin(&mut overlay, "SHEET", dimensions);
+            ui.slider(&mut overlay, "WAVENUMBER", &mut wavenumber, 1.0, 150.0);
+            ui.slider(&mut overlay, "WAVE SPEED", &mut wave_speed, 0.0, 0.1);
+            ui.slider(&mut overlay, "SPECULAR", &mut specular_strength, 0.0, 2.0);
+            ui.slider(&mut overlay, "SHININESS", &mut shininess, 1.0, 256.0);
+            if let Some(sheet) = sheets.first_mut() {
+                ui.label(&mut overlay, &format!("SOURCES {}/{} NEW {}", sheet.get_sources().len(), MAX_WAVE_SOURCES, new_source_kind.get_name()));
+                // The newest source is the one being edited.
+                if let Some(source) = sheet.get_sources_mut().last_mut() {
+                    ui.label(&mut overlay, &format!("LAST {}", source.kind.get_name()));
+                    ui.slider(&mut overlay, "AMPLITUDE", &mut source.amplitude, 0.0, 0.05);
+                    ui.slider(&mut overlay, "FREQUENCY", &mut source.frequency, -3.0, 3.0);
+                    ui.slider(&mut overlay, "PHASE", &mut source.phase, 0.0, f32::consts::PI * 2.0);
+                    ui.slider(&mut overlay, "DECAY", &mut source.decay, 0.0, 10.0);
+                }
+            }
+            ui.slider(&mut overlay, "FOUNTAIN", &mut fountain_rate, 0.0, 1000.0);
+            ui.label(&mut overlay, &format!("MODE {}", sheet_mode.get_name()));
+            match sheet_mode {
+                SheetMode::Ripple => (),
+                SheetMode::Waves => {
+                    ui.slider(&mut overlay, "PROPAGATION", &mut solver.wave_speed, 0.05, 2.0);
+                    ui.slider(&mut overlay, "DAMPING", &mut solver.damping, 0.0, 5.0);
+                    ui.label(&mut overlay, &format!("BOUNDARY {}", solver.boundary.get_name()));
+                },
+                SheetMode::Pattern => {
+                    ui.slider(&mut overlay, "FEED", &mut pattern.feed, 0.01, 0.1);
+                    ui.slider(&mut overlay, "KILL", &mut pattern.kill, 0.04, 0.07);
+                    ui.slider(&mut overlay, "DIFFUSION U", &mut pattern.diffusion_u, 0.05, 0.25);
+                    ui.slider(&mut overlay, "DIFFUSION V", &mut pattern.diffusion_v, 0.02, 0.125);
+                    ui.slider(&mut overlay, "DISPLACEMENT", &mut pattern_displacement, 0.0, 0.1);
+                }
+            }
+            ui.slider(&mut overlay, "MOUSE SENS", &mut mouse_sensitivity, 0.05, 2.0);
+            ui.slider(&mut overlay, "CAMERA SPEED", &mut camera_speed, 0.01, 1.0);
+            ui.end();
+        }
+        else {
+            overlay.add_text(dimensions[0] / 2.0 - 6.0, dimensions[1] / 2.0 - 8.0, 2.0, "+", [1.0, 1.0, 1.0, 0.8]);
+        }
+        overlay.draw(&display, &mut target, &overlay_program, dimensions);
+
         target.finish().unwrap();
 
         mouse_offset.x = 0.0;
@@ -130,6 +374,7 @@ fn main() {
             match event {
                 glutin::Event::DeviceEvent { event, ..} => match event {
                     glutin::DeviceEvent::MouseMotion{ delta }  => match delta {
+                        _ if ui.visible => (),
                         _ => {
                             mouse_offset.x = ( delta.0 as f32) * mouse_sensitivity;
                             mouse_offset.y = (-delta.1 as f32) * mouse_sensitivity;
@@ -143,6 +388,16 @@ fn main() {
                         dimensions[0] = size.width  as f32;
                         dimensions[1] = size.height as f32;
                     },
+                    glutin::WindowEvent::CursorMoved{ position, .. } => {
+                        ui.handle_cursor(position.x as f32, position.y as f32);
+                    },
+                    glutin::WindowEvent::MouseInput{ state, button: glutin::MouseButton::Left, .. } => {
+                        let pressed = state == glutin::ElementState::Pressed;
+                        if pressed && !ui.visible {
+                            poke = true;
+                        }
+                        ui.handle_button(pressed);
+                    },
                     glutin::WindowEvent::KeyboardInput{ input, .. } => {
                         let latch = match input.state {
                             glutin::ElementState::Pressed  => true,
@@ -170,6 +425,35 @@ fn main() {
                         if input.virtual_keycode == Some(glutin::VirtualKeyCode::Escape) {
                             closed = true;
                         }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::M) {
+                            sheet_mode = sheet_mode.next();
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::B) {
+                            cycle_boundary = true;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::C) {
+                            solver.clear();
+                            pattern.clear();
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::U) {
+                            toggle_audio = true;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::LBracket) {
+                            audio_seek -= SEEK_SECONDS;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::RBracket) {
+                            audio_seek += SEEK_SECONDS;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::K) {
+                            new_source_kind = new_source_kind.next();
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::Back) {
+                            remove_source = true;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::Tab) {
+                            ui.visible = !ui.visible;
+                            display.gl_window().hide_cursor(!ui.visible);
+                        }
                     }
                     _ => ()
                 },
@@ -191,10 +475,73 @@ fn main() {
         if input_holder.up {
             camera_pos += camera_speed * camera_up;
         }
-        t1 += 0.01;
-        t2 += 0.015;
+        if cycle_boundary {
+            solver.boundary = solver.boundary.next();
+            cycle_boundary = false;
+        }
+        // Clicking where the crosshair meets the sheet adds a source aimed away from its centre,
+        // drops a bump into the wave simulation or seeds the reaction-diffusion pattern.
+        if poke {
+            if let Some(sheet) = sheets.first_mut() {
+                if let Some(hit) = sheet.intersect(&Point3::from_coordinates(camera_pos), &camera_front) {
+                    match sheet_mode {
+                        SheetMode::Ripple => {
+                            let mut source = WaveSource::new(new_source_kind, hit);
+                            if hit.coords.norm_squared() > 0.0 {
+                                source.direction = hit.coords;
+                            }
+                            sheet.add_source(source);
+                        },
+                        SheetMode::Waves => solver.perturb(hit.x, hit.y, POKE_RADIUS, POKE_HEIGHT),
+                        SheetMode::Pattern => pattern.seed(hit.x + 0.5, hit.y + 0.5, SEED_RADIUS)
+                    }
+                }
+            }
+            poke = false;
+        }
+        if remove_source {
+            if let Some(sheet) = sheets.first_mut() {
+                sheet.get_sources_mut().pop();
+            }
+            remove_source = false;
+        }
+        if let Some(ref mut audio) = audio {
+            audio.apply_input(toggle_audio, audio_seek, 1.0 / 60.0);
+        }
+        toggle_audio = false;
+        audio_seek = 0.0;
+        t1 += wave_speed;
+    }
+
+}
+
+/// What drives the sheet's shape and colour.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum SheetMode {
+    /// The analytic sum of the sheet's wave sources.
+    Ripple,
+    /// The CPU wave solver, driven by the same sources.
+    Waves,
+    /// A Gray-Scott reaction-diffusion pattern.
+    Pattern
+}
+
+impl SheetMode {
+    fn get_name(&self) -> &'static str {
+        match *self {
+            SheetMode::Ripple  => "RIPPLE",
+            SheetMode::Waves   => "WAVES",
+            SheetMode::Pattern => "PATTERN"
+        }
     }
 
+    fn next(&self) -> Self {
+        match *self {
+            SheetMode::Ripple  => SheetMode::Waves,
+            SheetMode::Waves   => SheetMode::Pattern,
+            SheetMode::Pattern => SheetMode::Ripple
+        }
+    }
 }
 
 fn radianize(n: &f32) -> f32 {
@@ -0,0 +1,313 @@
+use hound::{SampleFormat, WavReader};
+use rodio::{Device, Sink};
+use rodio::buffer::SamplesBuffer;
+use std::env;
+use std::f32;
+use std::io;
+use std::time::Instant;
+
+/// Samples in each FFT window.
+const FFT_SIZE: usize = 2048;
+pub const BAND_COUNT: usize = 8;
+/// How far the seek keys jump, in seconds.
+pub const SEEK_SECONDS: f32 = 5.0;
+/// Lower edge of every band in hertz, spaced roughly an octave apart; the last band runs to Nyquist.
+const BAND_EDGES: [f32; BAND_COUNT] = [20.0, 60.0, 150.0, 400.0, 1000.0, 2500.0, 6000.0, 12000.0];
+
+/// A WAV file decoded to interleaved floats between -1 and 1.
+pub struct AudioTrack {
+    samples: Vec<f32>,
+    channels: u16,
+    sample_rate: u32
+}
+
+impl AudioTrack {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid = |err: ::hound::Error| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, err));
+        let mut reader = WavReader::open(path).map_err(invalid)?;
+        let spec = reader.spec();
+        let samples: Vec<f32> = match spec.sample_format {
+            SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>().map_err(invalid)?,
+            SampleFormat::Int => {
+                let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader.samples::<i32>()
+                    .map(|sample| sample.map(|s| s as f32 / scale))
+                    .collect::<Result<_, _>>()
+                    .map_err(invalid)?
+            }
+        };
+        Ok(AudioTrack {
+            samples,
+            channels: spec.channels,
+            sample_rate: spec.sample_rate
+        })
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length in seconds.
+    pub fn get_duration(&self) -> f32 {
+        self.frame_count() as f32 / self.sample_rate as f32
+    }
+
+    fn frame_count(&self) -> usize {
+        self.samples.len() / usize::max(self.channels as usize, 1)
+    }
+
+    /// The channels averaged at frame `frame`, or silence outside the track.
+    fn mono(&self, frame: usize) -> f32 {
+        let channels = self.channels as usize;
+        match self.samples.get(frame * channels..(frame + 1) * channels) {
+            Some(frame) => frame.iter().sum::<f32>() / channels as f32,
+            None => 0.0
+        }
+    }
+}
+
+/// Plays a track and turns whatever is under the playhead into band energies each frame.
+///
+/// In offline mode nothing is played and the playhead only moves by the `dt` handed to
+/// `update`, so frames rendered one by one stay in sync with the music.
+pub struct AudioPlayer {
+    track: AudioTrack,
+    offline: bool,
+    device: Option<Device>,
+    sink: Option<Sink>,
+    position: f32,
+    started: Option<(Instant, f32)>,
+    bands: [f32; BAND_COUNT],
+    peaks: [f32; BAND_COUNT],
+    /// Fraction of a band's energy kept from one frame to the next when it falls.
+    pub release: f32
+}
+
+impl AudioPlayer {
+    pub fn new(track: AudioTrack, offline: bool) -> Self {
+        AudioPlayer {
+            track,
+            offline,
+            device: if offline { None } else { ::rodio::default_output_device() },
+            sink: None,
+            position: 0.0,
+            started: None,
+            bands: [0.0; BAND_COUNT],
+            peaks: [1.0e-6; BAND_COUNT],
+            release: 0.85
+        }
+    }
+
+    pub fn get_track(&self) -> &AudioTrack {
+        &self.track
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Seconds from the start of the track.
+    pub fn get_position(&self) -> f32 {
+        self.position
+    }
+
+    /// Energy in every band, scaled by the loudest that band has been so far to lie in 0 to 1.
+    pub fn get_bands(&self) -> &[f32; BAND_COUNT] {
+        &self.bands
+    }
+
+    /// The lowest two bands, where the kick drum and bass live.
+    pub fn get_bass(&self) -> f32 {
+        (self.bands[0] + self.bands[1]) * 0.5
+    }
+
+    pub fn get_mid(&self) -> f32 {
+        (self.bands[3] + self.bands[4]) * 0.5
+    }
+
+    pub fn get_treble(&self) -> f32 {
+        (self.bands[6] + self.bands[7]) * 0.5
+    }
+
+    /// Mean energy over every band.
+    pub fn get_level(&self) -> f32 {
+        self.bands.iter().sum::<f32>() / BAND_COUNT as f32
+    }
+
+    pub fn play(&mut self) {
+        if self.is_playing() {
+            return;
+        }
+        self.started = Some((Instant::now(), self.position));
+        if let Some(ref device) = self.device {
+            let channels = usize::max(self.track.channels as usize, 1);
+            let start = usize::min((self.position * self.track.sample_rate as f32) as usize * channels, self.track.samples.len());
+            let sink = Sink::new(device);
+            sink.append(SamplesBuffer::new(self.track.channels, self.track.sample_rate, self.track.samples[start..].to_vec()));
+            self.sink = Some(sink);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.started = None;
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+    }
+
+    /// Switches between playing aloud by the wall clock and silently following `update`'s `dt`,
+    /// e.g. while frames are captured slower than real time.
+    pub fn set_offline(&mut self, offline: bool) {
+        let playing = self.is_playing();
+        self.pause();
+        self.offline = offline;
+        self.device = if offline { None } else { ::rodio::default_output_device() };
+        if playing {
+            self.play();
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        if self.is_playing() {
+            self.pause();
+        } else {
+            self.play();
+        }
+    }
+
+    /// Jumps to `seconds`, clamped to the track, carrying on playing if it was.
+    pub fn seek(&mut self, seconds: f32) {
+        let playing = self.is_playing();
+        self.pause();
+        self.position = f32::min(f32::max(seconds, 0.0), self.track.get_duration());
+        if playing {
+            self.play();
+        }
+    }
+
+    /// Handles a frame's play/pause toggle and seek, in seconds, then updates by `dt`.
+    pub fn apply_input(&mut self, toggle: bool, seek: f32, dt: f32) {
+        if toggle {
+            self.toggle();
+        }
+        if seek != 0.0 {
+            let position = self.position;
+            self.seek(position + seek);
+        }
+        self.update(dt);
+    }
+
+    /// Moves the playhead while playing, by the wall clock or by `dt` offline, and analyses the
+    /// window ending there.
+    pub fn update(&mut self, dt: f32) {
+        if let Some((instant, from)) = self.started {
+            if self.offline {
+                self.position += dt;
+            } else {
+                let elapsed = instant.elapsed();
+                self.position = from + elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1.0e-9;
+            }
+        }
+        if self.position >= self.track.get_duration() {
+            self.position = self.track.get_duration();
+            self.pause();
+        }
+        self.analyse();
+    }
+
+    fn analyse(&mut self) {
+        let end = (self.position * self.track.sample_rate as f32) as usize;
+        let start = end.saturating_sub(FFT_SIZE);
+        let mut re: Vec<f32> = vec![0.0; FFT_SIZE];
+        let mut im: Vec<f32> = vec![0.0; FFT_SIZE];
+        for (i, frame) in (start..end).enumerate() {
+            // A Hann window keeps the cut at the window's edges from smearing into every bin.
+            let hann = 0.5 - 0.5 * f32::cos(2.0 * f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32);
+            re[i] = self.track.mono(frame) * hann;
+        }
+        fft(&mut re, &mut im);
+
+        let bin_width = self.track.sample_rate as f32 / FFT_SIZE as f32;
+        let mut energies = [0.0; BAND_COUNT];
+        let mut counts = [0usize; BAND_COUNT];
+        for bin in 1..FFT_SIZE / 2 {
+            let frequency = bin as f32 * bin_width;
+            if frequency < BAND_EDGES[0] {
+                continue;
+            }
+            let band = BAND_EDGES.iter().rposition(|&edge| frequency >= edge).unwrap_or(0);
+            energies[band] += re[bin] * re[bin] + im[bin] * im[bin];
+            counts[band] += 1;
+        }
+        for band in 0..BAND_COUNT {
+            let energy = if counts[band] > 0 { f32::sqrt(energies[band] / counts[band] as f32) } else { 0.0 };
+            self.peaks[band] = f32::max(self.peaks[band], energy);
+            let normalised = energy / self.peaks[band];
+            self.bands[band] = f32::max(normalised, self.bands[band] * self.release);
+        }
+    }
+}
+
+/// In-place iterative radix-2 FFT; both slices must have the same power-of-two length.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut length = 2;
+    while length <= n {
+        let angle = -2.0 * f32::consts::PI / length as f32;
+        let (w_re, w_im) = (f32::cos(angle), f32::sin(angle));
+        for start in (0..n).step_by(length) {
+            let (mut t_re, mut t_im) = (1.0f32, 0.0f32);
+            for k in 0..length / 2 {
+                let (a, b) = (start + k, start + k + length / 2);
+                let u_re = re[b] * t_re - im[b] * t_im;
+                let u_im = re[b] * t_im + im[b] * t_re;
+                re[b] = re[a] - u_re;
+                im[b] = im[a] - u_im;
+                re[a] += u_re;
+                im[a] += u_im;
+                let next_re = t_re * w_re - t_im * w_im;
+                t_im = t_re * w_im + t_im * w_re;
+                t_re = next_re;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+/// Reads `--audio PATH` and `--offline` from the command line and starts playing the track.
+pub fn audio_from_args() -> Option<AudioPlayer> {
+    let args: Vec<String> = env::args().collect();
+    let path = match args.iter().position(|arg| arg == "--audio").and_then(|i| args.get(i + 1)) {
+        Some(path) => path,
+        None => return None
+    };
+    let offline = args.iter().any(|arg| arg == "--offline");
+    match AudioTrack::load(path) {
+        Ok(track) => {
+            let mut player = AudioPlayer::new(track, offline);
+            player.play();
+            Some(player)
+        },
+        Err(err) => {
+            eprintln!("could not load {}: {}", path, err);
+            None
+        }
+    }
+}
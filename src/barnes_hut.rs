@@ -0,0 +1,339 @@
+use nphysics3d::solver::IntegrationParameters;
+use nphysics3d::force_generator::ForceGenerator;
+use nphysics3d::object::{BodyHandle, BodySet};
+use nphysics3d::algebra::Force3;
+use na::{Point3, Vector3};
+use rayon::prelude::*;
+use std::f32;
+
+const MAX_DEPTH: usize = 32;
+
+struct OctreeNode {
+    center: Point3<f32>,
+    half_size: f32,
+    count: usize,
+    mass: f32,
+    center_of_mass: Point3<f32>,
+    // Index of the first of eight consecutive children, 0 for a leaf (the root is never a child).
+    first_child: usize,
+    body: Option<usize>
+}
+
+impl OctreeNode {
+    fn new(center: Point3<f32>, half_size: f32) -> Self {
+        OctreeNode {
+            center,
+            half_size,
+            count: 0,
+            mass: 0.0,
+            center_of_mass: Point3::origin(),
+            first_child: 0,
+            body: None
+        }
+    }
+
+    fn octant(&self, position: &Point3<f32>) -> usize {
+        let mut octant = 0;
+        if position.x >= self.center.x { octant |= 1; }
+        if position.y >= self.center.y { octant |= 2; }
+        if position.z >= self.center.z { octant |= 4; }
+        octant
+    }
+}
+
+/// Mass-aggregating octree over a set of point masses.
+pub struct Octree {
+    nodes: Vec<OctreeNode>
+}
+
+impl Octree {
+    pub fn new(positions: &[Point3<f32>], masses: &[f32]) -> Self {
+        let mut mins = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut maxs = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+        for position in positions {
+            for axis in 0..3 {
+                mins[axis] = f32::min(mins[axis], position[axis]);
+                maxs[axis] = f32::max(maxs[axis], position[axis]);
+            }
+        }
+        let root = if positions.is_empty() {
+            OctreeNode::new(Point3::origin(), 1.0)
+        } else {
+            let extents = maxs - mins;
+            let half_size = f32::max(extents.x, f32::max(extents.y, extents.z)) * 0.5 + 1.0e-3;
+            OctreeNode::new(na::center(&mins, &maxs), half_size)
+        };
+
+        let mut octree = Octree {
+            nodes: vec![root]
+        };
+        for body in 0..positions.len() {
+            octree.insert(0, body, positions, masses, 0);
+        }
+        octree
+    }
+
+    pub fn get_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn get_total_mass(&self) -> f32 {
+        self.nodes[0].mass
+    }
+
+    fn insert(&mut self, node: usize, body: usize, positions: &[Point3<f32>], masses: &[f32], depth: usize) {
+        let position = positions[body];
+        let mass = masses[body];
+        let was_empty = self.nodes[node].count == 0;
+        {
+            let n = &mut self.nodes[node];
+            let total = n.mass + mass;
+            // Averaging into an empty node would round, leaving a lone body a small pull on itself.
+            if was_empty {
+                n.center_of_mass = position;
+            } else if total > 0.0 {
+                n.center_of_mass = Point3::from_coordinates((n.center_of_mass.coords * n.mass + position.coords * mass) / total);
+            }
+            n.mass = total;
+            n.count += 1;
+        }
+        if was_empty {
+            self.nodes[node].body = Some(body);
+            return;
+        }
+        if self.nodes[node].first_child == 0 {
+            // Coincident bodies cannot be separated; keep them merged in the leaf aggregate.
+            if depth >= MAX_DEPTH {
+                return;
+            }
+            self.subdivide(node);
+            if let Some(existing) = self.nodes[node].body.take() {
+                let child = self.nodes[node].first_child + self.nodes[node].octant(&positions[existing]);
+                self.insert(child, existing, positions, masses, depth + 1);
+            }
+        }
+        let child = self.nodes[node].first_child + self.nodes[node].octant(&position);
+        self.insert(child, body, positions, masses, depth + 1);
+    }
+
+    fn subdivide(&mut self, node: usize) {
+        let center = self.nodes[node].center;
+        let quarter = self.nodes[node].half_size * 0.5;
+        let first_child = self.nodes.len();
+        for octant in 0..8 {
+            let offset = Vector3::new(
+                if octant & 1 != 0 { quarter } else { -quarter },
+                if octant & 2 != 0 { quarter } else { -quarter },
+                if octant & 4 != 0 { quarter } else { -quarter }
+            );
+            self.nodes.push(OctreeNode::new(center + offset, quarter));
+        }
+        self.nodes[node].first_child = first_child;
+    }
+
+    /// Gravitational acceleration at `position` with G = 1.
+    ///
+    /// A node is treated as a single mass when its size over its distance is below `theta`;
+    /// `theta = 0` visits every body. The softening length keeps close encounters finite and
+    /// makes a body's pull on itself vanish.
+    pub fn acceleration_at(&self, position: &Point3<f32>, theta: f32, softening: f32) -> Vector3<f32> {
+        let mut acceleration: Vector3<f32> = na::zero();
+        let mut stack: Vec<usize> = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.count == 0 {
+                continue;
+            }
+            let delta = node.center_of_mass - position;
+            let dist_sq = delta.norm_squared();
+            let size = node.half_size * 2.0;
+            if node.first_child == 0 || size * size < theta * theta * dist_sq {
+                acceleration += delta * (node.mass * inverse_cube(dist_sq, softening));
+            } else {
+                for octant in 0..8 {
+                    stack.push(node.first_child + octant);
+                }
+            }
+        }
+        acceleration
+    }
+}
+
+fn inverse_cube(dist_sq: f32, softening: f32) -> f32 {
+    let r_sq = dist_sq + softening * softening;
+    if r_sq <= 0.0 {
+        return 0.0;
+    }
+    1.0 / (r_sq * r_sq.sqrt())
+}
+
+/// Exact O(n²) accelerations with G = 1, the reference `Octree::acceleration_at` approximates.
+pub fn brute_force_accelerations(positions: &[Point3<f32>], masses: &[f32], softening: f32) -> Vec<Vector3<f32>> {
+    positions.par_iter()
+        .map(|position| {
+            let mut acceleration: Vector3<f32> = na::zero();
+            for (other, mass) in positions.iter().zip(masses.iter()) {
+                let delta = other - position;
+                acceleration += delta * (mass * inverse_cube(delta.norm_squared(), softening));
+            }
+            acceleration
+        })
+        .collect()
+}
+
+pub fn barnes_hut_accelerations(positions: &[Point3<f32>], masses: &[f32], theta: f32, softening: f32) -> Vec<Vector3<f32>> {
+    let octree = Octree::new(positions, masses);
+    positions.par_iter()
+        .map(|position| octree.acceleration_at(position, theta, softening))
+        .collect()
+}
+
+/// Mutual gravity between every registered body, approximated with a Barnes–Hut octree.
+pub struct NBodyGravity {
+    parts: Vec<BodyHandle>,
+    gravitational_constant: f32,
+    theta: f32,
+    softening: f32
+}
+
+impl NBodyGravity {
+    pub fn new(parts: Vec<BodyHandle>, gravitational_constant: f32, theta: f32) -> Self {
+        NBodyGravity {
+            parts,
+            gravitational_constant,
+            theta,
+            softening: 0.5
+        }
+    }
+
+    pub fn add_body_part(&mut self, body: BodyHandle) {
+        self.parts.push(body);
+    }
+
+    pub fn remove_body_part(&mut self, body: BodyHandle) {
+        self.parts.retain(|part| *part != body);
+    }
+
+    pub fn get_parts(&self) -> &[BodyHandle] {
+        &self.parts
+    }
+
+    pub fn get_gravitational_constant(&self) -> f32 {
+        self.gravitational_constant
+    }
+
+    pub fn set_gravitational_constant(&mut self, gravitational_constant: f32) {
+        self.gravitational_constant = gravitational_constant;
+    }
+
+    pub fn get_theta(&self) -> f32 {
+        self.theta
+    }
+
+    pub fn set_theta(&mut self, theta: f32) {
+        self.theta = theta;
+    }
+
+    pub fn set_softening(&mut self, softening: f32) {
+        self.softening = softening;
+    }
+}
+
+impl ForceGenerator<f32> for NBodyGravity {
+    fn apply(&mut self, _: &IntegrationParameters<f32>, bodies: &mut BodySet<f32>) -> bool {
+        let mut handles: Vec<BodyHandle> = Vec::with_capacity(self.parts.len());
+        let mut positions: Vec<Point3<f32>> = Vec::with_capacity(self.parts.len());
+        let mut masses: Vec<f32> = Vec::with_capacity(self.parts.len());
+        for handle in &self.parts {
+            if bodies.contains(*handle) {
+                let part = bodies.body_part(*handle);
+                handles.push(*handle);
+                positions.push(part.center_of_mass());
+                masses.push(part.inertia().linear);
+            }
+        }
+
+        let accelerations = barnes_hut_accelerations(&positions, &masses, self.theta, self.softening);
+        for (i, handle) in handles.iter().enumerate() {
+            let force = accelerations[i] * (self.gravitational_constant * masses[i]);
+            if force != na::zero() {
+                bodies.body_mut(*handle).activate();
+                bodies.body_part_mut(*handle).apply_force(&Force3::linear(force));
+            }
+        }
+        true
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOFTENING: f32 = 0.1;
+
+    /// A xorshift stream, enough to scatter bodies the same way on every run.
+    struct Scatter(u64);
+
+    impl Scatter {
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            min + (max - min) * ((self.0 >> 40) as f32 / (1u64 << 24) as f32)
+        }
+    }
+
+    fn random_bodies(count: usize, seed: u64) -> (Vec<Point3<f32>>, Vec<f32>) {
+        let mut rng = Scatter(0x9E37_79B9_7F4A_7C15 ^ seed);
+        let positions = (0..count)
+            .map(|_| Point3::new(rng.range(-10.0, 10.0), rng.range(-10.0, 10.0), rng.range(-10.0, 10.0)))
+            .collect();
+        let masses = (0..count).map(|_| rng.range(0.5, 2.0)).collect();
+        (positions, masses)
+    }
+
+    /// Largest error over every body, relative to the root mean square of the exact accelerations.
+    fn relative_error(approximate: &[Vector3<f32>], exact: &[Vector3<f32>]) -> f32 {
+        let rms = f32::sqrt(exact.iter().map(|a| a.norm_squared()).sum::<f32>() / exact.len() as f32);
+        approximate.iter()
+            .zip(exact.iter())
+            .map(|(a, b)| (a - b).norm())
+            .fold(0.0, f32::max) / f32::max(rms, 1.0e-6)
+    }
+
+    #[test]
+    fn theta_zero_matches_brute_force() {
+        for &(count, seed) in &[(1, 1), (2, 2), (7, 3), (300, 4)] {
+            let (positions, masses) = random_bodies(count, seed);
+            let exact = brute_force_accelerations(&positions, &masses, SOFTENING);
+            let approximate = barnes_hut_accelerations(&positions, &masses, 0.0, SOFTENING);
+            assert!(relative_error(&approximate, &exact) < 1.0e-4, "{} bodies", count);
+        }
+    }
+
+    /// The monopole approximation at the theta the scene uses stays within 5% of the exact pull.
+    #[test]
+    fn theta_half_stays_close_to_brute_force() {
+        let (positions, masses) = random_bodies(1000, 5);
+        let exact = brute_force_accelerations(&positions, &masses, SOFTENING);
+        let approximate = barnes_hut_accelerations(&positions, &masses, 0.5, SOFTENING);
+        assert!(relative_error(&approximate, &exact) < 0.05);
+    }
+
+    #[test]
+    fn coincident_bodies_merge() {
+        let mut positions = vec![Point3::new(1.0, 2.0, -3.0); 4];
+        positions.push(Point3::new(-4.0, 0.0, 2.0));
+        let masses = vec![1.0; positions.len()];
+        let octree = Octree::new(&positions, &masses);
+        assert_eq!(octree.get_total_mass(), 5.0);
+        let exact = brute_force_accelerations(&positions, &masses, SOFTENING);
+        let approximate = barnes_hut_accelerations(&positions, &masses, 0.0, SOFTENING);
+        assert!(relative_error(&approximate, &exact) < 1.0e-4);
+    }
+
+    #[test]
+    fn empty_set_has_no_accelerations() {
+        assert!(barnes_hut_accelerations(&[], &[], 0.5, SOFTENING).is_empty());
+        assert_eq!(Octree::new(&[], &[]).get_total_mass(), 0.0);
+    }
+}
@@ -0,0 +1,301 @@
+use nphysics3d::solver::IntegrationParameters;
+use nphysics3d::force_generator::ForceGenerator;
+use nphysics3d::object::{BodyHandle, BodySet};
+use nphysics3d::algebra::Force3;
+use na::{Isometry3, Point3, Vector3};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::f32;
+
+use scene_file::{SceneError, SceneTable};
+
+/// Tunables for the flock; every weight scales a steering acceleration.
+#[derive(Copy, Clone, Debug)]
+pub struct BoidsConfig {
+    pub neighbour_radius:   f32,
+    pub separation_radius:  f32,
+    pub separation_weight:  f32,
+    pub alignment_weight:   f32,
+    pub cohesion_weight:    f32,
+    pub goal_weight:        f32,
+    pub avoidance_weight:   f32,
+    pub avoidance_distance: f32,
+    pub max_speed:          f32,
+    pub max_acceleration:   f32
+}
+
+impl Default for BoidsConfig {
+    fn default() -> Self {
+        BoidsConfig {
+            neighbour_radius:   4.0,
+            separation_radius:  1.5,
+            separation_weight:  8.0,
+            alignment_weight:   1.0,
+            cohesion_weight:    0.5,
+            goal_weight:        0.2,
+            avoidance_weight:   20.0,
+            avoidance_distance: 3.0,
+            max_speed:          10.0,
+            max_acceleration:   30.0
+        }
+    }
+}
+
+impl BoidsConfig {
+    /// Overwrites whatever the scene's `[boids]` table sets, keeping the rest.
+    pub fn read(&mut self, table: &SceneTable) -> Result<(), SceneError> {
+        table.expect_keys(&[
+            "neighbour_radius", "separation_radius", "separation_weight", "alignment_weight", "cohesion_weight",
+            "goal_weight", "avoidance_weight", "avoidance_distance", "max_speed", "max_acceleration"
+        ])?;
+        if let Some(radius) = table.get_f32_in("neighbour_radius", 1.0e-3, f32::MAX)? {
+            self.neighbour_radius = radius;
+        }
+        if let Some(radius) = table.get_f32_in("separation_radius", 0.0, f32::MAX)? {
+            self.separation_radius = radius;
+        }
+        if let Some(weight) = table.get_f32_in("separation_weight", 0.0, f32::MAX)? {
+            self.separation_weight = weight;
+        }
+        if let Some(weight) = table.get_f32_in("alignment_weight", 0.0, f32::MAX)? {
+            self.alignment_weight = weight;
+        }
+        if let Some(weight) = table.get_f32_in("cohesion_weight", 0.0, f32::MAX)? {
+            self.cohesion_weight = weight;
+        }
+        if let Some(weight) = table.get_f32_in("goal_weight", 0.0, f32::MAX)? {
+            self.goal_weight = weight;
+        }
+        if let Some(weight) = table.get_f32_in("avoidance_weight", 0.0, f32::MAX)? {
+            self.avoidance_weight = weight;
+        }
+        if let Some(distance) = table.get_f32_in("avoidance_distance", 0.0, f32::MAX)? {
+            self.avoidance_distance = distance;
+        }
+        if let Some(speed) = table.get_f32_in("max_speed", 0.0, f32::MAX)? {
+            self.max_speed = speed;
+        }
+        if let Some(acceleration) = table.get_f32_in("max_acceleration", 0.0, f32::MAX)? {
+            self.max_acceleration = acceleration;
+        }
+        Ok(())
+    }
+}
+
+/// Buckets points into cubic cells so neighbour queries only visit nearby cells.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32, i32), Vec<usize>>
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32, positions: &[Point3<f32>]) -> Self {
+        let mut hash = SpatialHash {
+            cell_size,
+            cells: HashMap::new()
+        };
+        for (i, position) in positions.iter().enumerate() {
+            let cell = hash.cell_of(position);
+            hash.cells.entry(cell).or_insert_with(Vec::new).push(i);
+        }
+        hash
+    }
+
+    fn cell_of(&self, position: &Point3<f32>) -> (i32, i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+            (position.z / self.cell_size).floor() as i32
+        )
+    }
+
+    /// Indices of the points within `radius` of `position`, excluding `exclude`.
+    pub fn neighbours(&self, positions: &[Point3<f32>], position: &Point3<f32>, radius: f32, exclude: usize, out: &mut Vec<usize>) {
+        out.clear();
+        let reach = (radius / self.cell_size).ceil() as i32;
+        let (cx, cy, cz) = self.cell_of(position);
+        for x in (cx - reach)..(cx + reach + 1) {
+            for y in (cy - reach)..(cy + reach + 1) {
+                for z in (cz - reach)..(cz + reach + 1) {
+                    if let Some(cell) = self.cells.get(&(x, y, z)) {
+                        for &i in cell {
+                            if i != exclude && na::distance_squared(&positions[i], position) <= radius * radius {
+                                out.push(i);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A box the flock steers around, from its pose and half extents.
+#[derive(Copy, Clone, Debug)]
+struct Obstacle {
+    pose: Isometry3<f32>,
+    half_extents: Vector3<f32>
+}
+
+impl Obstacle {
+    /// The direction from the nearest point of the box out to `position`, and how far away that
+    /// point is; negative inside.
+    fn clearance(&self, position: &Point3<f32>) -> (Vector3<f32>, f32) {
+        let local = self.pose.inverse() * position;
+        let mut nearest = local;
+        for axis in 0..3 {
+            nearest[axis] = f32::min(f32::max(local[axis], -self.half_extents[axis]), self.half_extents[axis]);
+        }
+        let away = local - nearest;
+        if away.norm_squared() > 1.0e-12 {
+            return (self.pose.rotation * away.normalize(), away.norm());
+        }
+        // Inside, leave through the nearest face.
+        let mut axis = 0;
+        let mut depth = f32::max_value();
+        for a in 0..3 {
+            let d = self.half_extents[a] - local[a].abs();
+            if d < depth {
+                axis = a;
+                depth = d;
+            }
+        }
+        let mut normal: Vector3<f32> = na::zero();
+        normal[axis] = if local[axis] < 0.0 { -1.0 } else { 1.0 };
+        (self.pose.rotation * normal, -depth)
+    }
+}
+
+/// Separation, alignment and cohesion steering for a set of bodies.
+pub struct Boids {
+    parts: Vec<BodyHandle>,
+    config: BoidsConfig,
+    goal: Option<Point3<f32>>,
+    obstacles: Vec<Obstacle>
+}
+
+impl Boids {
+    pub fn new(parts: Vec<BodyHandle>, config: BoidsConfig) -> Self {
+        Boids {
+            parts,
+            config,
+            goal: None,
+            obstacles: Vec::new()
+        }
+    }
+
+    pub fn add_body_part(&mut self, body: BodyHandle) {
+        self.parts.push(body);
+    }
+
+    pub fn remove_body_part(&mut self, body: BodyHandle) {
+        self.parts.retain(|part| *part != body);
+    }
+
+    pub fn get_parts(&self) -> &[BodyHandle] {
+        &self.parts
+    }
+
+    pub fn get_config(&self) -> &BoidsConfig {
+        &self.config
+    }
+
+    pub fn get_config_mut(&mut self) -> &mut BoidsConfig {
+        &mut self.config
+    }
+
+    pub fn set_goal(&mut self, goal: Option<Point3<f32>>) {
+        self.goal = goal;
+    }
+
+    /// Adds a box the flock steers around, such as a wall or the ground.
+    pub fn add_obstacle(&mut self, pose: Isometry3<f32>, half_extents: Vector3<f32>) {
+        self.obstacles.push(Obstacle {
+            pose,
+            half_extents
+        });
+    }
+
+    fn steering(&self, i: usize, positions: &[Point3<f32>], velocities: &[Vector3<f32>], hash: &SpatialHash) -> Vector3<f32> {
+        let config = &self.config;
+        let position = positions[i];
+        let velocity = velocities[i];
+        let mut neighbours: Vec<usize> = Vec::new();
+        hash.neighbours(positions, &position, config.neighbour_radius, i, &mut neighbours);
+
+        let mut steering: Vector3<f32> = na::zero();
+        if !neighbours.is_empty() {
+            let mut separation: Vector3<f32> = na::zero();
+            let mut average_velocity: Vector3<f32> = na::zero();
+            let mut average_position: Vector3<f32> = na::zero();
+            for &j in &neighbours {
+                let away = position - positions[j];
+                let dist_sq = away.norm_squared();
+                if dist_sq < config.separation_radius * config.separation_radius && dist_sq > 1.0e-6 {
+                    separation += away / dist_sq;
+                }
+                average_velocity += velocities[j];
+                average_position += positions[j].coords;
+            }
+            let count = neighbours.len() as f32;
+            steering += separation * config.separation_weight;
+            steering += (average_velocity / count - velocity) * config.alignment_weight;
+            steering += (average_position / count - position.coords) * config.cohesion_weight;
+        }
+
+        if let Some(goal) = self.goal {
+            steering += (goal - position) * config.goal_weight;
+        }
+
+        for obstacle in &self.obstacles {
+            let (away, clearance) = obstacle.clearance(&position);
+            if clearance < config.avoidance_distance {
+                let urgency = 1.0 - f32::max(clearance, 0.0) / config.avoidance_distance;
+                steering += away * urgency * config.avoidance_weight;
+            }
+        }
+
+        let speed = velocity.norm();
+        if speed > config.max_speed {
+            steering += velocity * ((config.max_speed - speed) / speed);
+        }
+
+        let magnitude = steering.norm();
+        if magnitude > config.max_acceleration {
+            steering *= config.max_acceleration / magnitude;
+        }
+        steering
+    }
+}
+
+impl ForceGenerator<f32> for Boids {
+    fn apply(&mut self, _: &IntegrationParameters<f32>, bodies: &mut BodySet<f32>) -> bool {
+        let mut handles: Vec<BodyHandle> = Vec::with_capacity(self.parts.len());
+        let mut positions: Vec<Point3<f32>> = Vec::with_capacity(self.parts.len());
+        let mut velocities: Vec<Vector3<f32>> = Vec::with_capacity(self.parts.len());
+        let mut masses: Vec<f32> = Vec::with_capacity(self.parts.len());
+        for handle in &self.parts {
+            if bodies.contains(*handle) {
+                let part = bodies.body_part(*handle);
+                handles.push(*handle);
+                positions.push(part.center_of_mass());
+                velocities.push(part.velocity().linear);
+                masses.push(part.inertia().linear);
+            }
+        }
+
+        let hash = SpatialHash::new(self.config.neighbour_radius, &positions);
+        let accelerations: Vec<Vector3<f32>> = (0..positions.len())
+            .into_par_iter()
+            .map(|i| self.steering(i, &positions, &velocities, &hash))
+            .collect();
+        for (i, handle) in handles.iter().enumerate() {
+            let force = accelerations[i] * masses[i];
+            if force != na::zero() {
+                bodies.body_mut(*handle).activate();
+                bodies.body_part_mut(*handle).apply_force(&Force3::linear(force));
+            }
+        }
+        true
+    }
+}
\ No newline at end of file
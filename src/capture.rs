@@ -0,0 +1,359 @@
+use glium::{BlitTarget, Display};
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use png;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use na::Matrix4;
+
+/// How many times the window's width and height a poster is, unless `--poster-scale` says otherwise.
+pub const POSTER_SCALE: u32 = 4;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CaptureFormat {
+    /// One numbered PNG per frame in the output directory.
+    Png,
+    /// A single uncompressed YUV4MPEG2 stream, which ffmpeg reads directly.
+    Y4m
+}
+
+impl CaptureFormat {
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            CaptureFormat::Png => "PNG",
+            CaptureFormat::Y4m => "Y4M"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "png" => Some(CaptureFormat::Png),
+            "y4m" => Some(CaptureFormat::Y4m),
+            _     => None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CaptureSettings {
+    pub width: u32,
+    pub height: u32,
+    pub format: CaptureFormat,
+    /// Directory for PNGs, or file for Y4M where `-` means stdout.
+    pub output: String,
+    /// Frames per second written into the Y4M header; the simulation itself always steps once a frame.
+    pub fps: u32,
+    /// Frames to record before stopping by itself, or `None` to record until stopped.
+    pub frames: Option<usize>
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        CaptureSettings {
+            width: 1280,
+            height: 720,
+            format: CaptureFormat::Png,
+            output: String::from("capture"),
+            fps: 60,
+            frames: None
+        }
+    }
+}
+
+/// Records frames drawn into its own offscreen target, independent of the window's size.
+///
+/// The caller draws each frame into `get_framebuffer` and then calls `write_frame`.
+pub struct Capture {
+    settings: CaptureSettings,
+    color: Texture2d,
+    depth: DepthRenderBuffer,
+    stream: Option<BufWriter<Box<Write>>>,
+    frame: usize
+}
+
+impl Capture {
+    /// Creates the target and the output directory or stream, writing the Y4M header if needed.
+    pub fn start(display: &Display, settings: CaptureSettings) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        if settings.width == 0 || settings.height == 0 {
+            return Err(invalid(format!("capture size {}x{} is empty", settings.width, settings.height)));
+        }
+        let color = Texture2d::empty_with_format(display, UncompressedFloatFormat::U8U8U8U8, MipmapsOption::NoMipmap, settings.width, settings.height)
+            .map_err(|err| invalid(format!("{:?}", err)))?;
+        let depth = DepthRenderBuffer::new(display, DepthFormat::I24, settings.width, settings.height)
+            .map_err(|err| invalid(format!("{:?}", err)))?;
+
+        let stream = match settings.format {
+            CaptureFormat::Png => {
+                fs::create_dir_all(&settings.output)?;
+                None
+            },
+            CaptureFormat::Y4m => {
+                let writer: Box<Write> = if settings.output == "-" {
+                    Box::new(io::stdout())
+                } else {
+                    Box::new(File::create(&settings.output)?)
+                };
+                let mut stream = BufWriter::new(writer);
+                // 4:4:4 keeps full-resolution colour, so thin debug lines do not bleed.
+                write!(stream, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444 XCOLORRANGE=FULL\n", settings.width, settings.height, settings.fps)?;
+                Some(stream)
+            }
+        };
+
+        Ok(Capture {
+            settings,
+            color,
+            depth,
+            stream,
+            frame: 0
+        })
+    }
+
+    pub fn get_settings(&self) -> &CaptureSettings {
+        &self.settings
+    }
+
+    /// Frames written so far.
+    pub fn get_frame_count(&self) -> usize {
+        self.frame
+    }
+
+    pub fn get_aspect(&self) -> f32 {
+        self.settings.width as f32 / self.settings.height as f32
+    }
+
+    pub fn get_dimensions(&self) -> [f32; 2] {
+        [self.settings.width as f32, self.settings.height as f32]
+    }
+
+    /// Whether every requested frame has been written.
+    pub fn is_finished(&self) -> bool {
+        match self.settings.frames {
+            Some(frames) => self.frame >= frames,
+            None => false
+        }
+    }
+
+    pub fn get_color(&self) -> &Texture2d {
+        &self.color
+    }
+
+    pub fn get_framebuffer(&self, display: &Display) -> SimpleFrameBuffer {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.color, &self.depth).unwrap()
+    }
+
+    /// Reads back whatever was drawn into the target and appends it to the output.
+    pub fn write_frame(&mut self) -> io::Result<()> {
+        let (width, height) = (self.settings.width, self.settings.height);
+        let pixels = read_rgba(&self.color);
+        match self.stream {
+            Some(ref mut stream) => write_y4m_frame(stream, &pixels)?,
+            None => {
+                let path = Path::new(&self.settings.output).join(format!("frame_{:05}.png", self.frame));
+                save_png(&path, width, height, &pixels)?;
+            }
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Flushes the stream; PNGs are complete as soon as they are written.
+    pub fn finish(mut self) -> io::Result<usize> {
+        if let Some(ref mut stream) = self.stream {
+            stream.flush()?;
+        }
+        Ok(self.frame)
+    }
+}
+
+/// The texture's pixels as RGBA bytes, rows from the top down as image files expect.
+pub fn read_rgba(texture: &Texture2d) -> Vec<u8> {
+    let image: RawImage2d<u8> = texture.read();
+    let row = image.width as usize * 4;
+    // OpenGL hands rows back from the bottom up.
+    image.data.chunks(row).rev().flat_map(|line| line.iter().cloned()).collect()
+}
+
+pub fn save_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{}: {}", path.display(), err)))?;
+    writer.write_image_data(rgba)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{}: {}", path.display(), err)))
+}
+
+/// `projection` narrowed to one tile of a `tiles` × `tiles` grid over its frustum, counting
+/// columns from the left and rows from the bottom.
+///
+/// The tile's slice of clip space is scaled up to fill all of it, so drawing every tile with its
+/// own matrix covers the original view exactly, with no seams or change of perspective.
+pub fn tile_projection(projection: &Matrix4<f32>, tiles: u32, column: u32, row: u32) -> Matrix4<f32> {
+    let n = tiles as f32;
+    let offset = |index: u32| n - 1.0 - 2.0 * index as f32;
+    let tile = Matrix4::new(
+        n,   0.0, 0.0, offset(column),
+        0.0, n,   0.0, offset(row),
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0
+    );
+    tile * projection
+}
+
+/// Draws a `tiles` × `tiles` grid of `tile_width` × `tile_height` tiles through `tile_projection`
+/// and stitches them into one RGBA image, rows from the top down.
+///
+/// `draw` is handed the tile's target, which it must clear, and the tile's projection; anything
+/// sized in pixels rather than in the world, such as debug lines, stays as thin as it is in the window.
+pub fn render_tiled<F>(display: &Display, tile_width: u32, tile_height: u32, tiles: u32, projection: &Matrix4<f32>, mut draw: F) -> io::Result<Vec<u8>>
+    where F: FnMut(&mut SimpleFrameBuffer, &Matrix4<f32>)
+{
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    if tile_width == 0 || tile_height == 0 || tiles == 0 {
+        return Err(invalid(format!("cannot render {}x{} tiles of {}x{}", tiles, tiles, tile_width, tile_height)));
+    }
+    let color = Texture2d::empty_with_format(display, UncompressedFloatFormat::U8U8U8U8, MipmapsOption::NoMipmap, tile_width, tile_height)
+        .map_err(|err| invalid(format!("{:?}", err)))?;
+    let depth = DepthRenderBuffer::new(display, DepthFormat::I24, tile_width, tile_height)
+        .map_err(|err| invalid(format!("{:?}", err)))?;
+
+    let tile_row = tile_width as usize * 4;
+    let image_row = tile_row * tiles as usize;
+    let mut image: Vec<u8> = vec![0; image_row * (tile_height * tiles) as usize];
+    for row in 0..tiles {
+        for column in 0..tiles {
+            {
+                let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(display, &color, &depth).unwrap();
+                draw(&mut framebuffer, &tile_projection(projection, tiles, column, row));
+            }
+            let pixels = read_rgba(&color);
+            // Rows count up from the bottom of the frustum but down from the top of the image.
+            let top = (tiles - 1 - row) as usize * tile_height as usize;
+            for (y, line) in pixels.chunks(tile_row).enumerate() {
+                let start = (top + y) * image_row + column as usize * tile_row;
+                image[start..start + tile_row].copy_from_slice(line);
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// Renders the view as `render_tiled` does and saves it as the first `<prefix>_NNN.png` not
+/// already taken, returning where it went.
+pub fn save_still<F>(display: &Display, width: u32, height: u32, tiles: u32, projection: &Matrix4<f32>, prefix: &str, draw: F) -> io::Result<PathBuf>
+    where F: FnMut(&mut SimpleFrameBuffer, &Matrix4<f32>)
+{
+    let image = render_tiled(display, width, height, tiles, projection, draw)?;
+    let path = (0..)
+        .map(|index| PathBuf::from(format!("{}_{:03}.png", prefix, index)))
+        .find(|path| !path.exists())
+        .unwrap();
+    save_png(&path, width * tiles, height * tiles, &image)?;
+    Ok(path)
+}
+
+/// Converts RGBA to full-range BT.601 Y, Cb and Cr planes, one after another.
+fn write_y4m_frame<W: Write>(out: &mut W, rgba: &[u8]) -> io::Result<()> {
+    let count = rgba.len() / 4;
+    let mut planes: Vec<u8> = vec![0; count * 3];
+    for (i, pixel) in rgba.chunks(4).enumerate() {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+        let y = 0.299 * r + 0.587 * g + 0.114 * b;
+        let cb = 128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b;
+        let cr = 128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b;
+        planes[i] = to_byte(y);
+        planes[count + i] = to_byte(cb);
+        planes[2 * count + i] = to_byte(cr);
+    }
+    out.write_all(b"FRAME\n")?;
+    out.write_all(&planes)
+}
+
+fn to_byte(value: f32) -> u8 {
+    f32::min(f32::max(value.round(), 0.0), 255.0) as u8
+}
+
+/// Reads `--capture N`, `--capture-size WxH`, `--capture-format png|y4m`, `--capture-out PATH` and
+/// `--capture-fps N`. The settings are always returned for the capture key; the flag says whether
+/// `--capture` asked to start recording straight away.
+pub fn capture_from_args() -> (CaptureSettings, bool) {
+    let args: Vec<String> = env::args().collect();
+    let value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+    let mut settings = CaptureSettings::default();
+
+    if let Some(size) = value("--capture-size") {
+        let parsed: Vec<Option<u32>> = size.split('x').map(|v| v.parse().ok()).collect();
+        match parsed.as_slice() {
+            &[Some(width), Some(height)] => {
+                settings.width = width;
+                settings.height = height;
+            },
+            _ => eprintln!("--capture-size expects WIDTHxHEIGHT, e.g. 1920x1080")
+        }
+    }
+    if let Some(name) = value("--capture-format") {
+        match CaptureFormat::from_name(name) {
+            Some(format) => settings.format = format,
+            None => eprintln!("--capture-format expects png or y4m")
+        }
+    }
+    settings.output = match value("--capture-out") {
+        Some(path) => path.clone(),
+        None => match settings.format {
+            CaptureFormat::Png => String::from("capture"),
+            CaptureFormat::Y4m => String::from("capture.y4m")
+        }
+    };
+    if let Some(fps) = value("--capture-fps") {
+        match fps.parse() {
+            Ok(fps) if fps > 0 => settings.fps = fps,
+            _ => eprintln!("--capture-fps expects a whole number of frames per second")
+        }
+    }
+    let autostart = match value("--capture") {
+        Some(frames) => match frames.parse() {
+            Ok(frames) => {
+                settings.frames = Some(frames);
+                true
+            },
+            Err(_) => {
+                eprintln!("--capture expects a number of frames");
+                false
+            }
+        },
+        None => false
+    };
+    (settings, autostart)
+}
+
+/// Reads `--poster-scale N` from the command line, falling back to `POSTER_SCALE`.
+pub fn poster_scale_from_args() -> u32 {
+    let args: Vec<String> = env::args().collect();
+    match args.iter().position(|arg| arg == "--poster-scale").and_then(|i| args.get(i + 1)) {
+        Some(scale) => match scale.parse() {
+            Ok(scale) if scale > 0 => scale,
+            _ => {
+                eprintln!("--poster-scale expects a whole number above zero");
+                POSTER_SCALE
+            }
+        },
+        None => POSTER_SCALE
+    }
+}
+
+/// Where a `width` × `height` image lands when scaled to fit the window without stretching.
+pub fn fit_rect(width: u32, height: u32, window: [f32; 2]) -> BlitTarget {
+    let scale = f32::min(window[0] / width as f32, window[1] / height as f32);
+    let (fit_width, fit_height) = (width as f32 * scale, height as f32 * scale);
+    BlitTarget {
+        left: ((window[0] - fit_width) * 0.5) as u32,
+        bottom: ((window[1] - fit_height) * 0.5) as u32,
+        width: fit_width as i32,
+        height: fit_height as i32
+    }
+}
@@ -0,0 +1,359 @@
+use na::{Isometry3, Point3, Vector3};
+use ncollide3d::shape::Cuboid;
+use nphysics3d::world::World;
+use std::collections::HashMap;
+
+use force_field::ForceFieldSet;
+use mesh::Mesh;
+use shapes::Sheet;
+use vertex::Vertex;
+
+/// Which particles of a cloth are held in place.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ClothPin {
+    Free,
+    Corners,
+    /// The edge along the top of the sheet, at its largest local y.
+    TopEdge
+}
+
+impl ClothPin {
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            ClothPin::Free    => "FREE",
+            ClothPin::Corners => "CORNERS",
+            ClothPin::TopEdge => "TOP EDGE"
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match *self {
+            ClothPin::Free    => ClothPin::Corners,
+            ClothPin::Corners => ClothPin::TopEdge,
+            ClothPin::TopEdge => ClothPin::Free
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ClothSettings {
+    pub particle_mass: f32,
+    /// Fraction of each stretch or shear error corrected per iteration.
+    pub stiffness: f32,
+    /// Same for the links skipping a particle, which resist folding.
+    pub bend_stiffness: f32,
+    pub iterations: usize,
+    /// Fraction of velocity lost per second.
+    pub damping: f32,
+    pub gravity: Vector3<f32>,
+    /// Fraction of a particle's velocity lost when it touches a body.
+    pub friction: f32,
+    /// Distance kept between the cloth and bodies, and between distant parts of the cloth.
+    pub thickness: f32,
+    pub self_collision: bool
+}
+
+impl Default for ClothSettings {
+    fn default() -> Self {
+        ClothSettings {
+            particle_mass: 0.05,
+            stiffness: 1.0,
+            bend_stiffness: 0.2,
+            iterations: 8,
+            damping: 0.2,
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            friction: 0.5,
+            thickness: 0.1,
+            self_collision: false
+        }
+    }
+}
+
+struct Link {
+    a: usize,
+    b: usize,
+    rest: f32
+}
+
+/// A box a particle must stay outside of, from a cuboid collider near the cloth.
+struct Obstacle {
+    pose: Isometry3<f32>,
+    inverse: Isometry3<f32>,
+    half_extents: Vector3<f32>
+}
+
+/// A square grid of particles held together by distance constraints and solved with
+/// position-based dynamics.
+///
+/// Bodies push the cloth but the cloth does not push back, so it drapes over towers without
+/// toppling them.
+pub struct Cloth {
+    mesh: Mesh,
+    positions: Vec<Point3<f32>>,
+    previous: Vec<Point3<f32>>,
+    pinned: Vec<bool>,
+    links: Vec<Link>,
+    bends: Vec<Link>,
+    spacing: f32,
+    pub settings: ClothSettings
+}
+
+impl Cloth {
+    /// A `size` × `size` cloth of `segments` × `segments` cells lying in the xy-plane of `frame`.
+    pub fn new(frame: &Isometry3<f32>, size: f32, segments: usize, pin: ClothPin) -> Self {
+        let segments = usize::max(segments, 2);
+        let mesh = Mesh::rect(size, size, segments, segments);
+        let row = segments + 1;
+        let positions: Vec<Point3<f32>> = mesh.vertices.iter()
+            .map(|vertex| {
+                let (x, y, z) = vertex.get_position();
+                frame * Point3::new(x, y, z)
+            })
+            .collect();
+        let pinned: Vec<bool> = (0..positions.len())
+            .map(|k| {
+                let (i, j) = (k % row, k / row);
+                match pin {
+                    ClothPin::Free    => false,
+                    ClothPin::Corners => (i == 0 || i == segments) && (j == 0 || j == segments),
+                    ClothPin::TopEdge => j == segments
+                }
+            })
+            .collect();
+
+        let (links, bends) = {
+            let link = |a: usize, b: usize| Link { a, b, rest: (positions[b] - positions[a]).norm() };
+            let mut links: Vec<Link> = Vec::new();
+            let mut bends: Vec<Link> = Vec::new();
+            for j in 0..row {
+                for i in 0..row {
+                    let k = j * row + i;
+                    if i + 1 < row {
+                        links.push(link(k, k + 1));
+                    }
+                    if j + 1 < row {
+                        links.push(link(k, k + row));
+                    }
+                    // Both diagonals resist shear.
+                    if i + 1 < row && j + 1 < row {
+                        links.push(link(k, k + row + 1));
+                        links.push(link(k + 1, k + row));
+                    }
+                    if i + 2 < row {
+                        bends.push(link(k, k + 2));
+                    }
+                    if j + 2 < row {
+                        bends.push(link(k, k + 2 * row));
+                    }
+                }
+            }
+            (links, bends)
+        };
+
+        Cloth {
+            mesh,
+            previous: positions.clone(),
+            positions,
+            pinned,
+            links,
+            bends,
+            spacing: size / segments as f32,
+            settings: ClothSettings::default()
+        }
+    }
+
+    /// A cloth covering `sheet`, so the sheet can be dropped onto the scene.
+    pub fn from_sheet(sheet: &Sheet, segments: usize, pin: ClothPin) -> Self {
+        Cloth::new(&sheet.get_position(), sheet.get_size(), segments, pin)
+    }
+
+    pub fn get_particle_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn get_mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    /// Advances by `dt` under gravity and `fields`, then resolves links and collisions with `world`.
+    pub fn step(&mut self, dt: f32, world: &World<f32>, fields: Option<&ForceFieldSet>) {
+        if dt <= 0.0 {
+            return;
+        }
+        let settings = self.settings;
+        let mass = settings.particle_mass;
+        let keep = f32::max(1.0 - settings.damping * dt, 0.0);
+        for k in 0..self.positions.len() {
+            if self.pinned[k] {
+                continue;
+            }
+            let position = self.positions[k];
+            let moved = position - self.previous[k];
+            let mut force = settings.gravity * mass;
+            if let Some(fields) = fields {
+                let velocity = moved / dt;
+                for field in fields.get_fields() {
+                    force += field.force_at(&position, &velocity, mass, fields.get_time());
+                }
+            }
+            self.previous[k] = position;
+            self.positions[k] = position + moved * keep + force / mass * dt * dt;
+        }
+
+        let obstacles = self.find_obstacles(world);
+        for _ in 0..settings.iterations {
+            solve_links(&mut self.positions, &self.pinned, &self.links, settings.stiffness);
+            solve_links(&mut self.positions, &self.pinned, &self.bends, settings.bend_stiffness);
+            if settings.self_collision {
+                self.separate_particles();
+            }
+            self.collide(&obstacles);
+        }
+    }
+
+    /// Cuboid colliders whose bounding spheres come within reach of the cloth.
+    fn find_obstacles(&self, world: &World<f32>) -> Vec<Obstacle> {
+        let mut low = self.positions[0];
+        let mut high = self.positions[0];
+        for p in &self.positions {
+            for axis in 0..3 {
+                low[axis] = f32::min(low[axis], p[axis]);
+                high[axis] = f32::max(high[axis], p[axis]);
+            }
+        }
+        let center = na::center(&low, &high);
+        let reach = (high - low).norm() * 0.5 + self.settings.thickness;
+
+        let mut obstacles: Vec<Obstacle> = Vec::new();
+        for object in world.collision_world().collision_objects() {
+            let cuboid = match object.shape().as_shape::<Cuboid<f32>>() {
+                Some(cuboid) => cuboid,
+                None => continue
+            };
+            let half_extents = cuboid.half_extents() + Vector3::repeat(self.settings.thickness);
+            let pose = *object.position();
+            if (Point3::from_coordinates(pose.translation.vector) - center).norm() > reach + half_extents.norm() {
+                continue;
+            }
+            obstacles.push(Obstacle {
+                inverse: pose.inverse(),
+                pose,
+                half_extents
+            });
+        }
+        obstacles
+    }
+
+    /// Pushes particles out of every obstacle through its nearest face.
+    fn collide(&mut self, obstacles: &[Obstacle]) {
+        let friction = self.settings.friction;
+        for k in 0..self.positions.len() {
+            if self.pinned[k] {
+                continue;
+            }
+            for obstacle in obstacles {
+                let mut local = obstacle.inverse * self.positions[k];
+                let mut axis = 0;
+                let mut depth = f32::max_value();
+                for a in 0..3 {
+                    let d = obstacle.half_extents[a] - local[a].abs();
+                    if d < depth {
+                        axis = a;
+                        depth = d;
+                    }
+                }
+                if depth <= 0.0 {
+                    continue;
+                }
+                local[axis] = if local[axis] < 0.0 { -obstacle.half_extents[axis] } else { obstacle.half_extents[axis] };
+                self.positions[k] = obstacle.pose * local;
+                let moved = self.positions[k] - self.previous[k];
+                self.previous[k] += moved * friction;
+            }
+        }
+    }
+
+    /// Keeps particles that are not neighbours on the grid from passing through each other.
+    fn separate_particles(&mut self) {
+        let min_distance = f32::min(self.settings.thickness * 2.0, self.spacing * 0.9);
+        let cell = |p: &Point3<f32>| (
+            (p.x / min_distance).floor() as i32,
+            (p.y / min_distance).floor() as i32,
+            (p.z / min_distance).floor() as i32
+        );
+        let mut cells: HashMap<(i32, i32, i32), Vec<usize>> = HashMap::new();
+        for (k, p) in self.positions.iter().enumerate() {
+            cells.entry(cell(p)).or_insert_with(Vec::new).push(k);
+        }
+        for a in 0..self.positions.len() {
+            let (x, y, z) = cell(&self.positions[a]);
+            for dx in -1..2 {
+                for dy in -1..2 {
+                    for dz in -1..2 {
+                        let others = match cells.get(&(x + dx, y + dy, z + dz)) {
+                            Some(others) => others,
+                            None => continue
+                        };
+                        for &b in others {
+                            if b <= a {
+                                continue;
+                            }
+                            let offset = self.positions[b] - self.positions[a];
+                            let distance = offset.norm();
+                            if distance >= min_distance || distance == 0.0 {
+                                continue;
+                            }
+                            let correction = offset * ((min_distance - distance) / distance);
+                            move_pair(&mut self.positions, &self.pinned, a, b, correction, 1.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Current positions with smooth normals and the grid's UVs, ready to upload.
+    pub fn get_vertices(&self) -> Vec<Vertex> {
+        let mut normals: Vec<Vector3<f32>> = vec![na::zero(); self.positions.len()];
+        for triangle in self.mesh.indices.chunks(3) {
+            let (a, b, c) = (triangle[0] as usize, triangle[1] as usize, triangle[2] as usize);
+            let face = (self.positions[b] - self.positions[a]).cross(&(self.positions[c] - self.positions[a]));
+            normals[a] += face;
+            normals[b] += face;
+            normals[c] += face;
+        }
+        self.positions.iter()
+            .zip(normals.iter())
+            .zip(self.mesh.vertices.iter())
+            .map(|((p, n), vertex)| {
+                let n = if n.norm_squared() > 0.0 { n.normalize() } else { Vector3::y() };
+                Vertex::textured(p.x, p.y, p.z, (n.x, n.y, n.z), vertex.get_tex_coords())
+            })
+            .collect()
+    }
+}
+
+/// Moves every linked pair towards its rest length by `stiffness` of the error.
+fn solve_links(positions: &mut [Point3<f32>], pinned: &[bool], links: &[Link], stiffness: f32) {
+    for link in links {
+        let offset = positions[link.b] - positions[link.a];
+        let distance = offset.norm();
+        if distance == 0.0 {
+            continue;
+        }
+        let correction = offset * ((distance - link.rest) / distance);
+        move_pair(positions, pinned, link.a, link.b, -correction, stiffness);
+    }
+}
+
+/// Moves `b` by `correction` and `a` against it, split evenly unless one of them is pinned.
+fn move_pair(positions: &mut [Point3<f32>], pinned: &[bool], a: usize, b: usize, correction: Vector3<f32>, stiffness: f32) {
+    let (wa, wb) = match (pinned[a], pinned[b]) {
+        (false, false) => (0.5, 0.5),
+        (true, false)  => (0.0, 1.0),
+        (false, true)  => (1.0, 0.0),
+        (true, true)   => return
+    };
+    positions[a] -= correction * (wa * stiffness);
+    positions[b] += correction * (wb * stiffness);
+}
@@ -0,0 +1,147 @@
+use na::{Point3, Vector3};
+use ncollide3d::events::ContactEvent as CollisionEvent;
+use ncollide3d::world::CollisionObjectHandle;
+use nphysics3d::object::BodyHandle;
+use nphysics3d::world::World;
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ContactPhase {
+    Started,
+    Stopped
+}
+
+/// Two bodies starting or stopping to touch during the last step.
+#[derive(Copy, Clone, Debug)]
+pub struct ContactEvent {
+    pub phase: ContactPhase,
+    pub body1: BodyHandle,
+    pub body2: BodyHandle,
+    /// Deepest contact point and its normal, from body 1 towards body 2; only known when started.
+    pub point: Option<Point3<f32>>,
+    pub normal: Option<Vector3<f32>>,
+    /// Impulse needed to stop the bodies closing along the normal, in newton-seconds; 0 when stopped.
+    pub impulse: f32
+}
+
+pub type SubscriptionId = usize;
+
+/// Turns ncollide's per-step contact events into body-level events and hands them to subscribers.
+pub struct ContactEvents {
+    subscribers: Vec<(SubscriptionId, Box<FnMut(&ContactEvent)>)>,
+    next_id: SubscriptionId,
+    events: Vec<ContactEvent>,
+    velocities: HashMap<BodyHandle, (Vector3<f32>, f32)>,
+    total_started: usize
+}
+
+impl ContactEvents {
+    pub fn new() -> Self {
+        ContactEvents {
+            subscribers: Vec::new(),
+            next_id: 0,
+            events: Vec::new(),
+            velocities: HashMap::new(),
+            total_started: 0
+        }
+    }
+
+    /// Calls `callback` with every event from each following step until unsubscribed.
+    pub fn subscribe<F: FnMut(&ContactEvent) + 'static>(&mut self, callback: F) -> SubscriptionId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.subscribers.push((id, Box::new(callback)));
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriptionId) {
+        self.subscribers.retain(|&(subscriber, _)| subscriber != id);
+    }
+
+    /// Events from the last step.
+    pub fn get_events(&self) -> &[ContactEvent] {
+        &self.events
+    }
+
+    pub fn get_started_count(&self) -> usize {
+        self.events.iter().filter(|event| event.phase == ContactPhase::Started).count()
+    }
+
+    pub fn get_total_started(&self) -> usize {
+        self.total_started
+    }
+
+    /// Remembers velocities and masses from before the step, which the impulse estimate needs.
+    pub fn record_velocities(&mut self, world: &World<f32>, handles: &[BodyHandle]) {
+        self.velocities.clear();
+        for handle in handles {
+            if let Some(rigid_body) = world.rigid_body(*handle) {
+                self.velocities.insert(*handle, (rigid_body.velocity().linear, rigid_body.local_inertia().linear));
+            }
+        }
+    }
+
+    /// Reads the contact events the last step produced and notifies subscribers.
+    pub fn collect(&mut self, world: &World<f32>) {
+        let collision_world = world.collision_world();
+        let mut deepest: HashMap<(CollisionObjectHandle, CollisionObjectHandle), (Point3<f32>, Vector3<f32>, f32)> = HashMap::new();
+        for (co1, co2, manifold) in collision_world.contact_manifolds() {
+            for tracked in manifold.contacts() {
+                let contact = &tracked.contact;
+                let key = (co1.handle(), co2.handle());
+                if deepest.get(&key).map_or(true, |&(_, _, depth)| contact.depth > depth) {
+                    deepest.insert(key, (contact.world1, *contact.normal, contact.depth));
+                }
+            }
+        }
+
+        self.events.clear();
+        for event in collision_world.contact_events() {
+            let (phase, h1, h2) = match *event {
+                CollisionEvent::Started(h1, h2) => (ContactPhase::Started, h1, h2),
+                CollisionEvent::Stopped(h1, h2) => (ContactPhase::Stopped, h1, h2)
+            };
+            let (body1, body2) = match (world.collider(h1), world.collider(h2)) {
+                (Some(c1), Some(c2)) => (c1.data().body(), c2.data().body()),
+                _ => continue
+            };
+            let contact = match deepest.get(&(h1, h2)) {
+                Some(&(point, normal, _)) => Some((point, normal)),
+                None => deepest.get(&(h2, h1)).map(|&(point, normal, _)| (point, -normal))
+            };
+            let impulse = match (phase, contact) {
+                (ContactPhase::Started, Some((_, normal))) => self.impulse(body1, body2, &normal),
+                _ => 0.0
+            };
+            self.events.push(ContactEvent {
+                phase,
+                body1,
+                body2,
+                point: contact.map(|(point, _)| point),
+                normal: contact.map(|(_, normal)| normal),
+                impulse
+            });
+        }
+        self.total_started += self.get_started_count();
+
+        for &mut (_, ref mut callback) in self.subscribers.iter_mut() {
+            for event in &self.events {
+                callback(event);
+            }
+        }
+    }
+
+    /// Reduced mass times the closing speed along the normal; the ground counts as infinitely heavy.
+    fn impulse(&self, body1: BodyHandle, body2: BodyHandle, normal: &Vector3<f32>) -> f32 {
+        let (v1, m1) = self.velocities.get(&body1).cloned().unwrap_or((na::zero(), 0.0));
+        let (v2, m2) = self.velocities.get(&body2).cloned().unwrap_or((na::zero(), 0.0));
+        let reduced_mass = match (m1 > 0.0, m2 > 0.0) {
+            (true, true)   => m1 * m2 / (m1 + m2),
+            (true, false)  => m1,
+            (false, true)  => m2,
+            (false, false) => 0.0
+        };
+        let closing = (v1 - v2).dot(normal);
+        reduced_mass * f32::max(closing, 0.0)
+    }
+}
@@ -36,6 +36,22 @@ impl Cube {
         }
     }
 
+    /// A box with independent half extents, for walls, ramps and blocks that are not cubes.
+    pub fn with_extents(cube_type: CubeType, position: Isometry3<f32>, color: [f32; 3], half_extents: Vector3<f32>) -> Self {
+        Cube {
+            cube_type,
+            color,
+            size: f32::max(half_extents.x, f32::max(half_extents.y, half_extents.z)),
+            scale : Matrix4::new(
+                half_extents.x, 0.0, 0.0, 0.0,
+                0.0, half_extents.y, 0.0, 0.0,
+                0.0, 0.0, half_extents.z, 0.0,
+                0.0, 0.0, 0.0,            1.0
+            ),
+            position
+        }
+    }
+
     pub fn get_size(&self) -> f32 {
         self.size
     }
@@ -60,6 +76,14 @@ impl Cube {
         self.position.translation.vector.z
     }
 
+    pub fn get_position(&self) -> Isometry3<f32> {
+        self.position
+    }
+
+    pub fn get_half_extents(&self) -> Vector3<f32> {
+        Vector3::new(self.scale[(0, 0)], self.scale[(1, 1)], self.scale[(2, 2)])
+    }
+
     pub fn get_type(&self) -> &CubeType {
         &self.cube_type
     }
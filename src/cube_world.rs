@@ -9,12 +9,23 @@ extern crate ncollide3d;
 extern crate nphysics3d;
 extern crate rayon;
 extern crate noise;
+extern crate hound;
+extern crate rodio;
+extern crate png;
+extern crate toml;
 
 mod vertex;
 mod cube;
 mod input;
 mod cubody;
 mod world_force;
+mod overlay;
+mod hud;
+mod ui;
+mod voxel;
+mod audio;
+mod capture;
+mod scene_file;
 
 use noise::*;
 use rayon::prelude::*;
@@ -34,12 +45,79 @@ use cube::*;
 use input::*;
 use cubody::*;
 use world_force::*;
+use overlay::*;
+use hud::*;
+use ui::*;
+use voxel::*;
+use audio::*;
+use capture::*;
+use scene_file::*;
 
 const COLLIDER_MARGIN: f32 = 0.01;
+/// How many times faster than the slider the seed moves at full volume.
+const AUDIO_BOOST: f32 = 4.0;
+const SCREENSHOT_PREFIX: &str = "world";
+const POSTER_PREFIX: &str = "world_poster";
+
+/// The `[noise_world]` section: how big the voxel world is and how its noise drifts.
+struct NoiseWorld {
+    /// Cells along each side.
+    size: i32,
+    cell_size: f32,
+    seed: f32,
+    frequency: f32,
+    /// How far the seed moves each frame.
+    seed_speed: f32
+}
+
+impl Default for NoiseWorld {
+    fn default() -> Self {
+        NoiseWorld {
+            size: 30,
+            cell_size: 1.0,
+            seed: 0.001,
+            frequency: 0.05,
+            seed_speed: 0.01
+        }
+    }
+}
+
+impl NoiseWorld {
+    fn read(&mut self, table: &SceneTable) -> Result<(), SceneError> {
+        table.expect_keys(&["size", "cell_size", "seed", "frequency", "seed_speed"])?;
+        if let Some(size) = table.get_integer("size", 1)? {
+            self.size = size as i32;
+        }
+        if let Some(cell_size) = table.get_f32_in("cell_size", 0.01, f32::MAX)? {
+            self.cell_size = cell_size;
+        }
+        if let Some(seed) = table.get_f32("seed")? {
+            self.seed = seed;
+        }
+        if let Some(frequency) = table.get_f32_in("frequency", 0.0, f32::MAX)? {
+            self.frequency = frequency;
+        }
+        if let Some(seed_speed) = table.get_f32("seed_speed")? {
+            self.seed_speed = seed_speed;
+        }
+        Ok(())
+    }
+}
 
 fn main() {
     use glium::{glutin, Surface};
-    let mut dimensions: [f32; 2] = [800.0, 600.0];
+    let mut render = RenderSettings::default();
+    let mut camera = CameraSettings::new([0.0, 0.0, 20.0], -88.0, 0.0, 1.0);
+    let mut light = LightSettings::new((0.0, 0.0, 0.0), [1.0, 1.0, 1.0]);
+    let mut noise_world = NoiseWorld::default();
+    read_scene_from_args(|scene| {
+        scene.read_view(&mut render, &mut camera, &mut light)?;
+        match scene.get_root().get_table("noise_world")? {
+            Some(table) => noise_world.read(&table),
+            None => Ok(())
+        }
+    });
+    let mut dimensions: [f32; 2] = [render.width, render.height];
     let mut event_loop = glutin::EventsLoop::new();
 
     // let monitor = event_loop.get_available_monitors().nth(1);
@@ -57,11 +135,11 @@ fn main() {
     // let inertia = geom.inertia(1.1);
     // let center_of_mass = geom.center_of_mass();
 
-    let light_position: (f32, f32, f32) = (0.0, 0.0, 0.0);
+    let light_position: (f32, f32, f32) = light.position;
     let cube_iter = 5;
-    let mut cubes: Vec<Cube> = create_cube_world(0.1);
-
-    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+    let mut noise_frequency: f32 = noise_world.frequency;
+    let mut seed_speed: f32 = noise_world.seed_speed;
+    let mut cubes: Vec<Cube> = create_cube_world(noise_world.size, noise_world.cell_size, noise_world.seed, noise_frequency);
 
     let block_vertex_shader_src   = include_str!("../assets/block.vert");
     let block_fragment_shader_src = include_str!("../assets/block.frag");
@@ -71,13 +149,17 @@ fn main() {
     let light_fragment_shader_src = include_str!("../assets/light.frag");
     let light_program: Program = glium::Program::from_source(&display, light_vertex_shader_src, light_fragment_shader_src, None).unwrap();
 
-    let projection = geometry::Perspective3::new(dimensions[0]/dimensions[1], f32::consts::PI/2.0, 0.1, 1000.0);
+    let overlay_vertex_shader_src   = include_str!("../assets/overlay.vert");
+    let overlay_fragment_shader_src = include_str!("../assets/overlay.frag");
+    let overlay_program: Program = glium::Program::from_source(&display, overlay_vertex_shader_src, overlay_fragment_shader_src, None).unwrap();
+
+    let projection = geometry::Perspective3::new(render.get_aspect(), radianize(&render.fov), render.near, render.far);
 
     let expl = 0.1;
     let mut closed = false;
-    let mut d: f32 = 0.001;
+    let mut d: f32 = noise_world.seed;
 
-    let light_color:  [f32; 3] = [1.0, 1.0, 1.0];
+    let light_color:  [f32; 3] = light.color;
 
     let params = glium::DrawParameters {
         depth: glium::Depth {
@@ -89,23 +171,32 @@ fn main() {
     };
     let mut rotate_cubes: bool = false;
     let mut mouse_offset: Vector2<f32> = Vector2::new(0.0, 0.0);
-    let mouse_sensitivity: f32 = 0.5;
-    let mut pitch: f32 = 0.0;
-    let mut yaw: f32 = -88.0;
+    let mut mouse_sensitivity: f32 = 0.5;
+    let mut pitch: f32 = camera.pitch;
+    let mut yaw: f32 = camera.yaw;
 
-    let camera_speed: f32 = 1.0;
-    let mut camera_pos = glm::vec3(0.0, 0.0, 20.0);
+    let mut camera_speed: f32 = camera.speed;
+    let mut camera_pos = glm::vec3(camera.position[0], camera.position[1], camera.position[2]);
     let camera_up  = glm::vec3(0.0, 1.0, 0.0);
 
     let mut input_holder: Input = Input::new();
+    let mut hud: Hud = Hud::new();
+    let mut overlay: Overlay = Overlay::new(&display);
+    let mut ui: Ui = Ui::new();
 
     let cube_verts = glium::VertexBuffer::new(&display, &cube::get_cube_verts(1.0)).unwrap();
+    let mut audio: Option<AudioPlayer> = audio_from_args();
+    let mut toggle_audio: bool = false;
+    let mut audio_seek: f32 = 0.0;
+    let poster_scale: u32 = poster_scale_from_args();
+    // Tiles along each side and file prefix of a still to save with the next frame.
+    let mut still: Option<(u32, &str)> = None;
 
 
     while !closed {
 
         let mut target = display.draw();
-        target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+        target.clear_color_and_depth(render.get_clear_color(), 1.0);
 
         yaw   += mouse_offset.x;
         pitch += mouse_offset.y;
@@ -124,7 +215,6 @@ fn main() {
             f32::sin(r_yaw) * f32::cos(r_pitch)
         );
         let camera_front = glm::normalize(&front);
-        println!("{:?}", camera_front);
         let view = glm::look_at(
             &camera_pos,
             &(camera_pos+camera_front),
@@ -132,19 +222,42 @@ fn main() {
         );
         // println!("{:?}", view);
 
-        for cube in cubes.iter_mut() {
-            
-            let uniforms = uniform!{
-                window_size: dimensions,
-                lightColor:  light_color,
-                lightPos:    light_position,
-                model:       na4_to_gl4(&cube.get_model_transform()),
-                view:        na4_to_gl4(&view),
-                projection:  na4_to_gl4(&projection.as_matrix()),
-                objectColor: cube.get_color(),
-            };
-            target.draw(&cube_verts, &indices, &block_program, &uniforms, &params).unwrap();
+        let lights = (light_position, light_color);
+        draw_cubes(&mut target, &cubes, &cube_verts, &block_program, &params, lights, &view, projection.as_matrix(), dimensions);
+        if let Some((tiles, prefix)) = still.take() {
+            let saved = save_still(&display, dimensions[0] as u32, dimensions[1] as u32, tiles, projection.as_matrix(), prefix, |target, tile_projection| {
+                target.clear_color_and_depth(render.get_clear_color(), 1.0);
+                draw_cubes(target, &cubes, &cube_verts, &block_program, &params, lights, &view, tile_projection, dimensions);
+            });
+            match saved {
+                Ok(path) => eprintln!("saved {}", path.display()),
+                Err(err) => eprintln!("could not save {}: {}", prefix, err)
+            }
+        }
+
+        hud.tick();
+        overlay.clear();
+        if hud.visible {
+            hud.add_line(format!("FPS {:.1} ({:.2} MS)", hud.get_fps(), hud.get_frame_time()));
+            hud.add_line(format!("CUBES {}  DRAW CALLS {}  VERTICES {}", cubes.len(), cubes.len(), cubes.len() * 36));
+            hud.add_line(format!("CAMERA {:.1} {:.1} {:.1}", camera_pos.x, camera_pos.y, camera_pos.z));
+            hud.add_line(format!("FRONT {:.2} {:.2} {:.2}", camera_front.x, camera_front.y, camera_front.z));
+            hud.add_line(format!("YAW {:.1} PITCH {:.1}", yaw, pitch));
+            hud.add_line(format!("MODE NOISE WORLD  SEED {:.2}", d));
+            if let Some(ref audio) = audio {
+                hud.add_line(format!("AUDIO {:.1}/{:.1}S  LEVEL {:.2}", audio.get_position(), audio.get_track().get_duration(), audio.get_level()));
+            }
+            hud.build(&mut overlay, dimensions);
+        }
+        if ui.visible {
+            ui.begin(&mut overlay, "NOISE WORLD", dimensions);
+            ui.slider(&mut overlay, "FREQUENCY", &mut noise_frequency, 0.005, 0.3);
+            ui.slider(&mut overlay, "SEED SPEED", &mut seed_speed, 0.0, 0.1);
+            ui.slider(&mut overlay, "MOUSE SENS", &mut mouse_sensitivity, 0.05, 2.0);
+            ui.slider(&mut overlay, "CAMERA SPEED", &mut camera_speed, 0.05, 5.0);
+            ui.end();
         }
+        overlay.draw(&display, &mut target, &overlay_program, dimensions);
         target.finish().unwrap();
 
         mouse_offset.x = 0.0;
@@ -154,6 +267,7 @@ fn main() {
             match event {
                 glutin::Event::DeviceEvent { event, ..} => match event {
                     glutin::DeviceEvent::MouseMotion{ delta }  => match delta {
+                        _ if ui.visible => (),
                         _ => {
                             mouse_offset.x = ( delta.0 as f32) * mouse_sensitivity;
                             mouse_offset.y = (-delta.1 as f32) * mouse_sensitivity;
@@ -167,6 +281,12 @@ fn main() {
                         dimensions[0] = size.width  as f32;
                         dimensions[1] = size.height as f32;
                     },
+                    glutin::WindowEvent::CursorMoved{ position, .. } => {
+                        ui.handle_cursor(position.x as f32, position.y as f32);
+                    },
+                    glutin::WindowEvent::MouseInput{ state, button: glutin::MouseButton::Left, .. } => {
+                        ui.handle_button(state == glutin::ElementState::Pressed);
+                    },
                     glutin::WindowEvent::KeyboardInput{ input, .. } => {
                         let latch = match input.state {
                             glutin::ElementState::Pressed  => true,
@@ -194,6 +314,28 @@ fn main() {
                         if input.virtual_keycode == Some(glutin::VirtualKeyCode::Escape) {
                             closed = true;
                         }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::U) {
+                            toggle_audio = true;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::LBracket) {
+                            audio_seek -= SEEK_SECONDS;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::RBracket) {
+                            audio_seek += SEEK_SECONDS;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::F11) {
+                            still = Some((poster_scale, POSTER_PREFIX));
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::F12) {
+                            still = Some((1, SCREENSHOT_PREFIX));
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::H) {
+                            hud.visible = !hud.visible;
+                        }
+                        if latch && input.virtual_keycode == Some(glutin::VirtualKeyCode::Tab) {
+                            ui.visible = !ui.visible;
+                            display.gl_window().hide_cursor(!ui.visible);
+                        }
 //                        println!("{:?}", input)
                     }
                     _ => ()
@@ -216,13 +358,39 @@ fn main() {
         if input_holder.up {
             camera_pos += camera_speed * camera_up;
         }
+        if let Some(ref mut audio) = audio {
+            audio.apply_input(toggle_audio, audio_seek, 1.0 / 60.0);
+        }
+        toggle_audio = false;
+        audio_seek = 0.0;
         // world.step();
-        d += 0.01;
-        cubes = create_cube_world(d);
+        d += match audio {
+            Some(ref audio) => seed_speed * (1.0 + AUDIO_BOOST * audio.get_level()),
+            None => seed_speed
+        };
+        cubes = create_cube_world(noise_world.size, noise_world.cell_size, d, noise_frequency);
     }
 
 }
 
+/// Draws every cube lit by `lights`, a light's position and colour.
+fn draw_cubes<S: Surface>(target: &mut S, cubes: &[Cube], cube_verts: &VertexBuffer<vertex::Vertex>, program: &Program, params: &DrawParameters, lights: ((f32, f32, f32), [f32; 3]), view: &Matrix4<f32>, projection: &Matrix4<f32>, window_size: [f32; 2]) {
+    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+    let (light_position, light_color) = lights;
+    for cube in cubes {
+        let uniforms = uniform!{
+            window_size: window_size,
+            lightColor:  light_color,
+            lightPos:    light_position,
+            model:       na4_to_gl4(&cube.get_model_transform()),
+            view:        na4_to_gl4(view),
+            projection:  na4_to_gl4(projection),
+            objectColor: cube.get_color(),
+        };
+        target.draw(cube_verts, &indices, program, &uniforms, params).unwrap();
+    }
+}
+
 fn radianize(n: &f32) -> f32 {
     n * (f32::consts::PI/180.0)
 }
@@ -240,33 +408,18 @@ fn na4_to_gl4(mat: &Matrix4<f32>) -> [[f32; 4]; 4] {
     ]
 }
 
-fn create_cube_world(seed: f32) -> Vec<Cube> {
-    let cube_resolution = 1.0;
-    let noise = Fbm::new();
-    let mut cube_world = Vec::new();
-    for x in 0..30 {
-        let fx = x as f32;
-        for y in 0..30 {
-            let fy = y as f32;
-            for z in 0..30 {
-                let fz = z as f32;
-
-                let val = noise.get([(seed+fx*cube_resolution/20.0) as f64, (seed+fy*cube_resolution/20.0) as f64, (seed+fz*cube_resolution/20.0) as f64]);
-                if val >= 0.0 {
-                    let location: Vector3<f32> = Vector3::new(fx*cube_resolution, fy*cube_resolution, fz*cube_resolution);
-                    let pos = Isometry3::new(location, na::zero());
-                    let color = [(val*8.0) as f32, (val*5.0) as f32, (val*3.0) as f32];
-                    cube_world.push(
-                        Cube::new(
-                            CubeType::Block,
-                            pos,
-                            color,
-                            cube_resolution*0.5
-                        ),
-                    );
-                }
-            }
-        }
-    }
-    cube_world
+fn create_cube_world(size: i32, cube_resolution: f32, seed: f32, frequency: f32) -> Vec<Cube> {
+    let grid = VoxelGrid::from_noise(size, cube_resolution, seed, frequency);
+    grid.voxels.iter()
+        .map(|voxel| {
+            let (x, y, z) = voxel.coords;
+            let location: Vector3<f32> = Vector3::new(x as f32*cube_resolution, y as f32*cube_resolution, z as f32*cube_resolution);
+            Cube::new(
+                CubeType::Block,
+                Isometry3::new(location, na::zero()),
+                voxel.color,
+                cube_resolution*0.5
+            )
+        })
+        .collect()
 }
@@ -1,17 +1,29 @@
 
 use cube::*;
+use na::Isometry3;
 use nphysics3d::object::{BodyHandle};
 
 pub struct Cubody {
     pub cube: Cube,
-    pub handle: BodyHandle
+    pub handle: BodyHandle,
+    /// Where the cube sits in its body's frame; identity unless several cubes share one body.
+    pub offset: Isometry3<f32>
 }
 
 impl Cubody {
     pub fn new(cube: Cube, handle: BodyHandle) -> Self {
         Cubody {
             cube,
-            handle
+            handle,
+            offset: Isometry3::identity()
+        }
+    }
+
+    pub fn with_offset(cube: Cube, handle: BodyHandle, offset: Isometry3<f32>) -> Self {
+        Cubody {
+            cube,
+            handle,
+            offset
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,205 @@
+extern crate glium;
+extern crate nalgebra as na;
+
+use glium::*;
+use na::{Matrix4, Vector3, Point3, Isometry3};
+use ncollide3d::shape::{Cuboid, Shape};
+use nphysics3d::world::World;
+use vertex::{Vertex, LineVertex};
+use std::f32;
+
+const COLLIDER_COLOR: [f32; 3] = [0.0, 1.0, 0.0];
+const AABB_COLOR:     [f32; 3] = [1.0, 1.0, 0.0];
+const CONTACT_COLOR:  [f32; 3] = [1.0, 0.0, 0.0];
+const VELOCITY_COLOR: [f32; 3] = [0.0, 1.0, 1.0];
+const FORCE_COLOR:    [f32; 3] = [1.0, 0.0, 1.0];
+const NORMAL_COLOR:   [f32; 3] = [0.2, 0.4, 1.0];
+
+pub struct DebugDraw {
+    pub colliders:  bool,
+    pub aabbs:      bool,
+    pub contacts:   bool,
+    pub velocities: bool,
+    pub forces:     bool,
+    pub normals:    bool,
+    pub wireframe:  bool,
+    lines: Vec<LineVertex>
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        DebugDraw {
+            colliders:  false,
+            aabbs:      false,
+            contacts:   false,
+            velocities: false,
+            forces:     false,
+            normals:    false,
+            wireframe:  false,
+            lines: Vec::new()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn get_lines(&self) -> &[LineVertex] {
+        &self.lines
+    }
+
+    pub fn get_enabled_names(&self) -> Vec<&'static str> {
+        let toggles = [
+            (self.colliders,  "COLLIDERS"),
+            (self.aabbs,      "AABBS"),
+            (self.contacts,   "CONTACTS"),
+            (self.velocities, "VELOCITIES"),
+            (self.forces,     "FORCES"),
+            (self.normals,    "NORMALS"),
+            (self.wireframe,  "WIREFRAME")
+        ];
+        toggles.iter().filter(|t| t.0).map(|t| t.1).collect()
+    }
+
+    pub fn get_polygon_mode(&self) -> PolygonMode {
+        if self.wireframe {
+            PolygonMode::Line
+        } else {
+            PolygonMode::Fill
+        }
+    }
+
+    pub fn add_line(&mut self, a: Point3<f32>, b: Point3<f32>, color: [f32; 3]) {
+        self.lines.push(LineVertex::new(a.x, a.y, a.z, color));
+        self.lines.push(LineVertex::new(b.x, b.y, b.z, color));
+    }
+
+    pub fn add_cross(&mut self, center: &Point3<f32>, size: f32, color: [f32; 3]) {
+        for axis in 0..3 {
+            let mut offset: Vector3<f32> = na::zero();
+            offset[axis] = size;
+            self.add_line(center - offset, center + offset, color);
+        }
+    }
+
+    pub fn add_obb(&mut self, position: &Isometry3<f32>, half_extents: &Vector3<f32>, color: [f32; 3]) {
+        let mut corners: Vec<Point3<f32>> = Vec::with_capacity(8);
+        for i in 0..8 {
+            let x = if i & 1 == 0 { -half_extents.x } else { half_extents.x };
+            let y = if i & 2 == 0 { -half_extents.y } else { half_extents.y };
+            let z = if i & 4 == 0 { -half_extents.z } else { half_extents.z };
+            corners.push(position * Point3::new(x, y, z));
+        }
+        self.add_box(&corners, color);
+    }
+
+    pub fn add_aabb(&mut self, mins: &Point3<f32>, maxs: &Point3<f32>, color: [f32; 3]) {
+        let mut corners: Vec<Point3<f32>> = Vec::with_capacity(8);
+        for i in 0..8 {
+            let x = if i & 1 == 0 { mins.x } else { maxs.x };
+            let y = if i & 2 == 0 { mins.y } else { maxs.y };
+            let z = if i & 4 == 0 { mins.z } else { maxs.z };
+            corners.push(Point3::new(x, y, z));
+        }
+        self.add_box(&corners, color);
+    }
+
+    // Corners are indexed by their bits: 1 => +x, 2 => +y, 4 => +z.
+    fn add_box(&mut self, corners: &[Point3<f32>], color: [f32; 3]) {
+        for i in 0..8 {
+            for bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.add_line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    pub fn add_normals(&mut self, model: &Matrix4<f32>, verts: &[Vertex], length: f32) {
+        for vert in verts {
+            let (x, y, z) = vert.get_position();
+            let (nx, ny, nz) = vert.get_normal();
+            let start = model.transform_point(&Point3::new(x, y, z));
+            let direction = model.transform_vector(&Vector3::new(nx, ny, nz)).normalize();
+            self.add_line(start, start + direction * length, NORMAL_COLOR);
+        }
+    }
+
+    /// Three great circles, one around each axis.
+    pub fn add_sphere(&mut self, center: &Point3<f32>, radius: f32, color: [f32; 3]) {
+        let segments = 32;
+        for axis in 0..3 {
+            let mut previous: Option<Point3<f32>> = None;
+            for i in 0..(segments + 1) {
+                let angle = i as f32 / segments as f32 * f32::consts::PI * 2.0;
+                let mut offset: Vector3<f32> = na::zero();
+                offset[(axis + 1) % 3] = f32::cos(angle) * radius;
+                offset[(axis + 2) % 3] = f32::sin(angle) * radius;
+                let point = center + offset;
+                if let Some(previous) = previous {
+                    self.add_line(previous, point, color);
+                }
+                previous = Some(point);
+            }
+        }
+    }
+
+    pub fn add_force_center(&mut self, center: &Point3<f32>) {
+        if self.forces {
+            self.add_cross(center, 2.0, FORCE_COLOR);
+        }
+    }
+
+    pub fn add_world(&mut self, world: &World<f32>) {
+        for collider in world.colliders() {
+            let position = collider.position();
+            if self.colliders {
+                if let Some(cuboid) = collider.shape().as_shape::<Cuboid<f32>>() {
+                    let half_extents = cuboid.half_extents() + Vector3::repeat(collider.data().margin());
+                    self.add_obb(position, &half_extents, COLLIDER_COLOR);
+                }
+            }
+            if self.aabbs {
+                let aabb = collider.shape().aabb(position);
+                self.add_aabb(aabb.mins(), aabb.maxs(), AABB_COLOR);
+            }
+            if self.velocities {
+                if let Some(rigid_body) = world.rigid_body(collider.data().body()) {
+                    let start = rigid_body.center_of_mass();
+                    self.add_line(start, start + rigid_body.velocity().linear, VELOCITY_COLOR);
+                }
+            }
+        }
+        if self.contacts {
+            for (_, _, manifold) in world.collision_world().contact_manifolds() {
+                for tracked in manifold.contacts() {
+                    let contact = &tracked.contact;
+                    self.add_cross(&contact.world1, 0.1, CONTACT_COLOR);
+                    self.add_line(contact.world1, contact.world1 + *contact.normal * 0.5, CONTACT_COLOR);
+                }
+            }
+        }
+    }
+
+    pub fn draw<S: Surface>(&self, display: &Display, target: &mut S, program: &Program, view: [[f32; 4]; 4], projection: [[f32; 4]; 4]) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let line_verts = VertexBuffer::new(display, &self.lines).unwrap();
+        let indices = index::NoIndices(index::PrimitiveType::LinesList);
+        let uniforms = uniform!{
+            view:       view,
+            projection: projection,
+        };
+        let params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLessOrEqual,
+                write: false,
+                .. Default::default()
+            },
+            line_width: Some(1.0),
+            .. Default::default()
+        };
+        target.draw(&line_verts, &indices, program, &uniforms, &params).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,610 @@
+use nphysics3d::solver::IntegrationParameters;
+use nphysics3d::force_generator::ForceGenerator;
+use nphysics3d::object::{BodyHandle, BodySet};
+use nphysics3d::algebra::Force3;
+use na::{Point3, Vector3, Rotation3, Unit};
+use noise::{NoiseFn, Perlin, Seedable};
+use std::f32;
+
+use scene_file::{SceneError, SceneTable};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FieldKind {
+    Attractor,
+    Repulsor,
+    Radial,
+    Vortex,
+    Wind,
+    Drag,
+    Turbulence,
+    Pulse
+}
+
+impl FieldKind {
+    pub fn all() -> [FieldKind; 8] {
+        [FieldKind::Attractor, FieldKind::Repulsor, FieldKind::Radial, FieldKind::Vortex, FieldKind::Wind, FieldKind::Drag, FieldKind::Turbulence, FieldKind::Pulse]
+    }
+
+    pub fn from_name(name: &str) -> Option<FieldKind> {
+        match name.to_uppercase().as_str() {
+            "ATTRACTOR"  => Some(FieldKind::Attractor),
+            "REPULSOR"   => Some(FieldKind::Repulsor),
+            "RADIAL"     => Some(FieldKind::Radial),
+            "VORTEX"     => Some(FieldKind::Vortex),
+            "WIND"       => Some(FieldKind::Wind),
+            "DRAG"       => Some(FieldKind::Drag),
+            "TURBULENCE" => Some(FieldKind::Turbulence),
+            "PULSE"      => Some(FieldKind::Pulse),
+            _            => None
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            FieldKind::Attractor  => "ATTRACTOR",
+            FieldKind::Repulsor   => "REPULSOR",
+            FieldKind::Radial     => "RADIAL",
+            FieldKind::Vortex     => "VORTEX",
+            FieldKind::Wind       => "WIND",
+            FieldKind::Drag       => "DRAG",
+            FieldKind::Turbulence => "TURBULENCE",
+            FieldKind::Pulse      => "PULSE"
+        }
+    }
+
+    /// Slider range for the field's strength in the debug panel.
+    pub fn get_strength_range(&self) -> (f32, f32) {
+        match *self {
+            FieldKind::Attractor  => (0.0, 2000.0),
+            FieldKind::Repulsor   => (-1000.0, 0.0),
+            FieldKind::Radial     => (-1.0, 1.0),
+            FieldKind::Vortex     => (0.0, 100.0),
+            FieldKind::Wind       => (0.0, 20.0),
+            FieldKind::Drag       => (0.0, 5.0),
+            FieldKind::Turbulence => (0.0, 50.0),
+            FieldKind::Pulse      => (-1.0, 1.0)
+        }
+    }
+
+    /// The field added when this kind is toggled on at runtime.
+    pub fn default_field(&self) -> Box<ForceField> {
+        FieldSpec::new(*self).build()
+    }
+
+    /// Which `FieldSpec` parameters the kind uses besides its strength.
+    fn get_parameters(&self) -> &'static [&'static str] {
+        match *self {
+            FieldKind::Attractor  => &["center", "orbit"],
+            FieldKind::Repulsor   => &["center", "orbit"],
+            FieldKind::Radial     => &["center"],
+            FieldKind::Vortex     => &["center", "axis", "radius"],
+            FieldKind::Wind       => &["direction"],
+            FieldKind::Drag       => &[],
+            FieldKind::Turbulence => &["scale", "speed"],
+            FieldKind::Pulse      => &["center", "frequency"]
+        }
+    }
+}
+
+/// A field's kind and whichever of its parameters a scene sets; the rest keep the kind's defaults.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FieldSpec {
+    pub kind: FieldKind,
+    pub strength: Option<f32>,
+    pub center: Option<Point3<f32>>,
+    /// Rotation applied to an attractor's centre every step, as an axis scaled by the angle.
+    pub orbit: Option<Vector3<f32>>,
+    pub axis: Option<Vector3<f32>>,
+    pub radius: Option<f32>,
+    pub direction: Option<Vector3<f32>>,
+    pub scale: Option<f32>,
+    pub speed: Option<f32>,
+    pub frequency: Option<f32>
+}
+
+impl FieldSpec {
+    pub fn new(kind: FieldKind) -> Self {
+        FieldSpec {
+            kind,
+            strength: None,
+            center: None,
+            orbit: None,
+            axis: None,
+            radius: None,
+            direction: None,
+            scale: None,
+            speed: None,
+            frequency: None
+        }
+    }
+
+    pub fn build(&self) -> Box<ForceField> {
+        let center = |default: Point3<f32>| self.center.unwrap_or(default);
+        let strength = |default: f32| self.strength.unwrap_or(default);
+        match self.kind {
+            FieldKind::Attractor  => Box::new(PointAttractor::new(center(Point3::new(0.0, 100.0, 0.0)), strength(500.0), self.orbit.unwrap_or(Vector3::new(10.0, 100.0, 10.0)))),
+            FieldKind::Repulsor   => Box::new(PointAttractor::new(center(Point3::origin()), strength(-200.0), self.orbit.unwrap_or(na::zero()))),
+            FieldKind::Radial     => Box::new(Radial::new(center(Point3::origin()), strength(0.1))),
+            FieldKind::Vortex     => Box::new(Vortex::new(center(Point3::origin()), self.axis.unwrap_or(Vector3::y()), strength(20.0), self.radius.unwrap_or(20.0))),
+            FieldKind::Wind       => Box::new(Wind::new(self.direction.unwrap_or(Vector3::x()).normalize() * strength(5.0))),
+            FieldKind::Drag       => Box::new(Drag::new(strength(0.5))),
+            FieldKind::Turbulence => Box::new(Turbulence::new(strength(10.0), self.scale.unwrap_or(0.05), self.speed.unwrap_or(0.2))),
+            FieldKind::Pulse      => Box::new(Pulse::new(Box::new(Radial::new(center(Point3::origin()), strength(0.1))), self.frequency.unwrap_or(0.6)))
+        }
+    }
+
+    /// Reads one `[[fields]]` entry, refusing parameters its kind has no use for.
+    pub fn read(table: &SceneTable) -> Result<Self, SceneError> {
+        let names: Vec<&str> = FieldKind::all().iter().map(|kind| kind.get_name()).collect();
+        let kind = table.get_name("kind", FieldKind::from_name, &names)?
+            .ok_or_else(|| table.error("kind", format!("missing, expected one of {}", names.join(", "))))?;
+        let mut keys = vec!["kind", "strength"];
+        keys.extend_from_slice(kind.get_parameters());
+        table.expect_keys(&keys)?;
+
+        let vector = |key: &str| table.get_vec3(key).map(|v| v.map(|v| Vector3::new(v[0], v[1], v[2])));
+        let nonzero = |key: &str| match vector(key)? {
+            Some(v) if v.norm() == 0.0 => Err(table.error(key, String::from("must not be all zeros"))),
+            v => Ok(v)
+        };
+        // A point attractor's kind follows the sign of its strength, so each kind keeps its own sign.
+        let (min, max) = match kind {
+            FieldKind::Attractor => (0.0, f32::MAX),
+            FieldKind::Repulsor  => (f32::MIN, 0.0),
+            _                    => (f32::MIN, f32::MAX)
+        };
+        let strength = table.get_f32_in("strength", min, max)?;
+        if kind == FieldKind::Repulsor && strength == Some(0.0) {
+            return Err(table.error("strength", String::from("a repulsor needs a negative strength")));
+        }
+        Ok(FieldSpec {
+            kind,
+            strength,
+            center: vector("center")?.map(Point3::from_coordinates),
+            orbit: vector("orbit")?,
+            axis: nonzero("axis")?,
+            radius: table.get_f32_in("radius", 1.0e-3, f32::MAX)?,
+            direction: nonzero("direction")?,
+            scale: table.get_f32_in("scale", 0.0, f32::MAX)?,
+            speed: table.get_f32("speed")?,
+            frequency: table.get_f32("frequency")?
+        })
+    }
+}
+
+/// A force acting on bodies as a function of where they are and how they move.
+pub trait ForceField: Send + Sync {
+    fn get_kind(&self) -> FieldKind;
+
+    /// The force on a body of the given mass, `time` seconds into the simulation.
+    fn force_at(&self, position: &Point3<f32>, velocity: &Vector3<f32>, mass: f32, time: f32) -> Vector3<f32>;
+
+    /// Advances any internal motion of the field by one step.
+    fn update(&mut self, _dt: f32) {}
+
+    fn get_center(&self) -> Option<Point3<f32>> {
+        None
+    }
+
+    /// Moves a field that has a centre; used when restoring snapshots.
+    fn set_center(&mut self, _center: Point3<f32>) {}
+
+    /// Scales the overall strength of the field, e.g. from the debug panel.
+    fn get_strength(&self) -> f32;
+    fn set_strength(&mut self, strength: f32);
+}
+
+/// Inverse-square pull towards a point that can orbit the origin; a negative strength repels.
+pub struct PointAttractor {
+    kind: FieldKind,
+    center: Point3<f32>,
+    strength: f32,
+    softening: f32,
+    rotation: Rotation3<f32>
+}
+
+impl PointAttractor {
+    pub fn new(center: Point3<f32>, strength: f32, angles: Vector3<f32>) -> Self {
+        PointAttractor {
+            kind: if strength < 0.0 { FieldKind::Repulsor } else { FieldKind::Attractor },
+            center,
+            strength,
+            softening: 0.001,
+            rotation: Rotation3::new(angles)
+        }
+    }
+
+    pub fn with_softening(mut self, softening: f32) -> Self {
+        self.softening = softening;
+        self
+    }
+}
+
+impl ForceField for PointAttractor {
+    fn get_kind(&self) -> FieldKind {
+        self.kind
+    }
+
+    fn force_at(&self, position: &Point3<f32>, _: &Vector3<f32>, _: f32, _: f32) -> Vector3<f32> {
+        let delta_pos: Vector3<f32> = position - self.center;
+        let mag = self.softening + delta_pos.norm();
+        delta_pos * -(self.strength * (1.0/f32::powi(mag, 2)))
+    }
+
+    fn update(&mut self, _: f32) {
+        self.center = self.rotation * self.center;
+    }
+
+    fn get_center(&self) -> Option<Point3<f32>> {
+        Some(self.center)
+    }
+
+    fn set_center(&mut self, center: Point3<f32>) {
+        self.center = center;
+    }
+
+    fn get_strength(&self) -> f32 {
+        self.strength
+    }
+
+    fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+    }
+}
+
+/// Pushes bodies away from a point proportionally to their distance.
+pub struct Radial {
+    center: Point3<f32>,
+    strength: f32
+}
+
+impl Radial {
+    pub fn new(center: Point3<f32>, strength: f32) -> Self {
+        Radial {
+            center,
+            strength
+        }
+    }
+}
+
+impl ForceField for Radial {
+    fn get_kind(&self) -> FieldKind {
+        FieldKind::Radial
+    }
+
+    fn force_at(&self, position: &Point3<f32>, _: &Vector3<f32>, _: f32, _: f32) -> Vector3<f32> {
+        (position - self.center) * self.strength
+    }
+
+    fn get_center(&self) -> Option<Point3<f32>> {
+        Some(self.center)
+    }
+
+    fn set_center(&mut self, center: Point3<f32>) {
+        self.center = center;
+    }
+
+    fn get_strength(&self) -> f32 {
+        self.strength
+    }
+
+    fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+    }
+}
+
+/// Swirls bodies around an axis, fading out with distance from it.
+pub struct Vortex {
+    center: Point3<f32>,
+    axis: Unit<Vector3<f32>>,
+    strength: f32,
+    radius: f32
+}
+
+impl Vortex {
+    pub fn new(center: Point3<f32>, axis: Vector3<f32>, strength: f32, radius: f32) -> Self {
+        Vortex {
+            center,
+            axis: Unit::new_normalize(axis),
+            strength,
+            radius
+        }
+    }
+}
+
+impl ForceField for Vortex {
+    fn get_kind(&self) -> FieldKind {
+        FieldKind::Vortex
+    }
+
+    fn force_at(&self, position: &Point3<f32>, _: &Vector3<f32>, _: f32, _: f32) -> Vector3<f32> {
+        let delta_pos: Vector3<f32> = position - self.center;
+        let radial = delta_pos - self.axis.as_ref() * delta_pos.dot(&self.axis);
+        let distance = radial.norm();
+        if distance < 1.0e-6 {
+            return na::zero();
+        }
+        let falloff = f32::exp(-distance / self.radius);
+        self.axis.cross(&radial).normalize() * self.strength * falloff
+    }
+
+    fn get_center(&self) -> Option<Point3<f32>> {
+        Some(self.center)
+    }
+
+    fn set_center(&mut self, center: Point3<f32>) {
+        self.center = center;
+    }
+
+    fn get_strength(&self) -> f32 {
+        self.strength
+    }
+
+    fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+    }
+}
+
+/// A constant acceleration in one direction, independent of position.
+pub struct Wind {
+    direction: Unit<Vector3<f32>>,
+    strength: f32
+}
+
+impl Wind {
+    pub fn new(force: Vector3<f32>) -> Self {
+        Wind {
+            direction: Unit::new_normalize(force),
+            strength: force.norm()
+        }
+    }
+}
+
+impl ForceField for Wind {
+    fn get_kind(&self) -> FieldKind {
+        FieldKind::Wind
+    }
+
+    fn force_at(&self, _: &Point3<f32>, _: &Vector3<f32>, mass: f32, _: f32) -> Vector3<f32> {
+        self.direction.as_ref() * self.strength * mass
+    }
+
+    fn get_strength(&self) -> f32 {
+        self.strength
+    }
+
+    fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+    }
+}
+
+/// Linear drag opposing the body's velocity.
+pub struct Drag {
+    coefficient: f32
+}
+
+impl Drag {
+    pub fn new(coefficient: f32) -> Self {
+        Drag {
+            coefficient
+        }
+    }
+}
+
+impl ForceField for Drag {
+    fn get_kind(&self) -> FieldKind {
+        FieldKind::Drag
+    }
+
+    fn force_at(&self, _: &Point3<f32>, velocity: &Vector3<f32>, _: f32, _: f32) -> Vector3<f32> {
+        velocity * -self.coefficient
+    }
+
+    fn get_strength(&self) -> f32 {
+        self.coefficient
+    }
+
+    fn set_strength(&mut self, strength: f32) {
+        self.coefficient = strength;
+    }
+}
+
+/// Divergence-free curl noise, so bodies swirl without bunching up.
+pub struct Turbulence {
+    noise: Perlin,
+    strength: f32,
+    scale: f32,
+    speed: f32
+}
+
+impl Turbulence {
+    pub fn new(strength: f32, scale: f32, speed: f32) -> Self {
+        Turbulence {
+            noise: Perlin::new().set_seed(7),
+            strength,
+            scale,
+            speed
+        }
+    }
+
+    // Three decorrelated potentials sampled from the same noise at offset positions.
+    fn potential(&self, p: &Vector3<f32>, time: f32) -> Vector3<f32> {
+        let t = time * self.speed;
+        let sample = |offset: f32| self.noise.get([
+            (p.x + offset) as f64,
+            (p.y + offset) as f64,
+            (p.z + t + offset) as f64
+        ]) as f32;
+        Vector3::new(sample(0.0), sample(31.4), sample(-57.2))
+    }
+}
+
+impl ForceField for Turbulence {
+    fn get_kind(&self) -> FieldKind {
+        FieldKind::Turbulence
+    }
+
+    fn force_at(&self, position: &Point3<f32>, _: &Vector3<f32>, mass: f32, time: f32) -> Vector3<f32> {
+        let e = 0.01;
+        let p = position.coords * self.scale;
+        let dx = (self.potential(&(p + Vector3::x() * e), time) - self.potential(&(p - Vector3::x() * e), time)) / (2.0 * e);
+        let dy = (self.potential(&(p + Vector3::y() * e), time) - self.potential(&(p - Vector3::y() * e), time)) / (2.0 * e);
+        let dz = (self.potential(&(p + Vector3::z() * e), time) - self.potential(&(p - Vector3::z() * e), time)) / (2.0 * e);
+        let curl = Vector3::new(
+            dy.z - dz.y,
+            dz.x - dx.z,
+            dx.y - dy.x
+        );
+        curl * self.strength * mass
+    }
+
+    fn get_strength(&self) -> f32 {
+        self.strength
+    }
+
+    fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+    }
+}
+
+/// Modulates another field with the sin⁴ envelope that `WorldForce` used.
+pub struct Pulse {
+    field: Box<ForceField>,
+    frequency: f32
+}
+
+impl Pulse {
+    pub fn new(field: Box<ForceField>, frequency: f32) -> Self {
+        Pulse {
+            field,
+            frequency
+        }
+    }
+}
+
+impl ForceField for Pulse {
+    fn get_kind(&self) -> FieldKind {
+        FieldKind::Pulse
+    }
+
+    fn force_at(&self, position: &Point3<f32>, velocity: &Vector3<f32>, mass: f32, time: f32) -> Vector3<f32> {
+        let t = time * self.frequency;
+        let envelope = f32::powi(f32::sin(t), 4) * f32::sin(t / 2.0);
+        self.field.force_at(position, velocity, mass, time) * envelope
+    }
+
+    fn update(&mut self, dt: f32) {
+        self.field.update(dt);
+    }
+
+    fn get_center(&self) -> Option<Point3<f32>> {
+        self.field.get_center()
+    }
+
+    fn set_center(&mut self, center: Point3<f32>) {
+        self.field.set_center(center);
+    }
+
+    fn get_strength(&self) -> f32 {
+        self.field.get_strength()
+    }
+
+    fn set_strength(&mut self, strength: f32) {
+        self.field.set_strength(strength);
+    }
+}
+
+/// A force generator summing any number of fields over a set of bodies.
+pub struct ForceFieldSet {
+    parts: Vec<BodyHandle>,
+    fields: Vec<Box<ForceField>>,
+    time: f32
+}
+
+impl ForceFieldSet {
+    pub fn new(parts: Vec<BodyHandle>) -> Self {
+        ForceFieldSet {
+            parts,
+            fields: Vec::new(),
+            time: 0.0
+        }
+    }
+
+    pub fn add_body_part(&mut self, body: BodyHandle) {
+        self.parts.push(body);
+    }
+
+    pub fn remove_body_part(&mut self, body: BodyHandle) {
+        self.parts.retain(|part| *part != body);
+    }
+
+    pub fn get_parts(&self) -> &[BodyHandle] {
+        &self.parts
+    }
+
+    pub fn add_field(&mut self, field: Box<ForceField>) {
+        self.fields.push(field);
+    }
+
+    pub fn remove_kind(&mut self, kind: FieldKind) {
+        self.fields.retain(|field| field.get_kind() != kind);
+    }
+
+    pub fn has_kind(&self, kind: FieldKind) -> bool {
+        self.fields.iter().any(|field| field.get_kind() == kind)
+    }
+
+    /// Removes every field of `kind`, or adds its default field when there is none.
+    pub fn toggle(&mut self, kind: FieldKind) {
+        if self.has_kind(kind) {
+            self.remove_kind(kind);
+        } else {
+            self.add_field(kind.default_field());
+        }
+    }
+
+    pub fn get_fields(&self) -> &[Box<ForceField>] {
+        &self.fields
+    }
+
+    pub fn get_fields_mut(&mut self) -> &mut [Box<ForceField>] {
+        &mut self.fields
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+}
+
+impl ForceGenerator<f32> for ForceFieldSet {
+    fn apply(&mut self, params: &IntegrationParameters<f32>, bodies: &mut BodySet<f32>) -> bool {
+        for field in self.fields.iter_mut() {
+            field.update(params.dt);
+        }
+        for handle in &self.parts {
+            if bodies.contains(*handle) {
+                let mut total: Vector3<f32> = na::zero();
+                {
+                    let part = bodies.body_part(*handle);
+                    let position = part.center_of_mass();
+                    let velocity = part.velocity().linear;
+                    let mass = part.inertia().linear;
+                    for field in &self.fields {
+                        total += field.force_at(&position, &velocity, mass, self.time);
+                    }
+                }
+                // Sleeping bodies ignore applied forces, so a field reaching one has to wake it.
+                if total != na::zero() {
+                    bodies.body_mut(*handle).activate();
+                    bodies.body_part_mut(*handle).apply_force(&Force3::linear(total));
+                }
+            }
+        }
+        self.time += params.dt;
+        true
+    }
+}
\ No newline at end of file
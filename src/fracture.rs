@@ -0,0 +1,256 @@
+use na::{Isometry3, Matrix3, Point3, Translation3, Vector3};
+use ncollide3d::shape::{Cuboid, ShapeHandle};
+use nphysics3d::algebra::Inertia3;
+use nphysics3d::math::Velocity;
+use nphysics3d::object::{BodyHandle, Material};
+use nphysics3d::world::World;
+use std::collections::{HashMap, HashSet};
+
+use voxel::*;
+
+/// A voxel held by a compound body, with its centre in the body's frame.
+#[derive(Copy, Clone, Debug)]
+pub struct VoxelCell {
+    pub voxel: Voxel,
+    pub offset: Vector3<f32>
+}
+
+/// One rigid body carrying a collider per voxel cell.
+pub struct VoxelBody {
+    pub handle: BodyHandle,
+    pub cells: Vec<VoxelCell>
+}
+
+/// Adds a body made of cubes of side `cell_size`, placed at `local` positions within `frame`.
+///
+/// The body's origin is put at the cells' centre of mass and its inertia is the sum of every
+/// cell's own inertia moved there with the parallel axis theorem.
+pub fn add_voxel_body(world: &mut World<f32>, frame: &Isometry3<f32>, cells: &[(Voxel, Vector3<f32>)], cell_size: f32, density: f32, margin: f32, velocity: Velocity<f32>) -> VoxelBody {
+    let cell_mass = density * cell_size * cell_size * cell_size;
+    let mut center: Vector3<f32> = na::zero();
+    for &(_, local) in cells {
+        center += local;
+    }
+    center /= cells.len() as f32;
+
+    let mut angular: Matrix3<f32> = na::zero();
+    for &(_, local) in cells {
+        let d = local - center;
+        let own = cell_mass * cell_size * cell_size / 6.0;
+        angular += Matrix3::identity() * (own + cell_mass * d.norm_squared()) - d * d.transpose() * cell_mass;
+    }
+    let inertia = Inertia3::new(cell_mass * cells.len() as f32, angular);
+
+    let pose = frame * Translation3::from_vector(center);
+    let handle = world.add_rigid_body(pose, inertia, Point3::origin());
+    let geom = ShapeHandle::new(Cuboid::new(Vector3::repeat(cell_size * 0.5 - margin)));
+    let mut body_cells: Vec<VoxelCell> = Vec::with_capacity(cells.len());
+    for &(voxel, local) in cells {
+        let offset = local - center;
+        world.add_collider(margin, geom.clone(), handle, Isometry3::new(offset, na::zero()), Material::default());
+        body_cells.push(VoxelCell { voxel, offset });
+    }
+    if let Some(rigid_body) = world.rigid_body_mut(handle) {
+        rigid_body.set_velocity(velocity);
+    }
+    VoxelBody {
+        handle,
+        cells: body_cells
+    }
+}
+
+/// A compound body that broke, and the bodies that replaced it.
+pub struct FractureEvent {
+    pub removed: BodyHandle,
+    pub fragments: Vec<VoxelBody>
+}
+
+/// Watches voxel bodies and splits them into connected fragments after a hard enough hit.
+pub struct Fracture {
+    bodies: Vec<VoxelBody>,
+    cell_size: f32,
+    density: f32,
+    margin: f32,
+    /// Momentum change in one step, in newton-seconds, that breaks a body touching something.
+    pub threshold: f32,
+    /// Cells whose centres lie this close to the impact come off as single pieces.
+    pub break_radius: f32,
+    velocities: HashMap<BodyHandle, Vector3<f32>>,
+    pending: Vec<(usize, Point3<f32>)>
+}
+
+impl Fracture {
+    pub fn new(cell_size: f32, density: f32, margin: f32) -> Self {
+        Fracture {
+            bodies: Vec::new(),
+            cell_size,
+            density,
+            margin,
+            threshold: 40.0,
+            break_radius: 1.0,
+            velocities: HashMap::new(),
+            pending: Vec::new()
+        }
+    }
+
+    pub fn get_cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn get_bodies(&self) -> &[VoxelBody] {
+        &self.bodies
+    }
+
+    /// Adds a grid of voxels as one unbroken body with its grid origin at `origin`.
+    pub fn add_grid(&mut self, world: &mut World<f32>, grid: &VoxelGrid, origin: &Isometry3<f32>) -> &VoxelBody {
+        let cells: Vec<(Voxel, Vector3<f32>)> = grid.voxels.iter()
+            .map(|voxel| {
+                let (x, y, z) = voxel.coords;
+                (*voxel, Vector3::new(x as f32, y as f32, z as f32) * self.cell_size)
+            })
+            .collect();
+        let body = add_voxel_body(world, origin, &cells, self.cell_size, self.density, self.margin, Velocity::zero());
+        self.bodies.push(body);
+        &self.bodies[self.bodies.len() - 1]
+    }
+
+    /// True when the body will be split at the start of the next step.
+    pub fn is_breaking(&self, handle: BodyHandle) -> bool {
+        self.pending.iter().any(|&(index, _)| self.bodies[index].handle == handle)
+    }
+
+    /// Remembers velocities so the next `detect_breaks` can measure the change a step made.
+    pub fn record_velocities(&mut self, world: &World<f32>) {
+        self.velocities.clear();
+        for body in &self.bodies {
+            if let Some(rigid_body) = world.rigid_body(body.handle) {
+                self.velocities.insert(body.handle, rigid_body.velocity().linear);
+            }
+        }
+    }
+
+    /// Queues every body in contact whose momentum jumped past the threshold, at its mean contact point.
+    pub fn detect_breaks(&mut self, world: &World<f32>) {
+        let mut contacts: HashMap<BodyHandle, (Vector3<f32>, usize)> = HashMap::new();
+        for (co1, co2, manifold) in world.collision_world().contact_manifolds() {
+            for tracked in manifold.contacts() {
+                let contact = &tracked.contact;
+                for &(collider, point) in &[(co1, contact.world1), (co2, contact.world2)] {
+                    let entry = contacts.entry(collider.data().body()).or_insert((na::zero(), 0));
+                    entry.0 += point.coords;
+                    entry.1 += 1;
+                }
+            }
+        }
+
+        for (index, body) in self.bodies.iter().enumerate() {
+            if body.cells.len() < 2 || self.pending.iter().any(|&(pending, _)| pending == index) {
+                continue;
+            }
+            let (before, rigid_body) = match (self.velocities.get(&body.handle), world.rigid_body(body.handle)) {
+                (Some(before), Some(rigid_body)) => (*before, rigid_body),
+                _ => continue
+            };
+            let impulse = (rigid_body.velocity().linear - before).norm() * rigid_body.local_inertia().linear;
+            if impulse < self.threshold {
+                continue;
+            }
+            if let Some(&(sum, count)) = contacts.get(&body.handle) {
+                self.pending.push((index, Point3::from_coordinates(sum / count as f32)));
+            }
+        }
+    }
+
+    /// Replaces every queued body with one body per connected group of the cells that survive.
+    pub fn apply_breaks(&mut self, world: &mut World<f32>) -> Vec<FractureEvent> {
+        let mut pending: Vec<(usize, Point3<f32>)> = self.pending.drain(..).collect();
+        // Remove from the back so earlier indices stay valid.
+        pending.sort_by(|a, b| b.0.cmp(&a.0));
+        let mut events: Vec<FractureEvent> = Vec::new();
+        for (index, impact) in pending {
+            let body = self.bodies.swap_remove(index);
+            let (pose, velocity, center) = match world.rigid_body(body.handle) {
+                Some(rigid_body) => (rigid_body.position(), *rigid_body.velocity(), rigid_body.center_of_mass()),
+                None => continue
+            };
+            let groups = self.split(&body, &(pose.inverse() * impact));
+            world.remove_bodies(&[body.handle]);
+
+            let mut fragments: Vec<VoxelBody> = Vec::with_capacity(groups.len());
+            for group in groups {
+                let cells: Vec<(Voxel, Vector3<f32>)> = group.iter()
+                    .map(|&i| (body.cells[i].voxel, body.cells[i].offset))
+                    .collect();
+                let mut fragment_center: Vector3<f32> = na::zero();
+                for &(_, local) in &cells {
+                    fragment_center += local;
+                }
+                fragment_center /= cells.len() as f32;
+                // Each piece keeps the velocity its material had as part of the spinning whole.
+                let arm = pose * Point3::from_coordinates(fragment_center) - center;
+                let fragment_velocity = Velocity::new(velocity.linear + velocity.angular.cross(&arm), velocity.angular);
+                fragments.push(add_voxel_body(world, &pose, &cells, self.cell_size, self.density, self.margin, fragment_velocity));
+            }
+            events.push(FractureEvent {
+                removed: body.handle,
+                fragments: fragments.iter().map(|fragment| VoxelBody {
+                    handle: fragment.handle,
+                    cells: fragment.cells.clone()
+                }).collect()
+            });
+            self.bodies.extend(fragments);
+        }
+        events
+    }
+
+    /// Groups cell indices into face-connected pieces after detaching the cells near `impact`.
+    fn split(&self, body: &VoxelBody, impact: &Point3<f32>) -> Vec<Vec<usize>> {
+        let mut loose: HashSet<usize> = HashSet::new();
+        let mut nearest = 0;
+        let mut nearest_distance = f32::max_value();
+        for (i, cell) in body.cells.iter().enumerate() {
+            let distance = (cell.offset - impact.coords).norm();
+            if distance <= self.break_radius {
+                loose.insert(i);
+            }
+            if distance < nearest_distance {
+                nearest = i;
+                nearest_distance = distance;
+            }
+        }
+        loose.insert(nearest);
+
+        let by_coords: HashMap<(i32, i32, i32), usize> = body.cells.iter()
+            .enumerate()
+            .map(|(i, cell)| (cell.voxel.coords, i))
+            .collect();
+        let mut visited: Vec<bool> = vec![false; body.cells.len()];
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for start in 0..body.cells.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            if loose.contains(&start) {
+                groups.push(vec![start]);
+                continue;
+            }
+            let mut group: Vec<usize> = Vec::new();
+            let mut stack: Vec<usize> = vec![start];
+            while let Some(i) = stack.pop() {
+                group.push(i);
+                let (x, y, z) = body.cells[i].voxel.coords;
+                for &(dx, dy, dz) in &[(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)] {
+                    if let Some(&j) = by_coords.get(&(x + dx, y + dy, z + dz)) {
+                        if !visited[j] && !loose.contains(&j) {
+                            visited[j] = true;
+                            stack.push(j);
+                        }
+                    }
+                }
+            }
+            groups.push(group);
+        }
+        groups
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+use overlay::*;
+
+const GRAPH_SAMPLES: usize = 120;
+const GRAPH_HEIGHT:  f32 = 60.0;
+const TEXT_SCALE:    f32 = 2.0;
+const TARGET_FRAME_MS: f32 = 1000.0 / 60.0;
+
+pub struct Hud {
+    pub visible: bool,
+    last_frame: Instant,
+    frame_times: Vec<f32>,
+    next_sample: usize,
+    lines: Vec<String>
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Hud {
+            visible: true,
+            last_frame: Instant::now(),
+            frame_times: Vec::with_capacity(GRAPH_SAMPLES),
+            next_sample: 0,
+            lines: Vec::new()
+        }
+    }
+
+    /// Records the time since the previous call and returns it in milliseconds.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let frame_ms = duration_ms(now - self.last_frame);
+        self.last_frame = now;
+        if self.frame_times.len() < GRAPH_SAMPLES {
+            self.frame_times.push(frame_ms);
+        } else {
+            self.frame_times[self.next_sample] = frame_ms;
+        }
+        self.next_sample = (self.next_sample + 1) % GRAPH_SAMPLES;
+        frame_ms
+    }
+
+    pub fn get_frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    pub fn get_fps(&self) -> f32 {
+        let frame_ms = self.get_frame_time();
+        if frame_ms > 0.0 { 1000.0 / frame_ms } else { 0.0 }
+    }
+
+    pub fn add_line(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    /// Lays out the queued lines and the frame time graph, then forgets the lines.
+    pub fn build(&mut self, overlay: &mut Overlay, window_size: [f32; 2]) {
+        let line_height = (GLYPH_HEIGHT as f32 + 2.0) * TEXT_SCALE;
+        let width = self.lines.iter()
+            .map(|line| Overlay::text_width(line, TEXT_SCALE))
+            .fold(0.0, f32::max);
+        overlay.add_rect(4.0, 4.0, width + 8.0, line_height * self.lines.len() as f32 + 4.0, [0.0, 0.0, 0.0, 0.5]);
+        for (i, line) in self.lines.iter().enumerate() {
+            overlay.add_text(8.0, 8.0 + line_height * i as f32, TEXT_SCALE, line, [1.0, 1.0, 1.0, 1.0]);
+        }
+        self.lines.clear();
+
+        let bar_width = 2.0;
+        let base = window_size[1] - 8.0;
+        let scale = GRAPH_HEIGHT / (TARGET_FRAME_MS * 2.0);
+        overlay.add_rect(4.0, base - GRAPH_HEIGHT, bar_width * GRAPH_SAMPLES as f32 + 8.0, GRAPH_HEIGHT + 4.0, [0.0, 0.0, 0.0, 0.5]);
+        let count = self.frame_times.len();
+        for i in 0..count {
+            // Oldest sample first so the graph scrolls to the left.
+            let frame_ms = self.frame_times[(self.next_sample + i) % count];
+            let height = f32::min(frame_ms * scale, GRAPH_HEIGHT);
+            let color = if frame_ms <= TARGET_FRAME_MS { [0.2, 1.0, 0.2, 0.9] } else { [1.0, 0.2, 0.2, 0.9] };
+            overlay.add_rect(8.0 + bar_width * i as f32, base - height, bar_width, height, color);
+        }
+        overlay.add_rect(8.0, base - TARGET_FRAME_MS * scale, bar_width * GRAPH_SAMPLES as f32, 1.0, [1.0, 1.0, 0.0, 0.6]);
+    }
+}
+
+pub fn duration_ms(duration: Duration) -> f32 {
+    duration.as_secs() as f32 * 1000.0 + duration.subsec_nanos() as f32 / 1_000_000.0
+}
\ No newline at end of file
@@ -0,0 +1,253 @@
+use nphysics3d::solver::IntegrationParameters;
+use nphysics3d::force_generator::ForceGenerator;
+use nphysics3d::object::{BodyHandle, BodySet};
+use nphysics3d::algebra::Force3;
+use na::{Point3, Vector3};
+use std::collections::HashSet;
+use std::f32;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BlastKind {
+    /// Pushes everything in range outward in a single step.
+    Explosion,
+    /// Pushes bodies outward as an expanding front reaches them.
+    Shockwave,
+    /// Pulls everything in range towards the centre in a single step.
+    Implosion
+}
+
+impl BlastKind {
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            BlastKind::Explosion => "EXPLOSION",
+            BlastKind::Shockwave => "SHOCKWAVE",
+            BlastKind::Implosion => "IMPLOSION"
+        }
+    }
+}
+
+/// How an impulse weakens between the centre and the edge of the blast radius.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Falloff {
+    Constant,
+    Linear,
+    InverseSquare
+}
+
+impl Falloff {
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            Falloff::Constant      => "CONSTANT",
+            Falloff::Linear        => "LINEAR",
+            Falloff::InverseSquare => "INVERSE SQUARE"
+        }
+    }
+
+    pub fn next(&self) -> Falloff {
+        match *self {
+            Falloff::Constant      => Falloff::Linear,
+            Falloff::Linear        => Falloff::InverseSquare,
+            Falloff::InverseSquare => Falloff::Constant
+        }
+    }
+
+    /// Scale in [0, 1] at `distance` from the centre of a blast of the given radius.
+    pub fn scale(&self, distance: f32, radius: f32) -> f32 {
+        if distance > radius {
+            return 0.0;
+        }
+        match *self {
+            Falloff::Constant      => 1.0,
+            Falloff::Linear        => 1.0 - distance / radius,
+            Falloff::InverseSquare => 1.0 / f32::max(distance * distance, 1.0)
+        }
+    }
+}
+
+/// Parameters used for the next blast that gets triggered.
+#[derive(Copy, Clone, Debug)]
+pub struct BlastSettings {
+    pub radius: f32,
+    /// Impulse at the centre, in newton-seconds.
+    pub strength: f32,
+    pub falloff: Falloff,
+    /// How fast a shockwave front travels, in metres per second.
+    pub speed: f32
+}
+
+impl Default for BlastSettings {
+    fn default() -> Self {
+        BlastSettings {
+            radius: 8.0,
+            strength: 20.0,
+            falloff: Falloff::Linear,
+            speed: 15.0
+        }
+    }
+}
+
+pub struct Blast {
+    kind: BlastKind,
+    center: Point3<f32>,
+    settings: BlastSettings,
+    age: f32,
+    hit: HashSet<BodyHandle>
+}
+
+impl Blast {
+    pub fn get_kind(&self) -> BlastKind {
+        self.kind
+    }
+
+    pub fn get_center(&self) -> Point3<f32> {
+        self.center
+    }
+
+    pub fn get_radius(&self) -> f32 {
+        self.settings.radius
+    }
+
+    /// How far the blast has reached; instant blasts cover their whole radius at once.
+    pub fn get_front(&self) -> f32 {
+        match self.kind {
+            BlastKind::Shockwave => f32::min(self.age * self.settings.speed, self.settings.radius),
+            _ => self.settings.radius
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        match self.kind {
+            BlastKind::Shockwave => self.age * self.settings.speed > self.settings.radius,
+            _ => self.age > 0.0
+        }
+    }
+}
+
+/// Applies queued explosions, shockwaves and implosions to the registered bodies.
+pub struct Impulses {
+    parts: Vec<BodyHandle>,
+    settings: BlastSettings,
+    blasts: Vec<Blast>
+}
+
+impl Impulses {
+    pub fn new(parts: Vec<BodyHandle>) -> Self {
+        Impulses {
+            parts,
+            settings: BlastSettings::default(),
+            blasts: Vec::new()
+        }
+    }
+
+    pub fn add_body_part(&mut self, body: BodyHandle) {
+        self.parts.push(body);
+    }
+
+    /// Also forgets the body in running blasts, since the world may hand its handle to a new body.
+    pub fn remove_body_part(&mut self, body: BodyHandle) {
+        self.parts.retain(|part| *part != body);
+        for blast in self.blasts.iter_mut() {
+            blast.hit.remove(&body);
+        }
+    }
+
+    pub fn get_parts(&self) -> &[BodyHandle] {
+        &self.parts
+    }
+
+    pub fn get_settings(&self) -> &BlastSettings {
+        &self.settings
+    }
+
+    pub fn get_settings_mut(&mut self) -> &mut BlastSettings {
+        &mut self.settings
+    }
+
+    pub fn get_blasts(&self) -> &[Blast] {
+        &self.blasts
+    }
+
+    /// Queues a blast at `center` with the current settings; it acts from the next step.
+    pub fn trigger(&mut self, kind: BlastKind, center: Point3<f32>) {
+        self.blasts.push(Blast {
+            kind,
+            center,
+            settings: self.settings,
+            age: 0.0,
+            hit: HashSet::new()
+        });
+    }
+}
+
+impl ForceGenerator<f32> for Impulses {
+    fn apply(&mut self, params: &IntegrationParameters<f32>, bodies: &mut BodySet<f32>) -> bool {
+        if params.dt <= 0.0 {
+            return true;
+        }
+        for blast in self.blasts.iter_mut() {
+            blast.age += params.dt;
+            let front = blast.get_front();
+            for handle in &self.parts {
+                if !bodies.contains(*handle) || blast.hit.contains(handle) {
+                    continue;
+                }
+                let away = bodies.body_part(*handle).center_of_mass() - blast.center;
+                let distance = away.norm();
+                if distance > front {
+                    continue;
+                }
+                let direction = if distance > 1.0e-4 { away / distance } else { Vector3::y() };
+                let impulse = blast.settings.strength * blast.settings.falloff.scale(distance, blast.settings.radius);
+                let sign = if blast.kind == BlastKind::Implosion { -1.0 } else { 1.0 };
+                // A force held for one step delivers the impulse; sleeping bodies ignore forces until woken.
+                bodies.body_mut(*handle).activate();
+                bodies.body_part_mut(*handle).apply_force(&Force3::linear(direction * (sign * impulse / params.dt)));
+                blast.hit.insert(*handle);
+            }
+        }
+        self.blasts.retain(|blast| !blast.is_finished());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::{self, Isometry3};
+    use physics_scene::{capture_body_states, body_handles, BlockSpec, PhysicsScene, SceneOptions, ScenePreset};
+
+    #[test]
+    fn blast_wakes_a_settled_stack() {
+        let mut options = SceneOptions::for_preset(ScenePreset::Empty);
+        // Three blocks resting on the floor, whose top sits at y = -6.
+        options.blocks = (0..3).map(|level| BlockSpec {
+            half_extents: Vector3::repeat(0.5),
+            position: Isometry3::new(Vector3::new(0.0, -5.5 + level as f32, 0.0), na::zero()),
+            color: [0.8, 0.8, 0.8]
+        }).collect();
+        let mut scene = PhysicsScene::new(&options);
+        let handles = body_handles(&scene.cubes);
+        let asleep = |scene: &PhysicsScene| handles.iter()
+            .all(|handle| scene.world.rigid_body(*handle).map_or(false, |body| !body.is_active()));
+        let mut steps = 0;
+        while !asleep(&scene) {
+            assert!(steps < 2000, "the stack never fell asleep");
+            scene.step();
+            steps += 1;
+        }
+
+        let before = capture_body_states(&scene.world, &scene.cubes);
+        if let Ok(impulses) = scene.world.force_generator_mut(scene.impulses_handle).downcast_mut::<Impulses>() {
+            impulses.trigger(BlastKind::Explosion, Point3::new(0.0, -6.0, 0.0));
+        }
+        for _ in 0..10 {
+            scene.step();
+        }
+        let after = capture_body_states(&scene.world, &scene.cubes);
+        assert_eq!(before.len(), after.len());
+        for (start, end) in before.iter().zip(after.iter()) {
+            let moved = (end.position.translation.vector - start.position.translation.vector).norm();
+            assert!(moved > 1.0e-2, "body {:?} moved only {}", start.handle, moved);
+        }
+    }
+}
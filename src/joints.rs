@@ -0,0 +1,150 @@
+use nphysics3d::solver::IntegrationParameters;
+use nphysics3d::force_generator::ForceGenerator;
+use nphysics3d::joint::{BallConstraint, ConstraintHandle, FixedConstraint, PrismaticConstraint, RevoluteConstraint};
+use nphysics3d::object::{BodyHandle, BodySet};
+use nphysics3d::algebra::Force3;
+use nphysics3d::world::World;
+use na::{Isometry3, Point3, Unit, Vector3};
+
+/// The nphysics joint constraints a pair of cubes can be connected with.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum JointKind {
+    Fixed,
+    Ball,
+    /// Hinge around a world-space axis.
+    Revolute(Vector3<f32>),
+    /// Slider along a world-space axis.
+    Prismatic(Vector3<f32>)
+}
+
+/// Joins two bodies at a world-space anchor, using their current poses to find local anchors.
+///
+/// Either body can be `BodyHandle::ground()` to pin the other in place.
+pub fn connect(world: &mut World<f32>, a: BodyHandle, b: BodyHandle, kind: JointKind, anchor: Point3<f32>) -> ConstraintHandle {
+    let pose_a = world.body_part(a).position();
+    let pose_b = world.body_part(b).position();
+    let local_a = pose_a.inverse() * anchor;
+    let local_b = pose_b.inverse() * anchor;
+    match kind {
+        JointKind::Fixed => {
+            let frame = Isometry3::new(anchor.coords, na::zero());
+            world.add_constraint(FixedConstraint::new(a, b, pose_a.inverse() * frame, pose_b.inverse() * frame))
+        },
+        JointKind::Ball => {
+            world.add_constraint(BallConstraint::new(a, b, local_a, local_b))
+        },
+        JointKind::Revolute(axis) => {
+            let axis_a = Unit::new_normalize(pose_a.rotation.inverse() * axis);
+            let axis_b = Unit::new_normalize(pose_b.rotation.inverse() * axis);
+            world.add_constraint(RevoluteConstraint::new(a, b, local_a, axis_a, local_b, axis_b))
+        },
+        JointKind::Prismatic(axis) => {
+            let axis_a = Unit::new_normalize(pose_a.rotation.inverse() * axis);
+            world.add_constraint(PrismaticConstraint::new(a, b, local_a, axis_a, local_b))
+        }
+    }
+}
+
+/// A damped spring between anchor points given in each body's local frame.
+#[derive(Copy, Clone, Debug)]
+pub struct Spring {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    pub anchor_a: Point3<f32>,
+    pub anchor_b: Point3<f32>,
+    pub rest_length: f32,
+    pub stiffness: f32,
+    pub damping: f32
+}
+
+impl Spring {
+    /// A spring between two world-space points, at rest at their current distance.
+    pub fn between(world: &World<f32>, a: BodyHandle, point_a: Point3<f32>, b: BodyHandle, point_b: Point3<f32>, stiffness: f32, damping: f32) -> Self {
+        Spring {
+            a,
+            b,
+            anchor_a: world.body_part(a).position().inverse() * point_a,
+            anchor_b: world.body_part(b).position().inverse() * point_b,
+            rest_length: na::distance(&point_a, &point_b),
+            stiffness,
+            damping
+        }
+    }
+
+    /// Both anchors in world space.
+    pub fn get_endpoints(&self, world: &World<f32>) -> (Point3<f32>, Point3<f32>) {
+        (world.body_part(self.a).position() * self.anchor_a, world.body_part(self.b).position() * self.anchor_b)
+    }
+}
+
+/// Applies every registered spring's force at its anchors, so off-centre springs also twist bodies.
+pub struct Springs {
+    springs: Vec<Spring>
+}
+
+impl Springs {
+    pub fn new() -> Self {
+        Springs {
+            springs: Vec::new()
+        }
+    }
+
+    pub fn add_spring(&mut self, spring: Spring) {
+        self.springs.push(spring);
+    }
+
+    pub fn get_springs(&self) -> &[Spring] {
+        &self.springs
+    }
+
+    pub fn set_stiffness(&mut self, stiffness: f32) {
+        for spring in self.springs.iter_mut() {
+            spring.stiffness = stiffness;
+        }
+    }
+}
+
+impl ForceGenerator<f32> for Springs {
+    fn apply(&mut self, _: &IntegrationParameters<f32>, bodies: &mut BodySet<f32>) -> bool {
+        for spring in &self.springs {
+            if !bodies.contains(spring.a) || !bodies.contains(spring.b) {
+                continue;
+            }
+            let (point_a, velocity_a, center_a) = anchor_state(bodies, spring.a, &spring.anchor_a);
+            let (point_b, velocity_b, center_b) = anchor_state(bodies, spring.b, &spring.anchor_b);
+            let delta = point_b - point_a;
+            let length = delta.norm();
+            if length < 1.0e-6 {
+                continue;
+            }
+            let direction = delta / length;
+            let stretch = length - spring.rest_length;
+            let closing = (velocity_b - velocity_a).dot(&direction);
+            let force = direction * (spring.stiffness * stretch + spring.damping * closing);
+            if force == na::zero() {
+                continue;
+            }
+
+            if !spring.a.is_ground() {
+                let torque = (point_a - center_a).cross(&force);
+                bodies.body_mut(spring.a).activate();
+                bodies.body_part_mut(spring.a).apply_force(&Force3::new(force, torque));
+            }
+            if !spring.b.is_ground() {
+                let torque = (point_b - center_b).cross(&-force);
+                bodies.body_mut(spring.b).activate();
+                bodies.body_part_mut(spring.b).apply_force(&Force3::new(-force, torque));
+            }
+        }
+        true
+    }
+}
+
+/// World-space anchor position, its velocity, and the body's centre of mass.
+fn anchor_state(bodies: &BodySet<f32>, handle: BodyHandle, anchor: &Point3<f32>) -> (Point3<f32>, Vector3<f32>, Point3<f32>) {
+    let part = bodies.body_part(handle);
+    let point = part.position() * anchor;
+    let center = part.center_of_mass();
+    let velocity = part.velocity();
+    (point, velocity.linear + velocity.angular.cross(&(point - center)), center)
+}
@@ -8,13 +8,40 @@ extern crate nalgebra_glm as glm;
 extern crate ncollide3d;
 extern crate nphysics3d;
 extern crate rayon;
+extern crate noise;
+extern crate hound;
+extern crate rodio;
+extern crate png;
+extern crate toml;
 
 
 mod vertex;
 mod cube;
 mod input;
 mod cubody;
-mod world_force;
+mod force_field;
+mod barnes_hut;
+mod boids;
+mod impulse;
+mod joints;
+mod voxel;
+mod fracture;
+mod contact_events;
+mod rng;
+mod physics_scene;
+mod snapshot;
+mod debug_draw;
+mod overlay;
+mod hud;
+mod ui;
+mod picking;
+mod particles;
+mod mesh;
+mod shapes;
+mod cloth;
+mod audio;
+mod capture;
+mod scene_file;
 
 use rayon::prelude::*;
 use std::sync::{Arc, Mutex};
@@ -22,6 +49,10 @@ use glium::*;
 use na::{Matrix4, geometry, Vector3, Vector6, Vector2, Isometry3, Point3};
 use glm::*;
 use std::f32;
+use std::time::Instant;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use ncollide3d::shape::{Ball, Cuboid, ShapeHandle};
 use nphysics3d::object::{BodyHandle, Material, RigidBody};
 use nphysics3d::volumetric::Volumetric;
@@ -32,13 +63,65 @@ use nphysics3d::algebra::Force3;
 use cube::*;
 use input::*;
 use cubody::*;
-use world_force::*;
-
-const COLLIDER_MARGIN: f32 = 0.01;
+use force_field::*;
+use barnes_hut::*;
+use boids::*;
+use impulse::*;
+use joints::*;
+use contact_events::*;
+use physics_scene::*;
+use snapshot::*;
+use debug_draw::*;
+use overlay::*;
+use hud::*;
+use ui::*;
+use picking::*;
+use particles::*;
+use shapes::Sheet;
+use cloth::*;
+use audio::*;
+use capture::*;
+use scene_file::*;
+use vertex::Vertex;
+
+const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+const SNAPSHOT_FRAMES: usize = 600;
+const BLAST_DISTANCE: f32 = 15.0;
+const BLAST_COLOR: [f32; 3] = [1.0, 0.5, 0.0];
+const SPRING_COLOR: [f32; 3] = [0.6, 1.0, 0.2];
+const FLASH_COLOR: [f32; 3] = [1.0, 0.9, 0.6];
+const FLASH_IMPULSE: f32 = 5.0;
+const FLASH_DECAY: f32 = 0.9;
+const SPARK_IMPULSE: f32 = 2.0;
+const DUST_IMPULSE: f32 = 1.0;
+const SNAPSHOT_PATH: &str = "snapshot.txt";
+const CLOTH_COLOR: [f32; 3] = [0.8, 0.2, 0.3];
+const CLOTH_SIZE: f32 = 12.0;
+const CLOTH_SEGMENTS: usize = 30;
+/// How far above the crosshair's target a new cloth is dropped from.
+const CLOTH_DROP: f32 = 8.0;
+/// Colour cubes pulse towards with the energy of their frequency band.
+const AUDIO_COLOR: [f32; 3] = [0.2, 0.6, 1.0];
+/// How much stronger than its own strength the bass can make an attractor.
+const AUDIO_BOOST: f32 = 1.5;
+const SCREENSHOT_PREFIX: &str = "screenshot";
+const POSTER_PREFIX: &str = "poster";
 
 fn main() {
     use glium::{glutin, Surface};
-    let mut dimensions: [f32; 2] = [800.0, 600.0];
+    let mut render = RenderSettings::default();
+    let mut camera = CameraSettings::new([0.0, 0.0, 20.0], -88.0, 0.0, 1.0);
+    let mut light = LightSettings::new((0.0, 0.0, 0.0), [1.0, 1.0, 1.0]);
+    let mut scene_options = SceneOptions::default();
+    read_scene_from_args(|scene| {
+        scene.read_view(&mut render, &mut camera, &mut light)?;
+        scene_options.read(&scene.get_root())
+    });
+    // As in `simulate`, --preset wins over the scene file's.
+    if let Some(preset) = preset_from_args() {
+        scene_options.set_preset(preset);
+    }
+    let mut dimensions: [f32; 2] = [render.width, render.height];
     let mut event_loop = glutin::EventsLoop::new();
 
      let monitor = event_loop.get_available_monitors().nth(1);
@@ -50,62 +133,11 @@ fn main() {
     display.gl_window().hide_cursor(true);
     //display.gl_window().grab_cursor(true);
 
-    let mut world = World::<f32>::new();
-    world.set_gravity(Vector3::new(0.0, 0.0, 0.0));
-//    let mut world_force = WorldForce::new(Vec::new());
-//    let mut implode = Attractor::new(Vec::new(), Point3::new(0.0, 0.0, 0.0), 6.0, Vector3::new(0.0, 0.0, 0.0));
-    let mut planet  = Attractor::new(Vec::new(), Point3::new(0.0, 100.0, 0.0), 500.0, Vector3::new(10.0, 100.0, 10.0));
-//    let mut planet2 = Attractor::new(Vec::new(), Point3::new(0.0, -80.0, 0.0), 150.0, Vector3::new(0.001, 0.002, 0.001));
-    let geom = ShapeHandle::new(Cuboid::new(Vector3::repeat(0.5-COLLIDER_MARGIN)));
-    let inertia = geom.inertia(1.1);
-    let center_of_mass = geom.center_of_mass();
-
-    let light_position: (f32, f32, f32) = (0.0, 0.0, 0.0);
-    let cube_iter = 5;
-    let mut cubes: Vec<Cubody> = Vec::new();
-    let cube_resolution = 1.0;
-    for i in -cube_iter..cube_iter {
-        for j in -cube_iter..cube_iter {
-            for k in -cube_iter..cube_iter {
-                let fi = i as f32;
-                let fj = j as f32;
-                let fk = k as f32;
-                let hpi = 1.0/(0.1+hypot(fi, fj));
-                let hpj = 1.0/(0.1+hypot(fk, fj));
-                let hpk = 1.0/(0.1+hypot(fi, fk));
-                let location: Vector3<f32> = Vector3::new(fi*cube_resolution, fj*cube_resolution, 0.0 + (fk*cube_resolution));
-                let pos = Isometry3::new(location, na::zero());
-                let handle = world.add_rigid_body(pos, inertia, center_of_mass);
-                cubes.push(
-                    Cubody::new(
-                        Cube::new(
-                            CubeType::Block,
-                            pos,
-                            [hpi, hpj, hpk],
-                            cube_resolution*0.5
-                        ),
-                        handle
-                    )
-                );
-                world.add_collider(
-                    COLLIDER_MARGIN,
-                    geom.clone(),
-                    handle,
-                    Isometry3::identity(),
-                    Material::default()
-                );
-//                world_force.add_body_part(handle);
-//                implode.add_body_part(handle);
-                planet.add_body_part(handle);
-//                planet2.add_body_part(handle);
-            }
-        }
-    }
+    let mut scene = PhysicsScene::new(&scene_options);
+    let flashes: Rc<RefCell<HashMap<BodyHandle, f32>>> = Rc::new(RefCell::new(HashMap::new()));
+    subscribe_flashes(&mut scene, flashes.clone());
 
-//     world.add_force_generator(world_force);
-//    world.add_force_generator(implode);
-    world.add_force_generator(planet);
-//    world.add_force_generator(planet2);
+    let light_position: (f32, f32, f32) = light.position;
 //    cubes.push(Cube::new(
 //        CubeType::Light,
 //        Vector3::new(light_position.0, light_position.1, light_position.2),
@@ -114,8 +146,6 @@ fn main() {
 //        &display
 //    ));
 
-    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-
     let block_vertex_shader_src   = include_str!("../assets/block.vert");
     let block_fragment_shader_src = include_str!("../assets/block.frag");
     let block_program: Program = glium::Program::from_source(&display, block_vertex_shader_src, block_fragment_shader_src, None).unwrap();
@@ -124,13 +154,26 @@ fn main() {
     let light_fragment_shader_src = include_str!("../assets/light.frag");
     let light_program: Program = glium::Program::from_source(&display, light_vertex_shader_src, light_fragment_shader_src, None).unwrap();
 
-    let projection = geometry::Perspective3::new(dimensions[0]/dimensions[1], f32::consts::PI/2.0, 0.1, 1000.0);
+    let line_vertex_shader_src   = include_str!("../assets/line.vert");
+    let line_fragment_shader_src = include_str!("../assets/line.frag");
+    let line_program: Program = glium::Program::from_source(&display, line_vertex_shader_src, line_fragment_shader_src, None).unwrap();
+
+    let particle_vertex_shader_src   = include_str!("../assets/particle.vert");
+    let particle_fragment_shader_src = include_str!("../assets/particle.frag");
+    let particle_program: Program = glium::Program::from_source(&display, particle_vertex_shader_src, particle_fragment_shader_src, None).unwrap();
+
+    let overlay_vertex_shader_src   = include_str!("../assets/overlay.vert");
+    let overlay_fragment_shader_src = include_str!("../assets/overlay.frag");
+    let overlay_program: Program = glium::Program::from_source(&display, overlay_vertex_shader_src, overlay_fragment_shader_src, None).unwrap();
+
+    let projection = geometry::Perspective3::new(render.get_aspect(), radianize(&render.fov), render.near, render.far);
 
-    let expl = 0.1;
+    let mut blast: Option<BlastKind> = None;
+    let mut cycle_falloff: bool = false;
     let mut closed = false;
     let mut d: f32 = 0.001;
 
-    let light_color:  [f32; 3] = [1.0, 1.0, 1.0];
+    let light_color:  [f32; 3] = light.color;
 
     let params = glium::DrawParameters {
         depth: glium::Depth {
@@ -142,26 +185,66 @@ fn main() {
     };
     let mut rotate_cubes: bool = false;
     let mut mouse_offset: Vector2<f32> = Vector2::new(0.0, 0.0);
-    let mouse_sensitivity: f32 = 0.5;
-    let mut pitch: f32 = 0.0;
-    let mut yaw: f32 = -88.0;
+    let mut mouse_sensitivity: f32 = 0.5;
+    let mut pitch: f32 = camera.pitch;
+    let mut yaw: f32 = camera.yaw;
 
-    let camera_speed: f32 = 1.0;
-    let mut camera_pos = glm::vec3(0.0, 0.0, 20.0);
+    let mut camera_speed: f32 = camera.speed;
+    let mut camera_pos = glm::vec3(camera.position[0], camera.position[1], camera.position[2]);
     let camera_up  = glm::vec3(0.0, 1.0, 0.0);
 
     let mut input_holder: Input = Input::new();
+    let mut debug_draw: DebugDraw = DebugDraw::new();
+    let mut hud: Hud = Hud::new();
+    let mut overlay: Overlay = Overlay::new(&display);
+    let mut ui: Ui = Ui::new();
+    let mut grabber: Grabber = Grabber::new();
+    let mut cursor: (f32, f32) = (0.0, 0.0);
+    let mut grab_pressed: bool = false;
+    let mut grab_released: bool = false;
+    let mut toggled_field: Option<FieldKind> = None;
+    let mut toggle_nbody: bool = false;
+    let mut toggle_boids: bool = false;
+    let mut physics_ms: f32 = 0.0;
+    let mut timeline: Timeline = Timeline::new(SNAPSHOT_FRAMES);
+    let mut step_count: u64 = 0;
+    let mut step_once: bool = false;
+    let mut rewind_frames: usize = 0;
+    let mut save_snapshot: bool = false;
+    let mut load_snapshot: bool = false;
+    let mut next_preset: Option<ScenePreset> = None;
+    let mut particles: ParticleSystem = ParticleSystem::new(&display);
+    let sparks = EmitterConfig::sparks();
+    let dust = EmitterConfig::dust();
+    let mut toggle_trail: bool = false;
+    let mut cloth: Option<(Cloth, VertexBuffer<Vertex>, IndexBuffer<u32>)> = None;
+    let mut cloth_settings: ClothSettings = ClothSettings::default();
+    let mut cloth_pin: ClothPin = ClothPin::Free;
+    let mut toggle_cloth: bool = false;
+    let mut audio: Option<AudioPlayer> = audio_from_args();
+    let mut toggle_audio: bool = false;
+    let mut audio_seek: f32 = 0.0;
+    // Audio launched with --offline stays offline; otherwise it only is while capturing.
+    let audio_offline: bool = audio.as_ref().map_or(false, |audio| audio.is_offline());
+    let (capture_settings, capture_on_start) = capture_from_args();
+    let mut capture: Option<Capture> = None;
+    let mut toggle_capture: bool = capture_on_start;
+    let poster_scale: u32 = poster_scale_from_args();
+    // Tiles along each side and file prefix of a still to save with the next frame.
+    let mut still: Option<(u32, &str)> = None;
+    timeline.record(Snapshot::capture(step_count, &scene.world, &scene.cubes, scene.fields_handle));
 //    let cube_count = cubes.len();
 //    let arc_cubes = Arc::new(Mutex::new(cubes));
 //    let arc_program = Arc::new(Mutex::new(block_program));
 //    let arc_params = Arc::new(Mutex::new(params));
-    let cube_verts = glium::VertexBuffer::new(&display, &cube::get_cube_verts(1.0)).unwrap();
+    let cube_vert_data = cube::get_cube_verts(1.0);
+    let cube_verts = glium::VertexBuffer::new(&display, &cube_vert_data).unwrap();
 
 
     while !closed {
 
         let mut target = display.draw();
-        target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+        target.clear_color_and_depth(render.get_clear_color(), 1.0);
 
         yaw   += mouse_offset.x;
         pitch += mouse_offset.y;
@@ -187,22 +270,244 @@ fn main() {
             &camera_up
         );
 
-        for cubody in cubes.iter_mut() {
-            let cubody_pos = world.rigid_body(cubody.handle).unwrap().position();
-            let mut rigid_body: &mut RigidBody<f32> = world.rigid_body_mut(cubody.handle).unwrap();
-            cubody.cube.move_location(cubody_pos);
+        // Picking follows the cursor while the panel is open, otherwise the centre of the screen.
+        let pick_cursor = if ui.visible { cursor } else { (dimensions[0] / 2.0, dimensions[1] / 2.0) };
+        let pick_ray = screen_ray(pick_cursor, dimensions, &view, projection.as_matrix());
+        let hovered = match pick_ray {
+            Some(ref ray) => pick_body(&scene.world, ray).map(|hit| hit.0),
+            None => None
+        };
+
+        let cube_params = glium::DrawParameters {
+            polygon_mode: debug_draw.get_polygon_mode(),
+            .. params.clone()
+        };
+        debug_draw.clear();
+        let mut solids: Vec<(Matrix4<f32>, [f32; 3])> = Vec::with_capacity(scene.cubes.len() + scene.statics.len());
+        let mut vertex_count: usize = 0;
+
+        {
+            let mut flashes = flashes.borrow_mut();
+            for flash in flashes.values_mut() {
+                *flash *= FLASH_DECAY;
+            }
+            flashes.retain(|_, flash| *flash > 0.01);
+        }
+        for (i, cubody) in scene.cubes.iter_mut().enumerate() {
+            let flash = flashes.borrow().get(&cubody.handle).cloned().unwrap_or(0.0);
+            let pulse = match audio {
+                Some(ref audio) => audio.get_bands()[i % BAND_COUNT] * 0.6,
+                None => 0.0
+            };
+            let cubody_pos = scene.world.rigid_body(cubody.handle).unwrap().position();
+            let mut rigid_body: &mut RigidBody<f32> = scene.world.rigid_body_mut(cubody.handle).unwrap();
+            cubody.cube.move_location(cubody_pos * cubody.offset);
             
-            let uniforms = uniform!{
-                window_size: dimensions,
-                lightColor:  light_color,
-                lightPos:    light_position,
-                model:       na4_to_gl4(&cubody.cube.get_model_transform()),
-                view:        na4_to_gl4(&view),
-                projection:  na4_to_gl4(&projection.as_matrix()),
-                objectColor: cubody.cube.get_color(),
+            let color = if hovered == Some(cubody.handle) || grabber.get_grabbed() == Some(cubody.handle) {
+                HIGHLIGHT_COLOR
+            } else {
+                mix_color(mix_color(cubody.cube.get_color(), AUDIO_COLOR, pulse), FLASH_COLOR, flash)
+            };
+            solids.push((cubody.cube.get_model_transform(), color));
+            if debug_draw.normals {
+                debug_draw.add_normals(&cubody.cube.get_model_transform(), &cube_vert_data, 0.5);
+            }
+        }
+        for cube in &scene.statics {
+            solids.push((cube.get_model_transform(), cube.get_color()));
+        }
+        let mut draw_calls: usize = solids.len();
+        vertex_count += solids.len() * cube_vert_data.len();
+
+        if let Some((ref cloth, ref cloth_verts, _)) = cloth {
+            let vertices = cloth.get_vertices();
+            cloth_verts.write(&vertices);
+            draw_calls += 1;
+            vertex_count += vertices.len();
+        }
+
+        debug_draw.add_world(&scene.world);
+        if let Ok(fields) = scene.world.force_generator(scene.fields_handle).downcast_ref::<ForceFieldSet>() {
+            for field in fields.get_fields() {
+                if let Some(center) = field.get_center() {
+                    debug_draw.add_force_center(&center);
+                }
+            }
+        }
+        if debug_draw.forces {
+            if let Ok(springs) = scene.world.force_generator(scene.springs_handle).downcast_ref::<Springs>() {
+                for spring in springs.get_springs() {
+                    let (a, b) = spring.get_endpoints(&scene.world);
+                    debug_draw.add_line(a, b, SPRING_COLOR);
+                }
+            }
+        }
+        if let Ok(impulses) = scene.world.force_generator(scene.impulses_handle).downcast_ref::<Impulses>() {
+            for blast in impulses.get_blasts() {
+                debug_draw.add_sphere(&blast.get_center(), blast.get_front(), BLAST_COLOR);
+            }
+        }
+        if !debug_draw.get_lines().is_empty() {
+            draw_calls += 1;
+            vertex_count += debug_draw.get_lines().len();
+        }
+
+        // While capturing, the world is drawn offscreen at the capture's size and the window only
+        // shows a scaled copy, so recordings never depend on the window.
+        let mut stop_capture: bool = false;
+        {
+            let world_frame = WorldFrame {
+                solids,
+                cube_verts: &cube_verts,
+                cloth: cloth.as_ref().map(|&(_, ref verts, ref indices)| (verts, indices)),
+                particles: &particles,
+                debug_draw: &debug_draw,
+                block_program: &block_program,
+                particle_program: &particle_program,
+                line_program: &line_program,
+                light_position,
+                light_color,
+                params: cube_params
             };
-            target.draw(&cube_verts, &indices, &block_program, &uniforms, &params).unwrap();
+            match capture {
+                Some(ref mut capture) => {
+                    {
+                        let capture_projection = geometry::Perspective3::new(capture.get_aspect(), projection.fovy(), projection.znear(), projection.zfar());
+                        let mut framebuffer = capture.get_framebuffer(&display);
+                        framebuffer.clear_color_and_depth(render.get_clear_color(), 1.0);
+                        draw_world(&mut framebuffer, &display, &world_frame, &view, capture_projection.as_matrix(), capture.get_dimensions());
+                    }
+                    let preview = fit_rect(capture.get_settings().width, capture.get_settings().height, dimensions);
+                    capture.get_color().as_surface().blit_whole_color_to(&target, &preview, glium::uniforms::MagnifySamplerFilter::Linear);
+                    if let Err(err) = capture.write_frame() {
+                        eprintln!("capture failed: {}", err);
+                        stop_capture = true;
+                    }
+                    stop_capture = stop_capture || capture.is_finished();
+                },
+                None => draw_world(&mut target, &display, &world_frame, &view, projection.as_matrix(), dimensions)
+            }
+            // Stills are drawn again without the HUD; posters split the frustum into tiles so they can
+            // be larger than any single target the GPU allows.
+            if let Some((tiles, prefix)) = still.take() {
+                let saved = save_still(&display, dimensions[0] as u32, dimensions[1] as u32, tiles, projection.as_matrix(), prefix, |target, tile_projection| {
+                    target.clear_color_and_depth(render.get_clear_color(), 1.0);
+                    draw_world(target, &display, &world_frame, &view, tile_projection, dimensions);
+                });
+                match saved {
+                    Ok(path) => eprintln!("saved {}", path.display()),
+                    Err(err) => eprintln!("could not save {}: {}", prefix, err)
+                }
+            }
         }
+
+        hud.tick();
+        overlay.clear();
+        if hud.visible {
+            hud.add_line(format!("FPS {:.1} ({:.2} MS)", hud.get_fps(), hud.get_frame_time()));
+            hud.add_line(format!("SCENE {}", scene_options.preset.get_name()));
+            hud.add_line(format!("CUBES {}  DRAW CALLS {}  VERTICES {}", scene.cubes.len(), draw_calls, vertex_count));
+            hud.add_line(format!("PHYSICS STEP {:.2} MS", physics_ms));
+            let time_state = if timeline.paused { "PAUSED" } else if timeline.is_rewound() { "REPLAY" } else { "LIVE" };
+            hud.add_line(format!("STEP {}  FRAME {}/{}  {}", step_count, timeline.get_cursor() + 1, timeline.len(), time_state));
+            hud.add_line(format!("CAMERA {:.1} {:.1} {:.1}", camera_pos.x, camera_pos.y, camera_pos.z));
+            hud.add_line(format!("YAW {:.1} PITCH {:.1}", yaw, pitch));
+            hud.add_line(format!("MODE PHYSICS {}", debug_draw.get_enabled_names().join(" ")));
+            if let Ok(fields) = scene.world.force_generator(scene.fields_handle).downcast_ref::<ForceFieldSet>() {
+                let names: Vec<&str> = fields.get_fields().iter().map(|field| field.get_kind().get_name()).collect();
+                hud.add_line(format!("FIELDS {}", names.join(" ")));
+            }
+            if let Some(handle) = scene.nbody_handle {
+                if let Ok(gravity) = scene.world.force_generator(handle).downcast_ref::<NBodyGravity>() {
+                    hud.add_line(format!("NBODY G {:.2} THETA {:.2}", gravity.get_gravitational_constant(), gravity.get_theta()));
+                }
+            }
+            if scene.boids_handle.is_some() {
+                hud.add_line(String::from("BOIDS ON"));
+            }
+            hud.add_line(format!("COLLISIONS {}  TOTAL {}", scene.contacts.get_started_count(), scene.contacts.get_total_started()));
+            hud.add_line(format!("PARTICLES {}", particles.get_particle_count()));
+            if !scene.fracture.get_bodies().is_empty() {
+                hud.add_line(format!("VOXEL BODIES {}", scene.fracture.get_bodies().len()));
+            }
+            if let Some(ref capture) = capture {
+                let settings = capture.get_settings();
+                hud.add_line(format!("CAPTURE {} {}X{}  FRAME {}", settings.format.get_name(), settings.width, settings.height, capture.get_frame_count()));
+            }
+            if let Some(ref audio) = audio {
+                hud.add_line(format!("AUDIO {:.1}/{:.1}S{}  BASS {:.2} MID {:.2} TREBLE {:.2}",
+                    audio.get_position(), audio.get_track().get_duration(), if audio.is_playing() { "" } else { " PAUSED" },
+                    audio.get_bass(), audio.get_mid(), audio.get_treble()));
+            }
+            match cloth {
+                Some((ref cloth, _, _)) => hud.add_line(format!("CLOTH {} PARTICLES  NEXT PIN {}", cloth.get_particle_count(), cloth_pin.get_name())),
+                None => hud.add_line(format!("CLOTH OFF  NEXT PIN {}", cloth_pin.get_name()))
+            }
+            if let Ok(impulses) = scene.world.force_generator(scene.impulses_handle).downcast_ref::<Impulses>() {
+                let settings = impulses.get_settings();
+                hud.add_line(format!("BLAST RADIUS {:.1} IMPULSE {:.1} {}", settings.radius, settings.strength, settings.falloff.get_name()));
+            }
+            hud.build(&mut overlay, dimensions);
+        }
+        if !ui.visible {
+            overlay.add_text(dimensions[0] / 2.0 - 6.0, dimensions[1] / 2.0 - 8.0, 2.0, "+", [1.0, 1.0, 1.0, 0.8]);
+        }
+        if ui.visible {
+            ui.begin(&mut overlay, "SCENE", dimensions);
+            if let Ok(fields) = scene.world.force_generator_mut(scene.fields_handle).downcast_mut::<ForceFieldSet>() {
+                for field in fields.get_fields_mut().iter_mut() {
+                    let kind = field.get_kind();
+                    let (min, max) = kind.get_strength_range();
+                    let mut strength = field.get_strength();
+                    if ui.slider(&mut overlay, kind.get_name(), &mut strength, min, max) {
+                        field.set_strength(strength);
+                    }
+                }
+            }
+            if let Some(handle) = scene.nbody_handle {
+                if let Ok(gravity) = scene.world.force_generator_mut(handle).downcast_mut::<NBodyGravity>() {
+                    let mut constant = gravity.get_gravitational_constant();
+                    if ui.slider(&mut overlay, "NBODY G", &mut constant, 0.0, 10.0) {
+                        gravity.set_gravitational_constant(constant);
+                    }
+                    let mut theta = gravity.get_theta();
+                    if ui.slider(&mut overlay, "NBODY THETA", &mut theta, 0.0, 1.5) {
+                        gravity.set_theta(theta);
+                    }
+                }
+            }
+            if let Some(handle) = scene.boids_handle {
+                if let Ok(boids) = scene.world.force_generator_mut(handle).downcast_mut::<Boids>() {
+                    let config = boids.get_config_mut();
+                    ui.slider(&mut overlay, "NEIGHBOURS", &mut config.neighbour_radius, 0.5, 10.0);
+                    ui.slider(&mut overlay, "SEPARATION", &mut config.separation_weight, 0.0, 30.0);
+                    ui.slider(&mut overlay, "ALIGNMENT", &mut config.alignment_weight, 0.0, 5.0);
+                    ui.slider(&mut overlay, "COHESION", &mut config.cohesion_weight, 0.0, 5.0);
+                    ui.slider(&mut overlay, "GOAL", &mut config.goal_weight, 0.0, 2.0);
+                    ui.slider(&mut overlay, "AVOIDANCE", &mut config.avoidance_weight, 0.0, 60.0);
+                    ui.slider(&mut overlay, "MAX SPEED", &mut config.max_speed, 1.0, 50.0);
+                }
+            }
+            if let Ok(impulses) = scene.world.force_generator_mut(scene.impulses_handle).downcast_mut::<Impulses>() {
+                let settings = impulses.get_settings_mut();
+                ui.slider(&mut overlay, "BLAST RADIUS", &mut settings.radius, 1.0, 30.0);
+                ui.slider(&mut overlay, "BLAST FORCE", &mut settings.strength, 0.0, 200.0);
+                ui.slider(&mut overlay, "SHOCK SPEED", &mut settings.speed, 1.0, 60.0);
+            }
+            if cloth.is_some() {
+                ui.slider(&mut overlay, "CLOTH STIFF", &mut cloth_settings.stiffness, 0.0, 1.0);
+                ui.slider(&mut overlay, "CLOTH BEND", &mut cloth_settings.bend_stiffness, 0.0, 1.0);
+                ui.slider(&mut overlay, "CLOTH FRICTION", &mut cloth_settings.friction, 0.0, 1.0);
+                ui.checkbox(&mut overlay, "CLOTH SELF HIT", &mut cloth_settings.self_collision);
+            }
+            ui.slider(&mut overlay, "MOUSE SENS", &mut mouse_sensitivity, 0.05, 2.0);
+            ui.slider(&mut overlay, "CAMERA SPEED", &mut camera_speed, 0.05, 5.0);
+            ui.checkbox(&mut overlay, "WIREFRAME", &mut debug_draw.wireframe);
+            ui.checkbox(&mut overlay, "CONTACTS", &mut debug_draw.contacts);
+            ui.checkbox(&mut overlay, "VELOCITIES", &mut debug_draw.velocities);
+            ui.end();
+        }
+        overlay.draw(&display, &mut target, &overlay_program, dimensions);
         target.finish().unwrap();
 
         mouse_offset.x = 0.0;
@@ -212,6 +517,7 @@ fn main() {
             match event {
                 glutin::Event::DeviceEvent { event, ..} => match event {
                     glutin::DeviceEvent::MouseMotion{ delta }  => match delta {
+                        _ if ui.visible => (),
                         _ => {
                             mouse_offset.x = ( delta.0 as f32) * mouse_sensitivity;
                             mouse_offset.y = (-delta.1 as f32) * mouse_sensitivity;
@@ -225,6 +531,20 @@ fn main() {
                         dimensions[0] = size.width  as f32;
                         dimensions[1] = size.height as f32;
                     },
+                    glutin::WindowEvent::CursorMoved{ position, .. } => {
+                        cursor = (position.x as f32, position.y as f32);
+                        ui.handle_cursor(cursor.0, cursor.1);
+                    },
+                    glutin::WindowEvent::MouseInput{ state, button: glutin::MouseButton::Left, .. } => {
+                        let pressed = state == glutin::ElementState::Pressed;
+                        if pressed && !ui.is_hovered(dimensions) {
+                            grab_pressed = true;
+                        }
+                        if !pressed {
+                            grab_released = true;
+                        }
+                        ui.handle_button(pressed);
+                    },
                     glutin::WindowEvent::KeyboardInput{ input, .. } => {
                         let latch = match input.state {
                             glutin::ElementState::Pressed  => true,
@@ -252,6 +572,52 @@ fn main() {
                         if input.virtual_keycode == Some(glutin::VirtualKeyCode::Escape) {
                             closed = true;
                         }
+                        if latch {
+                            match input.virtual_keycode {
+                                Some(glutin::VirtualKeyCode::F1) => debug_draw.colliders  = !debug_draw.colliders,
+                                Some(glutin::VirtualKeyCode::F2) => debug_draw.aabbs      = !debug_draw.aabbs,
+                                Some(glutin::VirtualKeyCode::F3) => debug_draw.contacts   = !debug_draw.contacts,
+                                Some(glutin::VirtualKeyCode::F4) => debug_draw.velocities = !debug_draw.velocities,
+                                Some(glutin::VirtualKeyCode::F5) => debug_draw.forces     = !debug_draw.forces,
+                                Some(glutin::VirtualKeyCode::F6) => debug_draw.normals    = !debug_draw.normals,
+                                Some(glutin::VirtualKeyCode::F7) => debug_draw.wireframe  = !debug_draw.wireframe,
+                                Some(glutin::VirtualKeyCode::H)  => hud.visible = !hud.visible,
+                                Some(glutin::VirtualKeyCode::Key1) => toggled_field = Some(FieldKind::Attractor),
+                                Some(glutin::VirtualKeyCode::Key2) => toggled_field = Some(FieldKind::Repulsor),
+                                Some(glutin::VirtualKeyCode::Key3) => toggled_field = Some(FieldKind::Vortex),
+                                Some(glutin::VirtualKeyCode::Key4) => toggled_field = Some(FieldKind::Wind),
+                                Some(glutin::VirtualKeyCode::Key5) => toggled_field = Some(FieldKind::Drag),
+                                Some(glutin::VirtualKeyCode::Key6) => toggled_field = Some(FieldKind::Turbulence),
+                                Some(glutin::VirtualKeyCode::Key7) => toggled_field = Some(FieldKind::Pulse),
+                                Some(glutin::VirtualKeyCode::G) => toggle_nbody = true,
+                                Some(glutin::VirtualKeyCode::B) => toggle_boids = true,
+                                Some(glutin::VirtualKeyCode::E) => blast = Some(BlastKind::Explosion),
+                                Some(glutin::VirtualKeyCode::Q) => blast = Some(BlastKind::Shockwave),
+                                Some(glutin::VirtualKeyCode::X) => blast = Some(BlastKind::Implosion),
+                                Some(glutin::VirtualKeyCode::F) => cycle_falloff = true,
+                                Some(glutin::VirtualKeyCode::T) => toggle_trail = true,
+                                Some(glutin::VirtualKeyCode::C) => toggle_cloth = true,
+                                Some(glutin::VirtualKeyCode::V) => cloth_pin = cloth_pin.next(),
+                                Some(glutin::VirtualKeyCode::U) => toggle_audio = true,
+                                Some(glutin::VirtualKeyCode::LBracket) => audio_seek -= SEEK_SECONDS,
+                                Some(glutin::VirtualKeyCode::RBracket) => audio_seek += SEEK_SECONDS,
+                                Some(glutin::VirtualKeyCode::N) => next_preset = Some(scene_options.preset.next()),
+                                Some(glutin::VirtualKeyCode::P) => timeline.paused = !timeline.paused,
+                                Some(glutin::VirtualKeyCode::Right) => step_once = true,
+                                Some(glutin::VirtualKeyCode::Left)  => rewind_frames += 1,
+                                Some(glutin::VirtualKeyCode::Down)  => rewind_frames += 60,
+                                Some(glutin::VirtualKeyCode::F8)  => toggle_capture = true,
+                                Some(glutin::VirtualKeyCode::F9)  => save_snapshot = true,
+                                Some(glutin::VirtualKeyCode::F10) => load_snapshot = true,
+                                Some(glutin::VirtualKeyCode::F11) => still = Some((poster_scale, POSTER_PREFIX)),
+                                Some(glutin::VirtualKeyCode::F12) => still = Some((1, SCREENSHOT_PREFIX)),
+                                Some(glutin::VirtualKeyCode::Tab) => {
+                                    ui.visible = !ui.visible;
+                                    display.gl_window().hide_cursor(!ui.visible);
+                                },
+                                _ => ()
+                            }
+                        }
 //                        println!("{:?}", input)
                     }
                     _ => ()
@@ -274,19 +640,348 @@ fn main() {
         if input_holder.up {
             camera_pos += camera_speed * camera_up;
         }
-        world.step();
+        if let Some(ref ray) = pick_ray {
+            if grab_pressed {
+                // Interacting with a rewound world branches it, so the recorded future no longer applies.
+                timeline.truncate();
+                grabber.grab(&mut scene.world, ray);
+            }
+            grabber.drag(&mut scene.world, ray);
+        }
+        if grab_released {
+            grabber.release(&mut scene.world);
+        }
+        grab_pressed = false;
+        grab_released = false;
+        if let Some(kind) = toggled_field.take() {
+            if let Ok(fields) = scene.world.force_generator_mut(scene.fields_handle).downcast_mut::<ForceFieldSet>() {
+                fields.toggle(kind);
+            }
+        }
+        // Blasts go off where the crosshair meets a cube, or a fixed distance ahead of the camera.
+        let blast_center = match pick_ray {
+            Some(ref ray) => Some(match pick_body(&scene.world, ray) {
+                Some((_, hit, _)) => hit,
+                None => ray.origin + ray.dir * BLAST_DISTANCE
+            }),
+            None => None
+        };
+        if let Ok(impulses) = scene.world.force_generator_mut(scene.impulses_handle).downcast_mut::<Impulses>() {
+            if cycle_falloff {
+                let settings = impulses.get_settings_mut();
+                settings.falloff = settings.falloff.next();
+            }
+            if let (Some(kind), Some(center)) = (blast.take(), blast_center) {
+                impulses.trigger(kind, center);
+            }
+        }
+        blast = None;
+        cycle_falloff = false;
+        // C removes the cloth, or lays a sheet flat and drops it over whatever the crosshair is on.
+        if toggle_cloth {
+            if cloth.take().is_none() {
+                if let Some(center) = blast_center {
+                    let frame = Isometry3::new(center.coords + Vector3::y() * CLOTH_DROP, Vector3::x() * -f32::consts::FRAC_PI_2);
+                    let sheet = Sheet::new(frame, CLOTH_COLOR, CLOTH_SIZE);
+                    let new_cloth = Cloth::from_sheet(&sheet, CLOTH_SEGMENTS, cloth_pin);
+                    let cloth_verts = VertexBuffer::dynamic(&display, &new_cloth.get_vertices()).unwrap();
+                    let cloth_indices = new_cloth.get_mesh().index_buffer(&display);
+                    cloth = Some((new_cloth, cloth_verts, cloth_indices));
+                }
+            }
+            toggle_cloth = false;
+        }
+        if toggle_nbody {
+            match scene.nbody_handle.take() {
+                Some(handle) => {
+                    scene.world.remove_force_generator(handle);
+                },
+                None => {
+                    let gravity = NBodyGravity::new(body_handles(&scene.cubes), 1.0, 0.5);
+                    scene.nbody_handle = Some(scene.world.add_force_generator(gravity));
+                }
+            }
+            toggle_nbody = false;
+        }
+        if toggle_boids {
+            match scene.boids_handle.take() {
+                Some(handle) => {
+                    scene.world.remove_force_generator(handle);
+                },
+                None => {
+                    let boids = scene.create_boids(scene_options.boids_config);
+                    scene.boids_handle = Some(scene.world.add_force_generator(boids));
+                }
+            }
+            toggle_boids = false;
+        }
+        if let Some(handle) = scene.boids_handle {
+            // The flock chases the orbiting attractor when there is one.
+            let goal = match scene.world.force_generator(scene.fields_handle).downcast_ref::<ForceFieldSet>() {
+                Ok(fields) => fields.get_fields().iter()
+                    .filter(|field| field.get_kind() == FieldKind::Attractor)
+                    .filter_map(|field| field.get_center())
+                    .next(),
+                Err(_) => None
+            };
+            if let Ok(boids) = scene.world.force_generator_mut(handle).downcast_mut::<Boids>() {
+                boids.set_goal(goal);
+            }
+        }
+
+        if let Some(preset) = next_preset.take() {
+            grabber = Grabber::new();
+            scene_options = SceneOptions::for_preset(preset);
+            scene = PhysicsScene::new(&scene_options);
+            subscribe_flashes(&mut scene, flashes.clone());
+            flashes.borrow_mut().clear();
+            particles = ParticleSystem::new(&display);
+            cloth = None;
+            step_count = 0;
+            timeline.clear();
+            timeline.record(Snapshot::capture(step_count, &scene.world, &scene.cubes, scene.fields_handle));
+        }
+        if rewind_frames > 0 {
+            timeline.paused = true;
+            if let Some(snapshot) = timeline.rewind(rewind_frames) {
+                snapshot.restore(&mut scene.world, scene.fields_handle);
+                step_count = snapshot.step;
+            }
+            rewind_frames = 0;
+        }
+        if save_snapshot {
+            if let Some(snapshot) = timeline.current() {
+                match snapshot.save(SNAPSHOT_PATH) {
+                    Ok(()) => eprintln!("saved step {} to {}", snapshot.step, SNAPSHOT_PATH),
+                    Err(err) => eprintln!("could not save {}: {}", SNAPSHOT_PATH, err)
+                }
+            }
+            save_snapshot = false;
+        }
+        if load_snapshot {
+            match Snapshot::load(SNAPSHOT_PATH, &scene.cubes) {
+                Ok(snapshot) => {
+                    grabber.release(&mut scene.world);
+                    snapshot.restore(&mut scene.world, scene.fields_handle);
+                    step_count = snapshot.step;
+                    timeline.clear();
+                    timeline.record(snapshot);
+                    timeline.paused = true;
+                },
+                Err(err) => eprintln!("could not load {}: {}", SNAPSHOT_PATH, err)
+            }
+            load_snapshot = false;
+        }
+
+        // Paused worlds only move on request; recorded frames are replayed before simulating new ones.
+        if !timeline.paused || step_once {
+            let step_start = Instant::now();
+            let replayed = match timeline.forward() {
+                Some(snapshot) => {
+                    snapshot.restore(&mut scene.world, scene.fields_handle);
+                    step_count = snapshot.step;
+                    true
+                },
+                None => false
+            };
+            if !replayed {
+                // The bass boosts every attractor for this step only, so its slider and the scene's strength stay as set.
+                let base_strengths = audio.as_ref().map(|audio| scale_attractors(&mut scene, 1.0 + AUDIO_BOOST * audio.get_bass()));
+                let fractured = scene.step();
+                if let Some(strengths) = base_strengths {
+                    set_attractor_strengths(&mut scene, &strengths);
+                }
+                // Snapshots cannot bring back a body that broke apart, so history starts over at a fracture.
+                if fractured {
+                    timeline.clear();
+                }
+                if let Some((ref mut cloth, _, _)) = cloth {
+                    cloth.settings = cloth_settings;
+                    let fields = scene.world.force_generator(scene.fields_handle).downcast_ref::<ForceFieldSet>().ok();
+                    cloth.step(scene_options.timestep, &scene.world, fields);
+                }
+                // A body about to shatter must not be held by the mouse constraint when it is removed.
+                if let Some(handle) = grabber.get_grabbed() {
+                    if scene.fracture.is_breaking(handle) {
+                        grabber.release(&mut scene.world);
+                    }
+                }
+                // Hard hits throw sparks; anything landing on static geometry kicks up dust.
+                for event in scene.contacts.get_events() {
+                    if let Some(point) = event.point {
+                        if event.impulse > SPARK_IMPULSE {
+                            particles.burst(&sparks, point, usize::min((event.impulse * 2.0) as usize, 40));
+                        }
+                        if event.impulse > DUST_IMPULSE && (event.body1.is_ground() || event.body2.is_ground()) {
+                            particles.burst(&dust, point, 6);
+                        }
+                    }
+                }
+                step_count += 1;
+                timeline.record(Snapshot::capture(step_count, &scene.world, &scene.cubes, scene.fields_handle));
+            }
+            physics_ms = duration_ms(step_start.elapsed());
+        }
+        step_once = false;
+        if toggle_trail {
+            if let Some(body) = hovered {
+                match particles.find_attached(body) {
+                    Some(id) => particles.remove_emitter(id),
+                    None => {
+                        particles.attach(EmitterConfig::trail(), body);
+                    }
+                }
+            }
+            toggle_trail = false;
+        }
+        if let Some(ref mut audio) = audio {
+            audio.apply_input(toggle_audio, audio_seek, scene_options.timestep);
+        }
+        toggle_audio = false;
+        audio_seek = 0.0;
+        // Capturing steps the audio by the timestep like everything else, however slowly frames are written.
+        if toggle_capture || stop_capture {
+            match capture.take() {
+                Some(finished) => {
+                    let completed = finished.is_finished();
+                    match finished.finish() {
+                        Ok(frames) => eprintln!("captured {} frames", frames),
+                        Err(err) => eprintln!("capture failed: {}", err)
+                    }
+                    if let Some(ref mut audio) = audio {
+                        audio.set_offline(audio_offline);
+                    }
+                    // A capture asked for on the command line quits once done, so it can be scripted.
+                    if completed && capture_on_start {
+                        closed = true;
+                    }
+                },
+                None if toggle_capture => match Capture::start(&display, capture_settings.clone()) {
+                    Ok(started) => {
+                        if let Some(ref mut audio) = audio {
+                            audio.set_offline(true);
+                        }
+                        capture = Some(started);
+                    },
+                    Err(err) => eprintln!("could not start capture: {}", err)
+                },
+                None => ()
+            }
+            toggle_capture = false;
+        }
+        particles.follow_bodies(&scene.world);
+        particles.update(scene_options.timestep);
         d += 0.01;
     }
 
 }
 
+/// Everything in the world that gets drawn, gathered once a frame so the same frame can be drawn
+/// into the window and into offscreen targets.
+struct WorldFrame<'a> {
+    /// Model transform and colour of every dynamic and static cube.
+    solids: Vec<(Matrix4<f32>, [f32; 3])>,
+    cube_verts: &'a VertexBuffer<Vertex>,
+    cloth: Option<(&'a VertexBuffer<Vertex>, &'a IndexBuffer<u32>)>,
+    particles: &'a ParticleSystem,
+    debug_draw: &'a DebugDraw,
+    block_program: &'a Program,
+    particle_program: &'a Program,
+    line_program: &'a Program,
+    light_position: (f32, f32, f32),
+    light_color: [f32; 3],
+    params: glium::DrawParameters<'a>
+}
+
+fn draw_world<S: Surface>(target: &mut S, display: &Display, world: &WorldFrame, view: &Matrix4<f32>, projection: &Matrix4<f32>, window_size: [f32; 2]) {
+    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+    for &(ref model, color) in &world.solids {
+        let uniforms = uniform!{
+            window_size: window_size,
+            lightColor:  world.light_color,
+            lightPos:    world.light_position,
+            model:       na4_to_gl4(model),
+            view:        na4_to_gl4(view),
+            projection:  na4_to_gl4(projection),
+            objectColor: color,
+        };
+        target.draw(world.cube_verts, &indices, world.block_program, &uniforms, &world.params).unwrap();
+    }
+    if let Some((cloth_verts, cloth_indices)) = world.cloth {
+        let uniforms = uniform!{
+            window_size: window_size,
+            lightColor:  world.light_color,
+            lightPos:    world.light_position,
+            model:       na4_to_gl4(&Matrix4::identity()),
+            view:        na4_to_gl4(view),
+            projection:  na4_to_gl4(projection),
+            objectColor: CLOTH_COLOR,
+        };
+        target.draw(cloth_verts, cloth_indices, world.block_program, &uniforms, &world.params).unwrap();
+    }
+    world.particles.draw(display, target, world.particle_program, na4_to_gl4(view), na4_to_gl4(projection));
+    world.debug_draw.draw(display, target, world.line_program, na4_to_gl4(view), na4_to_gl4(projection));
+}
+
+/// Brightens both bodies of every new contact in proportion to how hard they hit.
+fn subscribe_flashes(scene: &mut PhysicsScene, flashes: Rc<RefCell<HashMap<BodyHandle, f32>>>) {
+    scene.contacts.subscribe(move |event| {
+        if event.phase != ContactPhase::Started {
+            return;
+        }
+        let intensity = f32::min(event.impulse / FLASH_IMPULSE, 1.0);
+        let mut flashes = flashes.borrow_mut();
+        for body in &[event.body1, event.body2] {
+            if !body.is_ground() {
+                let flash = flashes.entry(*body).or_insert(0.0);
+                *flash = f32::max(*flash, intensity);
+            }
+        }
+    });
+}
+
+/// Multiplies every attractor's strength by `factor` and returns the strengths it had before.
+fn scale_attractors(scene: &mut PhysicsScene, factor: f32) -> Vec<f32> {
+    let mut strengths: Vec<f32> = Vec::new();
+    if let Ok(fields) = scene.world.force_generator_mut(scene.fields_handle).downcast_mut::<ForceFieldSet>() {
+        for field in fields.get_fields_mut().iter_mut().filter(|field| field.get_kind() == FieldKind::Attractor) {
+            strengths.push(field.get_strength());
+            field.set_strength(strengths[strengths.len() - 1] * factor);
+        }
+    }
+    strengths
+}
+
+/// Gives the attractors, in order, the strengths returned by `scale_attractors`.
+fn set_attractor_strengths(scene: &mut PhysicsScene, strengths: &[f32]) {
+    if let Ok(fields) = scene.world.force_generator_mut(scene.fields_handle).downcast_mut::<ForceFieldSet>() {
+        let attractors = fields.get_fields_mut().iter_mut().filter(|field| field.get_kind() == FieldKind::Attractor);
+        for (field, strength) in attractors.zip(strengths) {
+            field.set_strength(*strength);
+        }
+    }
+}
+
+fn mix_color(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
+
+/// Reads `--preset NAME` from the command line, falling back to the floating swarm.
+fn preset_from_args() -> Option<ScenePreset> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == "--preset")?;
+    let preset = args.get(index + 1).and_then(|name| ScenePreset::from_name(name));
+    if preset.is_none() {
+        let names: Vec<&str> = ScenePreset::all().iter().map(|preset| preset.get_name()).collect();
+        eprintln!("--preset expects one of {}", names.join(", "));
+    }
+    preset
+}
+
 fn radianize(n: &f32) -> f32 {
     n * (f32::consts::PI/180.0)
 }
 
-fn hypot(a: f32, b: f32) -> f32 {
-    f32::sqrt(f32::powi(a, 2) + f32::powi(b, 2))
-}
  
 fn na4_to_gl4(mat: &Matrix4<f32>) -> [[f32; 4]; 4] {
     [
@@ -0,0 +1,190 @@
+use glium::{Display, IndexBuffer, VertexBuffer};
+use glium::index::PrimitiveType;
+use na::{Point3, Vector3};
+use std::f32;
+
+use vertex::Vertex;
+
+/// An indexed triangle list over a grid of `(segments_u + 1) × (segments_v + 1)` vertices.
+///
+/// Vertices are stored row by row along u, and every surface carries UVs running 0 to 1.
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>
+}
+
+impl Mesh {
+    /// Samples `position` and `normal` at every grid point; both get `(u, v)` in 0 to 1.
+    ///
+    /// Triangles wind counter-clockwise when viewed from the side the u × v tangent points to.
+    fn grid<P, N>(segments_u: usize, segments_v: usize, position: P, normal: N) -> Self
+        where P: Fn(f32, f32) -> Point3<f32>, N: Fn(f32, f32) -> Vector3<f32> {
+        let segments_u = usize::max(segments_u, 1);
+        let segments_v = usize::max(segments_v, 1);
+        let row = segments_u + 1;
+        let mut vertices: Vec<Vertex> = Vec::with_capacity(row * (segments_v + 1));
+        for j in 0..segments_v + 1 {
+            let v = j as f32 / segments_v as f32;
+            for i in 0..row {
+                let u = i as f32 / segments_u as f32;
+                let p = position(u, v);
+                let n = normal(u, v);
+                vertices.push(Vertex::textured(p.x, p.y, p.z, (n.x, n.y, n.z), (u, v)));
+            }
+        }
+        let mut indices: Vec<u32> = Vec::with_capacity(segments_u * segments_v * 6);
+        for j in 0..segments_v {
+            for i in 0..segments_u {
+                let a = (j * row + i) as u32;
+                let b = a + 1;
+                let c = a + 1 + row as u32;
+                let d = a + row as u32;
+                indices.extend_from_slice(&[a, b, c, c, d, a]);
+            }
+        }
+        Mesh {
+            vertices,
+            indices
+        }
+    }
+
+    /// Any surface `f(u, v)` with u and v in 0 to 1; normals come from finite differences and
+    /// point along ∂f/∂u × ∂f/∂v.
+    pub fn parametric<F: Fn(f32, f32) -> Point3<f32>>(segments_u: usize, segments_v: usize, f: F) -> Self {
+        let h = 1.0e-3;
+        let normal = |u: f32, v: f32| {
+            let du = f(u + h, v) - f(u - h, v);
+            let dv = f(u, v + h) - f(u, v - h);
+            let n = du.cross(&dv);
+            if n.norm_squared() > 0.0 { n.normalize() } else { Vector3::z() }
+        };
+        Mesh::grid(segments_u, segments_v, |u, v| f(u, v), normal)
+    }
+
+    /// A `width` × `height` rectangle centred on the origin in the xy-plane, facing +z.
+    pub fn rect(width: f32, height: f32, segments_u: usize, segments_v: usize) -> Self {
+        Mesh::grid(
+            segments_u,
+            segments_v,
+            |u, v| Point3::new((u - 0.5) * width, (v - 0.5) * height, 0.0),
+            |_, _| Vector3::z()
+        )
+    }
+
+    /// A disc in the xy-plane facing +z, with u going around and v going out from the centre.
+    pub fn disc(radius: f32, segments: usize, rings: usize) -> Self {
+        Mesh::grid(
+            segments,
+            rings,
+            |u, v| {
+                let angle = u * f32::consts::PI * 2.0;
+                Point3::new(v * radius * f32::cos(angle), -v * radius * f32::sin(angle), 0.0)
+            },
+            |_, _| Vector3::z()
+        )
+    }
+
+    /// A sphere about the origin, with u going around the y axis and v from the bottom pole to the top.
+    pub fn sphere(radius: f32, segments: usize, rings: usize) -> Self {
+        let direction = |u: f32, v: f32| {
+            let longitude = u * f32::consts::PI * 2.0;
+            let latitude = (v - 0.5) * f32::consts::PI;
+            Vector3::new(
+                 f32::cos(latitude) * f32::cos(longitude),
+                 f32::sin(latitude),
+                -f32::cos(latitude) * f32::sin(longitude)
+            )
+        };
+        Mesh::grid(segments, rings, |u, v| Point3::from_coordinates(direction(u, v) * radius), direction)
+    }
+
+    /// A torus about the y axis, with u going around the ring and v around the tube.
+    pub fn torus(major_radius: f32, minor_radius: f32, segments: usize, sides: usize) -> Self {
+        let angles = |u: f32, v: f32| (u * f32::consts::PI * 2.0, v * f32::consts::PI * 2.0);
+        Mesh::grid(
+            segments,
+            sides,
+            |u, v| {
+                let (around, tube) = angles(u, v);
+                let reach = major_radius + minor_radius * f32::cos(tube);
+                Point3::new(reach * f32::cos(around), minor_radius * f32::sin(tube), -reach * f32::sin(around))
+            },
+            |u, v| {
+                let (around, tube) = angles(u, v);
+                Vector3::new(
+                     f32::cos(tube) * f32::cos(around),
+                     f32::sin(tube),
+                    -f32::cos(tube) * f32::sin(around)
+                )
+            }
+        )
+    }
+
+    pub fn vertex_buffer(&self, display: &Display) -> VertexBuffer<Vertex> {
+        VertexBuffer::new(display, &self.vertices).unwrap()
+    }
+
+    pub fn index_buffer(&self, display: &Display) -> IndexBuffer<u32> {
+        IndexBuffer::new(display, PrimitiveType::TrianglesList, &self.indices).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the grid counts, that every index is in range and that UVs span 0 to 1.
+    fn check_grid(mesh: &Mesh, segments_u: usize, segments_v: usize) {
+        assert_eq!(mesh.vertices.len(), (segments_u + 1) * (segments_v + 1));
+        assert_eq!(mesh.indices.len(), segments_u * segments_v * 6);
+        assert!(mesh.indices.iter().all(|&index| (index as usize) < mesh.vertices.len()));
+        let (us, vs): (Vec<f32>, Vec<f32>) = mesh.vertices.iter().map(|vertex| vertex.get_tex_coords()).unzip();
+        assert_eq!(us.iter().cloned().fold(f32::MAX, f32::min), 0.0);
+        assert_eq!(us.iter().cloned().fold(f32::MIN, f32::max), 1.0);
+        assert_eq!(vs.iter().cloned().fold(f32::MAX, f32::min), 0.0);
+        assert_eq!(vs.iter().cloned().fold(f32::MIN, f32::max), 1.0);
+    }
+
+    #[test]
+    fn rect_counts() {
+        check_grid(&Mesh::rect(2.0, 1.0, 4, 3), 4, 3);
+        check_grid(&Mesh::rect(1.0, 1.0, 1, 1), 1, 1);
+    }
+
+    #[test]
+    fn rect_keeps_every_row_for_odd_segments() {
+        let mesh = Mesh::rect(1.0, 1.0, 61, 61);
+        check_grid(&mesh, 61, 61);
+        let mut rows: Vec<f32> = mesh.vertices.iter().map(|vertex| vertex.get_position().1).collect();
+        rows.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        rows.dedup();
+        assert_eq!(rows.len(), 62);
+        assert_eq!(rows[0], -0.5);
+        assert_eq!(rows[61], 0.5);
+    }
+
+    #[test]
+    fn disc_counts() {
+        check_grid(&Mesh::disc(1.0, 16, 4), 16, 4);
+    }
+
+    #[test]
+    fn sphere_counts() {
+        check_grid(&Mesh::sphere(1.0, 24, 12), 24, 12);
+    }
+
+    #[test]
+    fn torus_counts() {
+        check_grid(&Mesh::torus(2.0, 0.5, 32, 9), 32, 9);
+    }
+
+    #[test]
+    fn parametric_counts() {
+        check_grid(&Mesh::parametric(5, 7, |u, v| Point3::new(u, v, u * v)), 5, 7);
+    }
+
+    #[test]
+    fn zero_segments_become_one() {
+        check_grid(&Mesh::rect(1.0, 1.0, 0, 0), 1, 1);
+    }
+}
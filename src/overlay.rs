@@ -0,0 +1,174 @@
+extern crate glium;
+
+use glium::*;
+use glium::texture::{RawImage2d, Texture2d};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use vertex::OverlayVertex;
+
+pub const GLYPH_WIDTH:  u32 = 6;
+pub const GLYPH_HEIGHT: u32 = 8;
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_ROWS:    u32 = 6;
+const FIRST_CHAR:    u32 = 32;
+// The last atlas cell is filled solid so rectangles can share the text batch.
+const SOLID_CHAR:    u32 = 127;
+
+pub struct Overlay {
+    atlas: Texture2d,
+    verts: Vec<OverlayVertex>
+}
+
+impl Overlay {
+    pub fn new(display: &Display) -> Self {
+        let width  = ATLAS_COLUMNS * GLYPH_WIDTH;
+        let height = ATLAS_ROWS * GLYPH_HEIGHT;
+        let mut pixels: Vec<u8> = vec![0; (width * height * 4) as usize];
+        for code in FIRST_CHAR..(SOLID_CHAR + 1) {
+            let cell = code - FIRST_CHAR;
+            let cell_x = (cell % ATLAS_COLUMNS) * GLYPH_WIDTH;
+            let cell_y = (cell / ATLAS_COLUMNS) * GLYPH_HEIGHT;
+            let rows = glyph((code as u8 as char).to_ascii_uppercase());
+            for py in 0..GLYPH_HEIGHT {
+                for px in 0..GLYPH_WIDTH {
+                    let lit = if code == SOLID_CHAR {
+                        true
+                    } else {
+                        py < 7 && px < 5 && rows[py as usize] & (0x10 >> px) != 0
+                    };
+                    if lit {
+                        let offset = (((cell_y + py) * width + cell_x + px) * 4) as usize;
+                        for channel in 0..4 {
+                            pixels[offset + channel] = 255;
+                        }
+                    }
+                }
+            }
+        }
+        let image = RawImage2d::from_raw_rgba_reversed(&pixels, (width, height));
+        Overlay {
+            atlas: Texture2d::new(display, image).unwrap(),
+            verts: Vec::new()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.verts.clear();
+    }
+
+    pub fn text_width(text: &str, scale: f32) -> f32 {
+        text.chars().count() as f32 * GLYPH_WIDTH as f32 * scale
+    }
+
+    pub fn add_text(&mut self, x: f32, y: f32, scale: f32, text: &str, color: [f32; 4]) {
+        let mut cursor = x;
+        for c in text.chars() {
+            let code = c as u32;
+            let code = if code >= FIRST_CHAR && code < SOLID_CHAR { code } else { '?' as u32 };
+            if c != ' ' {
+                self.add_cell(cursor, y, GLYPH_WIDTH as f32 * scale, GLYPH_HEIGHT as f32 * scale, code, color);
+            }
+            cursor += GLYPH_WIDTH as f32 * scale;
+        }
+    }
+
+    pub fn add_rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        self.add_cell(x, y, w, h, SOLID_CHAR, color);
+    }
+
+    fn add_cell(&mut self, x: f32, y: f32, w: f32, h: f32, code: u32, color: [f32; 4]) {
+        let cell = code - FIRST_CHAR;
+        let atlas_w = (ATLAS_COLUMNS * GLYPH_WIDTH) as f32;
+        let atlas_h = (ATLAS_ROWS * GLYPH_HEIGHT) as f32;
+        let u0 = ((cell % ATLAS_COLUMNS) * GLYPH_WIDTH) as f32 / atlas_w;
+        let u1 = u0 + GLYPH_WIDTH as f32 / atlas_w;
+        let v0 = 1.0 - ((cell / ATLAS_COLUMNS) * GLYPH_HEIGHT) as f32 / atlas_h;
+        let v1 = v0 - GLYPH_HEIGHT as f32 / atlas_h;
+
+        self.verts.push(OverlayVertex::new(x,     y,     u0, v0, color));
+        self.verts.push(OverlayVertex::new(x + w, y,     u1, v0, color));
+        self.verts.push(OverlayVertex::new(x + w, y + h, u1, v1, color));
+        self.verts.push(OverlayVertex::new(x + w, y + h, u1, v1, color));
+        self.verts.push(OverlayVertex::new(x,     y + h, u0, v1, color));
+        self.verts.push(OverlayVertex::new(x,     y,     u0, v0, color));
+    }
+
+    pub fn draw<S: Surface>(&self, display: &Display, target: &mut S, program: &Program, window_size: [f32; 2]) {
+        if self.verts.is_empty() {
+            return;
+        }
+        let overlay_verts = VertexBuffer::new(display, &self.verts).unwrap();
+        let indices = index::NoIndices(index::PrimitiveType::TrianglesList);
+        let uniforms = uniform!{
+            window_size: window_size,
+            atlas: self.atlas.sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest),
+        };
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            .. Default::default()
+        };
+        target.draw(&overlay_verts, &indices, program, &uniforms, &params).unwrap();
+    }
+}
+
+// 5x7 glyphs, one byte per row with the leftmost pixel in bit 4.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '[' => [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E],
+        ']' => [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E],
+        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '?' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+        _   => [0x00; 7]
+    }
+}
\ No newline at end of file
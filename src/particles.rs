@@ -0,0 +1,328 @@
+extern crate glium;
+extern crate nalgebra as na;
+
+use glium::*;
+use na::{Point3, Vector3};
+use nphysics3d::object::BodyHandle;
+use nphysics3d::world::World;
+use std::f32;
+
+use rng::Rng;
+use vertex::{ParticleCorner, ParticleInstance};
+
+const MAX_PARTICLES: usize = 20000;
+
+/// How an emitter's particles are born and how they change over their life.
+#[derive(Copy, Clone, Debug)]
+pub struct EmitterConfig {
+    /// Particles per second while the emitter is active.
+    pub rate: f32,
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    pub direction: Vector3<f32>,
+    /// Half-angle of the cone particles leave in, in radians; π sprays in every direction.
+    pub spread: f32,
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    pub start_size: f32,
+    pub end_size: f32,
+    pub acceleration: Vector3<f32>,
+    /// Fraction of velocity lost per second.
+    pub drag: f32
+}
+
+impl EmitterConfig {
+    /// Short-lived bright streaks for impacts.
+    pub fn sparks() -> Self {
+        EmitterConfig {
+            rate: 0.0,
+            lifetime: (0.2, 0.6),
+            speed: (4.0, 12.0),
+            direction: Vector3::y(),
+            spread: f32::consts::PI,
+            start_color: [1.0, 0.8, 0.3, 1.0],
+            end_color: [1.0, 0.2, 0.0, 0.0],
+            start_size: 0.15,
+            end_size: 0.02,
+            acceleration: Vector3::new(0.0, -9.81, 0.0),
+            drag: 1.0
+        }
+    }
+
+    /// Slow, fading puffs left behind a moving body.
+    pub fn trail() -> Self {
+        EmitterConfig {
+            rate: 60.0,
+            lifetime: (0.8, 1.2),
+            speed: (0.0, 0.3),
+            direction: Vector3::y(),
+            spread: f32::consts::PI,
+            start_color: [0.4, 0.7, 1.0, 0.6],
+            end_color: [0.1, 0.2, 1.0, 0.0],
+            start_size: 0.4,
+            end_size: 0.05,
+            acceleration: na::zero(),
+            drag: 0.5
+        }
+    }
+
+    /// Large, dim, slowly rising clouds.
+    pub fn dust() -> Self {
+        EmitterConfig {
+            rate: 0.0,
+            lifetime: (1.5, 3.0),
+            speed: (0.5, 2.0),
+            direction: Vector3::y(),
+            spread: 1.2,
+            start_color: [0.5, 0.45, 0.4, 0.3],
+            end_color: [0.3, 0.3, 0.3, 0.0],
+            start_size: 0.6,
+            end_size: 2.0,
+            acceleration: Vector3::new(0.0, 0.3, 0.0),
+            drag: 1.5
+        }
+    }
+
+    /// A steady upward spray.
+    pub fn fountain() -> Self {
+        EmitterConfig {
+            rate: 200.0,
+            lifetime: (1.0, 2.0),
+            speed: (2.0, 4.0),
+            direction: Vector3::z(),
+            spread: 0.3,
+            start_color: [0.6, 0.8, 1.0, 0.8],
+            end_color: [0.1, 0.3, 1.0, 0.0],
+            start_size: 0.2,
+            end_size: 0.05,
+            acceleration: Vector3::new(0.0, -2.0, 0.0),
+            drag: 0.2
+        }
+    }
+}
+
+struct Particle {
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+    age: f32,
+    lifetime: f32,
+    config: EmitterConfig
+}
+
+pub struct Emitter {
+    pub config: EmitterConfig,
+    pub position: Point3<f32>,
+    pub active: bool,
+    /// Body whose centre of mass the emitter follows, for trails.
+    pub attached: Option<BodyHandle>,
+    accumulator: f32
+}
+
+pub type EmitterId = usize;
+
+/// CPU-simulated particles drawn as instanced, camera-facing quads with additive blending.
+pub struct ParticleSystem {
+    emitters: Vec<Option<Emitter>>,
+    particles: Vec<Particle>,
+    rng: Rng,
+    quad: VertexBuffer<ParticleCorner>
+}
+
+impl ParticleSystem {
+    pub fn new(display: &Display) -> Self {
+        let corners = [
+            ParticleCorner::new(-0.5, -0.5),
+            ParticleCorner::new( 0.5, -0.5),
+            ParticleCorner::new(-0.5,  0.5),
+            ParticleCorner::new( 0.5,  0.5)
+        ];
+        ParticleSystem {
+            emitters: Vec::new(),
+            particles: Vec::new(),
+            rng: Rng::new(1),
+            quad: VertexBuffer::new(display, &corners).unwrap()
+        }
+    }
+
+    pub fn get_particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn add_emitter(&mut self, config: EmitterConfig, position: Point3<f32>) -> EmitterId {
+        let emitter = Emitter {
+            config,
+            position,
+            active: true,
+            attached: None,
+            accumulator: 0.0
+        };
+        match self.emitters.iter().position(|slot| slot.is_none()) {
+            Some(id) => {
+                self.emitters[id] = Some(emitter);
+                id
+            },
+            None => {
+                self.emitters.push(Some(emitter));
+                self.emitters.len() - 1
+            }
+        }
+    }
+
+    pub fn remove_emitter(&mut self, id: EmitterId) {
+        if let Some(slot) = self.emitters.get_mut(id) {
+            *slot = None;
+        }
+    }
+
+    pub fn get_emitter_mut(&mut self, id: EmitterId) -> Option<&mut Emitter> {
+        self.emitters.get_mut(id).and_then(|slot| slot.as_mut())
+    }
+
+    /// The emitter following `body`, if there is one.
+    pub fn find_attached(&self, body: BodyHandle) -> Option<EmitterId> {
+        self.emitters.iter().position(|slot| match *slot {
+            Some(ref emitter) => emitter.attached == Some(body),
+            None => false
+        })
+    }
+
+    /// Adds a trail emitter that follows `body`.
+    pub fn attach(&mut self, config: EmitterConfig, body: BodyHandle) -> EmitterId {
+        let id = self.add_emitter(config, Point3::origin());
+        if let Some(emitter) = self.get_emitter_mut(id) {
+            emitter.attached = Some(body);
+        }
+        id
+    }
+
+    /// Moves attached emitters to their bodies, dropping those whose body is gone.
+    pub fn follow_bodies(&mut self, world: &World<f32>) {
+        for slot in self.emitters.iter_mut() {
+            let body = match *slot {
+                Some(Emitter { attached: Some(body), .. }) => body,
+                _ => continue
+            };
+            match world.rigid_body(body) {
+                Some(rigid_body) => {
+                    if let Some(ref mut emitter) = *slot {
+                        emitter.position = rigid_body.center_of_mass();
+                    }
+                },
+                None => *slot = None
+            }
+        }
+    }
+
+    /// Spawns `count` particles at once, e.g. for a collision.
+    pub fn burst(&mut self, config: &EmitterConfig, position: Point3<f32>, count: usize) {
+        for _ in 0..count {
+            self.spawn(config, position);
+        }
+    }
+
+    fn spawn(&mut self, config: &EmitterConfig, position: Point3<f32>) {
+        if self.particles.len() >= MAX_PARTICLES {
+            return;
+        }
+        let direction = cone_direction(&mut self.rng, &config.direction, config.spread);
+        let speed = self.rng.range(config.speed.0, config.speed.1);
+        let lifetime = self.rng.range(config.lifetime.0, config.lifetime.1);
+        self.particles.push(Particle {
+            position,
+            velocity: direction * speed,
+            age: 0.0,
+            lifetime,
+            config: *config
+        });
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let mut births: Vec<(EmitterConfig, Point3<f32>)> = Vec::new();
+        for slot in self.emitters.iter_mut() {
+            if let Some(ref mut emitter) = *slot {
+                if !emitter.active {
+                    continue;
+                }
+                emitter.accumulator += emitter.config.rate * dt;
+                while emitter.accumulator >= 1.0 {
+                    births.push((emitter.config, emitter.position));
+                    emitter.accumulator -= 1.0;
+                }
+            }
+        }
+        for (config, position) in births {
+            self.spawn(&config, position);
+        }
+
+        for particle in self.particles.iter_mut() {
+            let config = &particle.config;
+            particle.velocity += config.acceleration * dt;
+            particle.velocity *= f32::max(1.0 - config.drag * dt, 0.0);
+            particle.position += particle.velocity * dt;
+            particle.age += dt;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    pub fn draw<S: Surface>(&self, display: &Display, target: &mut S, program: &Program, view: [[f32; 4]; 4], projection: [[f32; 4]; 4]) {
+        if self.particles.is_empty() {
+            return;
+        }
+        let instances: Vec<ParticleInstance> = self.particles.iter()
+            .map(|particle| {
+                let config = &particle.config;
+                let t = particle.age / particle.lifetime;
+                let mut color = [0.0; 4];
+                for i in 0..4 {
+                    color[i] = config.start_color[i] + (config.end_color[i] - config.start_color[i]) * t;
+                }
+                let size = config.start_size + (config.end_size - config.start_size) * t;
+                let p = particle.position;
+                ParticleInstance::new([p.x, p.y, p.z], color, size)
+            })
+            .collect();
+        let instance_buffer = VertexBuffer::dynamic(display, &instances).unwrap();
+        let uniforms = uniform!{
+            view: view,
+            projection: projection
+        };
+        let params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLessOrEqual,
+                write: false,
+                .. Default::default()
+            },
+            blend: Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::SourceAlpha,
+                    destination: LinearBlendingFactor::One
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::One
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0)
+            },
+            .. Default::default()
+        };
+        target.draw(
+            (&self.quad, instance_buffer.per_instance().unwrap()),
+            &index::NoIndices(index::PrimitiveType::TriangleStrip),
+            program,
+            &uniforms,
+            &params
+        ).unwrap();
+    }
+}
+
+/// A random unit vector within `spread` radians of `axis`, uniform over the cap's area.
+fn cone_direction(rng: &mut Rng, axis: &Vector3<f32>, spread: f32) -> Vector3<f32> {
+    let axis = if axis.norm_squared() > 0.0 { axis.normalize() } else { Vector3::y() };
+    let cos_theta = rng.range(f32::cos(f32::min(spread, f32::consts::PI)), 1.0);
+    let sin_theta = f32::sqrt(f32::max(1.0 - cos_theta * cos_theta, 0.0));
+    let phi = rng.range(0.0, f32::consts::PI * 2.0);
+    let helper = if axis.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    let u = axis.cross(&helper).normalize();
+    let v = axis.cross(&u);
+    axis * cos_theta + (u * f32::cos(phi) + v * f32::sin(phi)) * sin_theta
+}
@@ -0,0 +1,904 @@
+use na::{Vector3, Isometry3, Point3};
+use ncollide3d::shape::{Cuboid, ShapeHandle};
+use nphysics3d::object::{BodyHandle, Material};
+use nphysics3d::volumetric::Volumetric;
+use nphysics3d::world::World;
+use nphysics3d::force_generator::ForceGeneratorHandle;
+use nphysics3d::math::Velocity;
+use std::f32;
+
+use cube::*;
+use cubody::*;
+use force_field::*;
+use barnes_hut::*;
+use boids::*;
+use impulse::*;
+use joints::*;
+use voxel::*;
+use fracture::*;
+use contact_events::*;
+use rng::Rng;
+use scene_file::{SceneError, SceneTable};
+
+pub const COLLIDER_MARGIN: f32 = 0.01;
+const DENSITY: f32 = 1.1;
+const GRAVITY: f32 = -9.81;
+const GROUND_Y: f32 = -6.0;
+const STATIC_COLOR: [f32; 3] = [0.4, 0.4, 0.45];
+const VOXEL_SIZE: f32 = 0.5;
+const LATTICE_OFFSETS: [(i32, i32, i32); 13] = [
+    (1, 0, 0), (0, 1, 0), (0, 0, 1),
+    (1, 1, 0), (1, -1, 0), (1, 0, 1), (1, 0, -1), (0, 1, 1), (0, 1, -1),
+    (1, 1, 1), (1, 1, -1), (1, -1, 1), (1, -1, -1)
+];
+
+/// The layouts a scene can start from; every preset but `Swarm` has gravity and a floor.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ScenePreset {
+    Swarm,
+    Pyramid,
+    Dominoes,
+    Jenga,
+    Container,
+    Structures,
+    Breakable,
+    /// Just the floor, for scene files that place every body themselves.
+    Empty
+}
+
+impl ScenePreset {
+    pub fn all() -> [ScenePreset; 8] {
+        [ScenePreset::Swarm, ScenePreset::Pyramid, ScenePreset::Dominoes, ScenePreset::Jenga, ScenePreset::Container, ScenePreset::Structures, ScenePreset::Breakable, ScenePreset::Empty]
+    }
+
+    pub fn from_name(name: &str) -> Option<ScenePreset> {
+        ScenePreset::all().iter().cloned().find(|preset| preset.get_name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            ScenePreset::Swarm     => "SWARM",
+            ScenePreset::Pyramid   => "PYRAMID",
+            ScenePreset::Dominoes  => "DOMINOES",
+            ScenePreset::Jenga     => "JENGA",
+            ScenePreset::Container => "CONTAINER",
+            ScenePreset::Structures => "STRUCTURES",
+            ScenePreset::Breakable => "BREAKABLE",
+            ScenePreset::Empty     => "EMPTY"
+        }
+    }
+
+    pub fn next(&self) -> ScenePreset {
+        let all = ScenePreset::all();
+        let index = all.iter().position(|preset| preset == self).unwrap_or(0);
+        all[(index + 1) % all.len()]
+    }
+
+    pub fn has_gravity(&self) -> bool {
+        *self != ScenePreset::Swarm
+    }
+}
+
+/// How a grid colours each cube.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GridColor {
+    /// Each channel is `1/(0.1+hypot)` of the cube's offset from the centre in one plane, as the swarm has always been.
+    Hypot,
+    Solid([f32; 3])
+}
+
+/// A cube of `count` × `count` × `count` blocks, `spacing` apart around `center`.
+#[derive(Clone, Debug)]
+pub struct CubeGrid {
+    pub center: Vector3<f32>,
+    pub count: i32,
+    pub spacing: f32,
+    /// Edge length of each block.
+    pub size: f32,
+    /// Largest random offset of each block along each axis.
+    pub jitter: f32,
+    pub color: GridColor
+}
+
+impl CubeGrid {
+    /// The swarm preset's grid, from `cube_iter`, `cube_resolution` and `jitter`.
+    pub fn swarm(options: &SceneOptions) -> Self {
+        CubeGrid {
+            center: na::zero(),
+            count: options.cube_iter * 2,
+            spacing: options.cube_resolution,
+            size: options.cube_resolution,
+            jitter: options.jitter,
+            color: GridColor::Hypot
+        }
+    }
+
+    /// Reads one `[[grids]]` entry; `color` is either `"hypot"` or `[r, g, b]`.
+    pub fn read(table: &SceneTable) -> Result<Self, SceneError> {
+        table.expect_keys(&["center", "count", "spacing", "size", "jitter", "color"])?;
+        let mut grid = CubeGrid {
+            center: na::zero(),
+            count: 10,
+            spacing: 1.0,
+            size: 1.0,
+            jitter: 0.0,
+            color: GridColor::Hypot
+        };
+        if let Some(c) = table.get_vec3("center")? {
+            grid.center = Vector3::new(c[0], c[1], c[2]);
+        }
+        if let Some(count) = table.get_integer("count", 1)? {
+            grid.count = count as i32;
+        }
+        if let Some(spacing) = table.get_f32_in("spacing", 0.01, f32::MAX)? {
+            grid.spacing = spacing;
+        }
+        grid.size = table.get_f32_in("size", 0.01, f32::MAX)?.unwrap_or(grid.spacing);
+        if let Some(jitter) = table.get_f32_in("jitter", 0.0, f32::MAX)? {
+            grid.jitter = jitter;
+        }
+        grid.color = match table.get_str("color") {
+            Ok(Some(name)) if name.eq_ignore_ascii_case("hypot") => GridColor::Hypot,
+            Ok(Some(name)) => return Err(table.error("color", format!("unknown colouring {:?}, expected \"hypot\" or [r, g, b]", name))),
+            Ok(None) => grid.color,
+            Err(_) => GridColor::Solid(table.get_color("color")?.unwrap())
+        };
+        Ok(grid)
+    }
+}
+
+/// A single dynamic block placed by a scene file.
+#[derive(Clone, Debug)]
+pub struct BlockSpec {
+    pub half_extents: Vector3<f32>,
+    pub position: Isometry3<f32>,
+    pub color: [f32; 3]
+}
+
+impl BlockSpec {
+    /// Reads one `[[cubes]]` entry; `size` is the full edge lengths and `rotation` an axis scaled by the angle.
+    pub fn read(table: &SceneTable) -> Result<Self, SceneError> {
+        table.expect_keys(&["position", "rotation", "size", "color"])?;
+        let position = table.get_vec3("position")?.ok_or_else(|| table.error("position", String::from("missing")))?;
+        let rotation = table.get_vec3("rotation")?.unwrap_or([0.0; 3]);
+        let size = table.get_vec3("size")?.unwrap_or([1.0; 3]);
+        if size.iter().any(|&s| s <= 0.0) {
+            return Err(table.error("size", format!("{:?} must be positive along every axis", size)));
+        }
+        Ok(BlockSpec {
+            half_extents: Vector3::new(size[0], size[1], size[2]) * 0.5,
+            position: Isometry3::new(Vector3::new(position[0], position[1], position[2]), Vector3::new(rotation[0], rotation[1], rotation[2])),
+            color: table.get_color("color")?.unwrap_or([0.8, 0.8, 0.8])
+        })
+    }
+}
+
+/// Everything needed to rebuild the cube swarm, with or without a window.
+#[derive(Clone, Debug)]
+pub struct SceneOptions {
+    pub preset: ScenePreset,
+    pub cube_iter: i32,
+    pub cube_resolution: f32,
+    pub seed: u64,
+    pub jitter: f32,
+    pub timestep: f32,
+    /// Replaces the preset's gravity when set.
+    pub gravity: Option<Vector3<f32>>,
+    pub fields: Vec<FieldSpec>,
+    /// Added on top of whatever the preset builds.
+    pub grids: Vec<CubeGrid>,
+    pub blocks: Vec<BlockSpec>,
+    pub nbody: bool,
+    pub boids: bool,
+    pub boids_config: BoidsConfig
+}
+
+impl SceneOptions {
+    /// Defaults for a preset; the gravity presets start without force fields so stacks stay standing.
+    pub fn for_preset(preset: ScenePreset) -> Self {
+        SceneOptions {
+            preset,
+            fields: if preset.has_gravity() { Vec::new() } else { vec![FieldSpec::new(FieldKind::Attractor)] },
+            cube_iter: if preset == ScenePreset::Container { 3 } else { 5 },
+            .. SceneOptions::default()
+        }
+    }
+
+    /// Switches to `preset`, replacing the grid size and force fields with the preset's own; the
+    /// seed, jitter, timestep, gravity, extra grids and cubes, the generator switches and the flock's
+    /// settings are kept.
+    /// Warns on stderr when that discards a grid size or fields a scene file set.
+    pub fn set_preset(&mut self, preset: ScenePreset) {
+        let current = SceneOptions::for_preset(self.preset);
+        if self.cube_iter != current.cube_iter {
+            eprintln!("preset {} replaces the grid size {} with its own", preset.get_name(), self.cube_iter);
+        }
+        if self.fields != current.fields {
+            eprintln!("preset {} replaces the {} force field(s) set with its own", preset.get_name(), self.fields.len());
+        }
+        let defaults = SceneOptions::for_preset(preset);
+        self.preset = preset;
+        self.cube_iter = defaults.cube_iter;
+        self.fields = defaults.fields;
+    }
+
+    /// Overwrites whatever the file's `[physics]`, `[boids]`, `[[grids]]`, `[[cubes]]` and `[[fields]]` set.
+    /// A `preset` resets everything else to that preset's defaults first.
+    pub fn read(&mut self, root: &SceneTable) -> Result<(), SceneError> {
+        if let Some(physics) = root.get_table("physics")? {
+            physics.expect_keys(&["preset", "seed", "jitter", "timestep", "gravity", "grid", "spacing", "nbody", "boids"])?;
+            let names: Vec<&str> = ScenePreset::all().iter().map(|preset| preset.get_name()).collect();
+            if let Some(preset) = physics.get_name("preset", ScenePreset::from_name, &names)? {
+                *self = SceneOptions::for_preset(preset);
+            }
+            if let Some(seed) = physics.get_integer("seed", 0)? {
+                self.seed = seed as u64;
+            }
+            if let Some(jitter) = physics.get_f32_in("jitter", 0.0, f32::MAX)? {
+                self.jitter = jitter;
+            }
+            if let Some(timestep) = physics.get_f32_in("timestep", 1.0e-4, 1.0)? {
+                self.timestep = timestep;
+            }
+            if let Some(g) = physics.get_vec3("gravity")? {
+                self.gravity = Some(Vector3::new(g[0], g[1], g[2]));
+            }
+            if let Some(grid) = physics.get_integer("grid", 1)? {
+                self.cube_iter = grid as i32;
+            }
+            if let Some(spacing) = physics.get_f32_in("spacing", 0.01, f32::MAX)? {
+                self.cube_resolution = spacing;
+            }
+            if let Some(nbody) = physics.get_bool("nbody")? {
+                self.nbody = nbody;
+            }
+            if let Some(boids) = physics.get_bool("boids")? {
+                self.boids = boids;
+            }
+        }
+        if let Some(table) = root.get_table("boids")? {
+            self.boids_config.read(&table)?;
+        }
+        if let Some(tables) = root.get_tables("grids")? {
+            self.grids = tables.iter().map(CubeGrid::read).collect::<Result<_, _>>()?;
+        }
+        if let Some(tables) = root.get_tables("cubes")? {
+            self.blocks = tables.iter().map(BlockSpec::read).collect::<Result<_, _>>()?;
+        }
+        if let Some(tables) = root.get_tables("fields")? {
+            self.fields = tables.iter().map(FieldSpec::read).collect::<Result<_, _>>()?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for SceneOptions {
+    fn default() -> Self {
+        SceneOptions {
+            preset: ScenePreset::Swarm,
+            cube_iter: 5,
+            cube_resolution: 1.0,
+            seed: 0,
+            jitter: 0.0,
+            timestep: 1.0 / 60.0,
+            gravity: None,
+            fields: vec![FieldSpec::new(FieldKind::Attractor)],
+            grids: Vec::new(),
+            blocks: Vec::new(),
+            nbody: false,
+            boids: false,
+            boids_config: BoidsConfig::default()
+        }
+    }
+}
+
+pub struct PhysicsScene {
+    pub world: World<f32>,
+    pub cubes: Vec<Cubody>,
+    pub statics: Vec<Cube>,
+    pub fields_handle: ForceGeneratorHandle,
+    pub impulses_handle: ForceGeneratorHandle,
+    pub springs_handle: ForceGeneratorHandle,
+    pub fracture: Fracture,
+    pub contacts: ContactEvents,
+    pub nbody_handle: Option<ForceGeneratorHandle>,
+    pub boids_handle: Option<ForceGeneratorHandle>
+}
+
+impl PhysicsScene {
+    pub fn new(options: &SceneOptions) -> Self {
+        let mut world = World::<f32>::new();
+        let fields_handle = world.add_force_generator(ForceFieldSet::new(Vec::new()));
+        let impulses_handle = world.add_force_generator(Impulses::new(Vec::new()));
+        let springs_handle = world.add_force_generator(Springs::new());
+        let mut scene = PhysicsScene {
+            world,
+            cubes: Vec::new(),
+            statics: Vec::new(),
+            fields_handle,
+            impulses_handle,
+            springs_handle,
+            fracture: Fracture::new(VOXEL_SIZE, DENSITY, COLLIDER_MARGIN),
+            contacts: ContactEvents::new(),
+            nbody_handle: None,
+            boids_handle: None
+        };
+        scene.world.set_timestep(options.timestep);
+        if options.preset.has_gravity() {
+            scene.world.set_gravity(Vector3::new(0.0, GRAVITY, 0.0));
+            scene.add_static(Vector3::new(40.0, 0.5, 40.0), Isometry3::new(Vector3::new(0.0, GROUND_Y - 0.5, 0.0), na::zero()));
+        } else {
+            scene.world.set_gravity(Vector3::new(0.0, 0.0, 0.0));
+        }
+        if let Some(gravity) = options.gravity {
+            scene.world.set_gravity(gravity);
+        }
+
+        let mut rng = Rng::new(options.seed);
+        match options.preset {
+            ScenePreset::Swarm     => scene.build_swarm(options, &mut rng),
+            ScenePreset::Pyramid   => scene.build_pyramid(),
+            ScenePreset::Dominoes  => scene.build_dominoes(),
+            ScenePreset::Jenga     => scene.build_jenga(),
+            ScenePreset::Container => scene.build_container(options, &mut rng),
+            ScenePreset::Structures => scene.build_structures(),
+            ScenePreset::Breakable => scene.build_breakable(options),
+            ScenePreset::Empty     => ()
+        }
+        for grid in &options.grids {
+            scene.add_grid(grid, &mut rng);
+        }
+        for block in &options.blocks {
+            scene.add_block(block.half_extents, block.position, block.color);
+        }
+
+        let handles = body_handles(&scene.cubes);
+        if let Ok(fields) = scene.world.force_generator_mut(fields_handle).downcast_mut::<ForceFieldSet>() {
+            for handle in &handles {
+                fields.add_body_part(*handle);
+            }
+            for spec in &options.fields {
+                fields.add_field(spec.build());
+            }
+        }
+        if let Ok(impulses) = scene.world.force_generator_mut(impulses_handle).downcast_mut::<Impulses>() {
+            for handle in &handles {
+                impulses.add_body_part(*handle);
+            }
+        }
+        if options.nbody {
+            scene.nbody_handle = Some(scene.world.add_force_generator(NBodyGravity::new(handles, 1.0, 0.5)));
+        }
+        if options.boids {
+            let boids = scene.create_boids(options.boids_config);
+            scene.boids_handle = Some(scene.world.add_force_generator(boids));
+        }
+        scene
+    }
+
+    /// A flock of every body that steers around the scene's static boxes.
+    pub fn create_boids(&self, config: BoidsConfig) -> Boids {
+        let mut boids = Boids::new(body_handles(&self.cubes), config);
+        for cube in &self.statics {
+            boids.add_obstacle(cube.get_position(), cube.get_half_extents());
+        }
+        boids
+    }
+
+    /// Adds a dynamic box with its collider and a matching `Cubody`.
+    pub fn add_block(&mut self, half_extents: Vector3<f32>, pos: Isometry3<f32>, color: [f32; 3]) -> BodyHandle {
+        let geom = ShapeHandle::new(Cuboid::new(half_extents - Vector3::repeat(COLLIDER_MARGIN)));
+        let handle = self.world.add_rigid_body(pos, geom.inertia(DENSITY), geom.center_of_mass());
+        self.world.add_collider(
+            COLLIDER_MARGIN,
+            geom,
+            handle,
+            Isometry3::identity(),
+            Material::default()
+        );
+        let cube = if half_extents.x == half_extents.y && half_extents.y == half_extents.z {
+            Cube::new(CubeType::Block, pos, color, half_extents.x)
+        } else {
+            Cube::with_extents(CubeType::Block, pos, color, half_extents)
+        };
+        self.cubes.push(Cubody::new(cube, handle));
+        handle
+    }
+
+    /// Adds a box attached to the ground body, which never moves.
+    fn add_static(&mut self, half_extents: Vector3<f32>, pos: Isometry3<f32>) {
+        let geom = ShapeHandle::new(Cuboid::new(half_extents - Vector3::repeat(COLLIDER_MARGIN)));
+        self.world.add_collider(
+            COLLIDER_MARGIN,
+            geom,
+            BodyHandle::ground(),
+            pos,
+            Material::default()
+        );
+        self.statics.push(Cube::with_extents(CubeType::Block, pos, STATIC_COLOR, half_extents));
+    }
+
+    fn build_swarm(&mut self, options: &SceneOptions, rng: &mut Rng) {
+        self.add_grid(&CubeGrid::swarm(options), rng);
+    }
+
+    /// Adds a grid of blocks, counting offsets from its centre so that an even count sits one
+    /// block towards the negative side, as the swarm always has.
+    pub fn add_grid(&mut self, grid: &CubeGrid, rng: &mut Rng) {
+        let half = grid.count / 2;
+        for i in -half..grid.count - half {
+            for j in -half..grid.count - half {
+                for k in -half..grid.count - half {
+                    let fi = i as f32;
+                    let fj = j as f32;
+                    let fk = k as f32;
+                    let color = match grid.color {
+                        GridColor::Hypot => [1.0/(0.1+hypot(fi, fj)), 1.0/(0.1+hypot(fk, fj)), 1.0/(0.1+hypot(fi, fk))],
+                        GridColor::Solid(color) => color
+                    };
+                    let jitter = Vector3::new(
+                        rng.range(-grid.jitter, grid.jitter),
+                        rng.range(-grid.jitter, grid.jitter),
+                        rng.range(-grid.jitter, grid.jitter)
+                    );
+                    let location: Vector3<f32> = grid.center + Vector3::new(fi, fj, fk) * grid.spacing + jitter;
+                    self.add_block(Vector3::repeat(grid.size*0.5), Isometry3::new(location, na::zero()), color);
+                }
+            }
+        }
+    }
+
+    /// A stepped pyramid of unit cubes with a ramp beside it for sliding blocks into it.
+    fn build_pyramid(&mut self) {
+        let base = 8;
+        for level in 0..base {
+            let count = base - level;
+            let offset = (count - 1) as f32 * 0.5;
+            for i in 0..count {
+                for k in 0..count {
+                    let location = Vector3::new(
+                        (i as f32 - offset) * 1.01,
+                        GROUND_Y + 0.5 + level as f32,
+                        (k as f32 - offset) * 1.01
+                    );
+                    let shade = 0.3 + 0.7 * level as f32 / base as f32;
+                    self.add_block(Vector3::repeat(0.5), Isometry3::new(location, na::zero()), [shade, 0.4, 1.0 - shade]);
+                }
+            }
+        }
+
+        let tilt = -0.35;
+        self.add_static(Vector3::new(6.0, 0.25, 1.5), Isometry3::new(Vector3::new(-13.0, GROUND_Y + 2.2, 0.0), Vector3::new(0.0, 0.0, tilt)));
+        for n in 0..3 {
+            let x = -17.0 + n as f32 * 1.5;
+            let y = GROUND_Y + 2.2 + 0.8 + (-13.0 - x) * f32::tan(-tilt);
+            self.add_block(Vector3::repeat(0.5), Isometry3::new(Vector3::new(x, y, 0.0), Vector3::new(0.0, 0.0, tilt)), [1.0, 0.8, 0.2]);
+        }
+    }
+
+    /// A line of dominoes whose first tile already leans into the second.
+    fn build_dominoes(&mut self) {
+        let half_extents = Vector3::new(0.1, 1.0, 0.5);
+        let count = 24;
+        for n in 0..count {
+            let x = (n as f32 - count as f32 * 0.5) * 1.2;
+            let lean = if n == 0 { -0.3 } else { 0.0 };
+            let lift = if n == 0 { 0.1 } else { 0.0 };
+            let shade = n as f32 / count as f32;
+            self.add_block(
+                half_extents,
+                Isometry3::new(Vector3::new(x, GROUND_Y + half_extents.y + lift, 0.0), Vector3::new(0.0, 0.0, lean)),
+                [0.9, 0.9 - shade * 0.6, 0.3 + shade * 0.6]
+            );
+        }
+    }
+
+    /// Layers of three blocks, each layer turned a quarter turn from the one below.
+    fn build_jenga(&mut self) {
+        let half_extents = Vector3::new(1.5, 0.3, 0.5);
+        let layers = 16;
+        for layer in 0..layers {
+            let y = GROUND_Y + half_extents.y + layer as f32 * (half_extents.y * 2.0 + 0.002);
+            let turned = layer % 2 == 1;
+            for n in 0..3 {
+                let across = (n as f32 - 1.0) * (half_extents.z * 2.0 + 0.01);
+                let (location, angle) = if turned {
+                    (Vector3::new(across, y, 0.0), f32::consts::FRAC_PI_2)
+                } else {
+                    (Vector3::new(0.0, y, across), 0.0)
+                };
+                let shade = if (layer + n) % 2 == 0 { 1.0 } else { 0.85 };
+                self.add_block(
+                    half_extents,
+                    Isometry3::new(location, Vector3::new(0.0, angle, 0.0)),
+                    [0.8 * shade, 0.6 * shade, 0.35 * shade]
+                );
+            }
+        }
+    }
+
+    /// An open box with a jittered grid of cubes dropped into it.
+    fn build_container(&mut self, options: &SceneOptions, rng: &mut Rng) {
+        let cube_iter = options.cube_iter;
+        let spacing = 1.2;
+        let half_width = cube_iter as f32 * spacing + 1.0;
+        let half_height = 4.0;
+        let wall_y = GROUND_Y + half_height;
+        self.add_static(Vector3::new(half_width, half_height, 0.25), Isometry3::new(Vector3::new(0.0, wall_y,  half_width), na::zero()));
+        self.add_static(Vector3::new(half_width, half_height, 0.25), Isometry3::new(Vector3::new(0.0, wall_y, -half_width), na::zero()));
+        self.add_static(Vector3::new(0.25, half_height, half_width), Isometry3::new(Vector3::new( half_width, wall_y, 0.0), na::zero()));
+        self.add_static(Vector3::new(0.25, half_height, half_width), Isometry3::new(Vector3::new(-half_width, wall_y, 0.0), na::zero()));
+
+        for i in -cube_iter..cube_iter {
+            for j in 0..(cube_iter * 2) {
+                for k in -cube_iter..cube_iter {
+                    let jitter = Vector3::new(
+                        rng.range(-options.jitter, options.jitter),
+                        0.0,
+                        rng.range(-options.jitter, options.jitter)
+                    );
+                    let location = Vector3::new(
+                        (i as f32 + 0.5) * spacing,
+                        GROUND_Y + 2.0 + j as f32 * spacing,
+                        (k as f32 + 0.5) * spacing
+                    ) + jitter;
+                    let color = [(i + cube_iter) as f32 / (cube_iter * 2) as f32, j as f32 / (cube_iter * 2) as f32, 0.8];
+                    self.add_block(Vector3::repeat(0.5), Isometry3::new(location, na::zero()), color);
+                }
+            }
+        }
+    }
+    /// A chain, a rope, a bridge and a soft lattice side by side.
+    fn build_structures(&mut self) {
+        let top = GROUND_Y + 14.0;
+        self.build_chain(Point3::new(-16.0, top, 0.0), Vector3::new(1.0, 0.0, 0.0), 8, JointKind::Revolute(Vector3::z()));
+        self.build_rope(Point3::new(-6.0, top, -3.0), Point3::new(6.0, top, -3.0), 14);
+        self.build_bridge(Point3::new(-6.0, GROUND_Y + 4.0, 4.0), Point3::new(6.0, GROUND_Y + 4.0, 4.0), 10);
+        self.build_lattice(Point3::new(10.0, GROUND_Y + 2.0, -2.0), (4, 4, 4), 1.4, 60.0);
+    }
+
+    /// Adds a joint between two bodies; see `joints::connect`.
+    pub fn connect(&mut self, a: BodyHandle, b: BodyHandle, kind: JointKind, anchor: Point3<f32>) {
+        connect(&mut self.world, a, b, kind, anchor);
+    }
+
+    /// Adds a spring between two world-space points, at rest at their current distance.
+    pub fn add_spring(&mut self, a: BodyHandle, point_a: Point3<f32>, b: BodyHandle, point_b: Point3<f32>, stiffness: f32, damping: f32) {
+        let spring = Spring::between(&self.world, a, point_a, b, point_b, stiffness, damping);
+        if let Ok(springs) = self.world.force_generator_mut(self.springs_handle).downcast_mut::<Springs>() {
+            springs.add_spring(spring);
+        }
+    }
+
+    /// Cubes hanging from a ground pin at `start`, each joined to the next halfway between them.
+    pub fn build_chain(&mut self, start: Point3<f32>, direction: Vector3<f32>, links: usize, kind: JointKind) -> Vec<BodyHandle> {
+        let step = direction.normalize() * 1.2;
+        let mut handles: Vec<BodyHandle> = Vec::with_capacity(links);
+        let mut previous = BodyHandle::ground();
+        for n in 0..links {
+            let center = start + step * (n as f32 + 0.5);
+            let handle = self.add_block(Vector3::repeat(0.4), Isometry3::new(center.coords, na::zero()), [0.8, 0.8, 0.9]);
+            // The first link is pinned with a ball so it can swing whatever the chain's joint kind.
+            let joint = if n == 0 { JointKind::Ball } else { kind };
+            self.connect(previous, handle, joint, center - step * 0.5);
+            handles.push(handle);
+            previous = handle;
+        }
+        handles
+    }
+
+    /// Small cubes joined by ball joints, pinned to the ground body at both ends.
+    pub fn build_rope(&mut self, start: Point3<f32>, end: Point3<f32>, segments: usize) -> Vec<BodyHandle> {
+        let segments = usize::max(segments, 1);
+        let step = (end - start) / segments as f32;
+        let mut handles: Vec<BodyHandle> = Vec::with_capacity(segments);
+        let mut previous = BodyHandle::ground();
+        for n in 0..segments {
+            let center = start + step * (n as f32 + 0.5);
+            let handle = self.add_block(Vector3::repeat(0.2), Isometry3::new(center.coords, na::zero()), [0.9, 0.7, 0.4]);
+            self.connect(previous, handle, JointKind::Ball, center - step * 0.5);
+            handles.push(handle);
+            previous = handle;
+        }
+        self.connect(previous, BodyHandle::ground(), JointKind::Ball, end);
+        handles
+    }
+
+    /// Planks hinged to each other across the gap, with both ends hinged to the ground body.
+    pub fn build_bridge(&mut self, start: Point3<f32>, end: Point3<f32>, planks: usize) -> Vec<BodyHandle> {
+        let planks = usize::max(planks, 1);
+        let span = end - start;
+        let step = span / planks as f32;
+        let axis = Vector3::y().cross(&span).normalize();
+        let half_extents = Vector3::new(step.norm() * 0.45, 0.1, 1.0);
+        let rotation = Vector3::y() * f32::atan2(-span.z, span.x);
+        let mut handles: Vec<BodyHandle> = Vec::with_capacity(planks);
+        let mut previous = BodyHandle::ground();
+        for n in 0..planks {
+            let center = start + step * (n as f32 + 0.5);
+            let handle = self.add_block(half_extents, Isometry3::new(center.coords, rotation), [0.6, 0.45, 0.3]);
+            self.connect(previous, handle, JointKind::Revolute(axis), center - step * 0.5);
+            handles.push(handle);
+            previous = handle;
+        }
+        self.connect(previous, BodyHandle::ground(), JointKind::Revolute(axis), end);
+        handles
+    }
+
+    /// A block of cubes held together only by springs to their neighbours, including diagonals.
+    pub fn build_lattice(&mut self, origin: Point3<f32>, size: (usize, usize, usize), spacing: f32, stiffness: f32) -> Vec<BodyHandle> {
+        let (nx, ny, nz) = size;
+        let index = |x: usize, y: usize, z: usize| (x * ny + y) * nz + z;
+        let mut handles: Vec<BodyHandle> = Vec::with_capacity(nx * ny * nz);
+        let mut centers: Vec<Point3<f32>> = Vec::with_capacity(nx * ny * nz);
+        for x in 0..nx {
+            for y in 0..ny {
+                for z in 0..nz {
+                    let center = origin + Vector3::new(x as f32, y as f32, z as f32) * spacing;
+                    let color = [0.3 + 0.7 * x as f32 / nx as f32, 0.9, 0.3 + 0.7 * z as f32 / nz as f32];
+                    handles.push(self.add_block(Vector3::repeat(0.4), Isometry3::new(center.coords, na::zero()), color));
+                    centers.push(center);
+                }
+            }
+        }
+        let damping = stiffness * 0.05;
+        for x in 0..nx {
+            for y in 0..ny {
+                for z in 0..nz {
+                    let i = index(x, y, z);
+                    // Each cell links forward to its 13 neighbours so every pair is joined exactly once.
+                    for &(dx, dy, dz) in LATTICE_OFFSETS.iter() {
+                        let (tx, ty, tz) = (x as i32 + dx, y as i32 + dy, z as i32 + dz);
+                        if tx < 0 || ty < 0 || tz < 0 || tx >= nx as i32 || ty >= ny as i32 || tz >= nz as i32 {
+                            continue;
+                        }
+                        let j = index(tx as usize, ty as usize, tz as usize);
+                        self.add_spring(handles[i], centers[i], handles[j], centers[j], stiffness, damping);
+                    }
+                }
+            }
+        }
+        handles
+    }
+
+    /// A noise-carved voxel boulder dropped onto a solid voxel wall.
+    fn build_breakable(&mut self, options: &SceneOptions) {
+        let wall = VoxelGrid::solid((16, 12, 2), VOXEL_SIZE, [0.7, 0.7, 0.75]);
+        self.add_voxels(&wall, &Isometry3::new(Vector3::new(-4.0, GROUND_Y + VOXEL_SIZE * 0.5, 0.0), na::zero()));
+        let boulder = VoxelGrid::from_noise(8, VOXEL_SIZE, options.seed as f32 * 0.1, 0.3);
+        if !boulder.voxels.is_empty() {
+            self.add_voxels(&boulder, &Isometry3::new(Vector3::new(-2.0, GROUND_Y + 14.0, -1.0), na::zero()));
+        }
+    }
+
+    /// Adds a voxel grid as one breakable body, drawn as one `Cubody` per cell.
+    pub fn add_voxels(&mut self, grid: &VoxelGrid, origin: &Isometry3<f32>) -> BodyHandle {
+        let (handle, cells) = {
+            let body = self.fracture.add_grid(&mut self.world, grid, origin);
+            (body.handle, body.cells.clone())
+        };
+        self.add_cell_cubes(handle, &cells);
+        handle
+    }
+
+    fn add_cell_cubes(&mut self, handle: BodyHandle, cells: &[VoxelCell]) {
+        let pose = match self.world.rigid_body(handle) {
+            Some(rigid_body) => rigid_body.position(),
+            None => return
+        };
+        for cell in cells {
+            let offset = Isometry3::new(cell.offset, na::zero());
+            let cube = Cube::new(CubeType::Block, pose * offset, cell.voxel.color, self.fracture.get_cell_size() * 0.5);
+            self.cubes.push(Cubody::with_offset(cube, handle, offset));
+        }
+    }
+
+    /// Lets the force generators act on a body added after the scene was built.
+    pub fn register_body(&mut self, handle: BodyHandle) {
+        if let Ok(fields) = self.world.force_generator_mut(self.fields_handle).downcast_mut::<ForceFieldSet>() {
+            fields.add_body_part(handle);
+        }
+        if let Ok(impulses) = self.world.force_generator_mut(self.impulses_handle).downcast_mut::<Impulses>() {
+            impulses.add_body_part(handle);
+        }
+        if let Some(nbody_handle) = self.nbody_handle {
+            if let Ok(gravity) = self.world.force_generator_mut(nbody_handle).downcast_mut::<NBodyGravity>() {
+                gravity.add_body_part(handle);
+            }
+        }
+        if let Some(boids_handle) = self.boids_handle {
+            if let Ok(boids) = self.world.force_generator_mut(boids_handle).downcast_mut::<Boids>() {
+                boids.add_body_part(handle);
+            }
+        }
+    }
+
+    /// Drops a removed body from the force generators; the world reuses freed handles, so a
+    /// fragment could otherwise be listed twice and pushed twice as hard.
+    pub fn unregister_body(&mut self, handle: BodyHandle) {
+        if let Ok(fields) = self.world.force_generator_mut(self.fields_handle).downcast_mut::<ForceFieldSet>() {
+            fields.remove_body_part(handle);
+        }
+        if let Ok(impulses) = self.world.force_generator_mut(self.impulses_handle).downcast_mut::<Impulses>() {
+            impulses.remove_body_part(handle);
+        }
+        if let Some(nbody_handle) = self.nbody_handle {
+            if let Ok(gravity) = self.world.force_generator_mut(nbody_handle).downcast_mut::<NBodyGravity>() {
+                gravity.remove_body_part(handle);
+            }
+        }
+        if let Some(boids_handle) = self.boids_handle {
+            if let Ok(boids) = self.world.force_generator_mut(boids_handle).downcast_mut::<Boids>() {
+                boids.remove_body_part(handle);
+            }
+        }
+    }
+
+    /// Steps the world, splitting any voxel body that broke on the previous step,
+    /// publishing the step's contact events and moving every cube to its body's new position.
+    /// Returns true when a body was split, i.e. the set of bodies changed.
+    pub fn step(&mut self) -> bool {
+        let events = self.fracture.apply_breaks(&mut self.world);
+        let fractured = !events.is_empty();
+        for event in events {
+            self.cubes.retain(|cubody| cubody.handle != event.removed);
+            self.unregister_body(event.removed);
+            for fragment in &event.fragments {
+                self.add_cell_cubes(fragment.handle, &fragment.cells);
+                self.register_body(fragment.handle);
+            }
+        }
+        self.fracture.record_velocities(&self.world);
+        self.contacts.record_velocities(&self.world, &body_handles(&self.cubes));
+        self.world.step();
+        self.fracture.detect_breaks(&self.world);
+        self.contacts.collect(&self.world);
+        for cubody in self.cubes.iter_mut() {
+            if let Some(rigid_body) = self.world.rigid_body(cubody.handle) {
+                cubody.cube.move_location(rigid_body.position() * cubody.offset);
+            }
+        }
+        fractured
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BodyState {
+    pub handle: BodyHandle,
+    pub position: Isometry3<f32>,
+    pub velocity: Velocity<f32>
+}
+
+/// The distinct bodies behind `cubes`; the cells of a voxel body are stored next to each other.
+pub fn body_handles(cubes: &[Cubody]) -> Vec<BodyHandle> {
+    let mut handles: Vec<BodyHandle> = cubes.iter().map(|cubody| cubody.handle).collect();
+    handles.dedup();
+    handles
+}
+
+/// Reads the position and velocity of every body behind `cubes`.
+pub fn capture_body_states(world: &World<f32>, cubes: &[Cubody]) -> Vec<BodyState> {
+    body_handles(cubes).into_iter()
+        .filter_map(|handle| world.rigid_body(handle).map(|rigid_body| BodyState {
+            handle,
+            position: rigid_body.position(),
+            velocity: *rigid_body.velocity()
+        }))
+        .collect()
+}
+
+pub fn hypot(a: f32, b: f32) -> f32 {
+    f32::sqrt(f32::powi(a, 2) + f32::powi(b, 2))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scene_file::SceneFile;
+
+    const STEPS: usize = 30;
+
+    /// Every body's position and velocity after `STEPS` steps of a small jittered swarm pulled by
+    /// the attractor, n-body gravity and boids at once, as raw bits.
+    fn run(seed: u64) -> Vec<u32> {
+        let options = SceneOptions {
+            seed,
+            cube_iter: 2,
+            jitter: 0.2,
+            nbody: true,
+            boids: true,
+            .. SceneOptions::default()
+        };
+        let mut scene = PhysicsScene::new(&options);
+        for _ in 0..STEPS {
+            scene.step();
+        }
+        let mut bits: Vec<u32> = Vec::new();
+        for state in capture_body_states(&scene.world, &scene.cubes) {
+            let values = state.position.translation.vector.iter()
+                .chain(state.position.rotation.quaternion().coords.iter())
+                .chain(state.velocity.linear.iter())
+                .chain(state.velocity.angular.iter());
+            bits.extend(values.map(|value| value.to_bits()));
+        }
+        bits
+    }
+
+    #[test]
+    fn same_seed_is_bit_identical() {
+        let first = run(7);
+        assert!(!first.is_empty());
+        assert_eq!(first, run(7));
+    }
+
+    #[test]
+    fn different_seed_differs() {
+        assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn bad_field_strength_is_named() {
+        let text = "[[fields]]\nkind = \"vortex\"\nstrength = 5.0\n\n[[fields]]\nkind = \"attractor\"\nstrength = -5.0\n";
+        let scene = SceneFile::parse(text).unwrap();
+        let err = SceneOptions::default().read(&scene.get_root()).err().expect("a negative attractor should not read");
+        assert_eq!(err.field, "fields[1].strength");
+    }
+
+    #[test]
+    fn boids_table_sets_the_flock() {
+        let scene = SceneFile::parse("[boids]\nneighbour_radius = 6.0\navoidance_distance = 1.0\nmax_speed = 4.0\n").unwrap();
+        let mut options = SceneOptions::default();
+        options.read(&scene.get_root()).unwrap();
+        assert_eq!(options.boids_config.neighbour_radius, 6.0);
+        assert_eq!(options.boids_config.avoidance_distance, 1.0);
+        assert_eq!(options.boids_config.max_speed, 4.0);
+        assert_eq!(options.boids_config.cohesion_weight, BoidsConfig::default().cohesion_weight);
+
+        let scene = SceneFile::parse("[boids]\nmax_speed = -1.0\n").unwrap();
+        let err = SceneOptions::default().read(&scene.get_root()).err().expect("a negative speed should not read");
+        assert_eq!(err.field, "boids.max_speed");
+    }
+
+    fn assert_listed_once(name: &str, parts: &[BodyHandle], handles: &[BodyHandle]) {
+        for handle in handles {
+            let count = parts.iter().filter(|part| *part == handle).count();
+            assert_eq!(count, 1, "{} lists {:?} {} times", name, handle, count);
+        }
+        assert_eq!(parts.len(), handles.len(), "{} lists bodies that are gone", name);
+    }
+
+    #[test]
+    fn fragments_are_registered_once() {
+        let options = SceneOptions {
+            nbody: true,
+            boids: true,
+            .. SceneOptions::for_preset(ScenePreset::Breakable)
+        };
+        let mut scene = PhysicsScene::new(&options);
+        let unbroken = scene.fracture.get_bodies().len();
+        // Break on the first real contact.
+        scene.fracture.threshold = 0.1;
+        let mut steps = 0;
+        while scene.fracture.get_bodies().len() == unbroken {
+            assert!(steps < 600, "nothing broke");
+            scene.step();
+            steps += 1;
+        }
+        // Fragments join the generators at the start of the step after the break.
+        scene.step();
+
+        let handles = body_handles(&scene.cubes);
+        let world = &mut scene.world;
+        if let Ok(fields) = world.force_generator_mut(scene.fields_handle).downcast_mut::<ForceFieldSet>() {
+            assert_listed_once("fields", fields.get_parts(), &handles);
+        }
+        if let Ok(impulses) = world.force_generator_mut(scene.impulses_handle).downcast_mut::<Impulses>() {
+            assert_listed_once("impulses", impulses.get_parts(), &handles);
+        }
+        if let Ok(gravity) = world.force_generator_mut(scene.nbody_handle.unwrap()).downcast_mut::<NBodyGravity>() {
+            assert_listed_once("n-body gravity", gravity.get_parts(), &handles);
+        }
+        if let Ok(boids) = world.force_generator_mut(scene.boids_handle.unwrap()).downcast_mut::<Boids>() {
+            assert_listed_once("boids", boids.get_parts(), &handles);
+        }
+    }
+}
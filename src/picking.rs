@@ -0,0 +1,87 @@
+extern crate nalgebra as na;
+
+use na::{Matrix4, Point3, Vector4};
+use ncollide3d::query::Ray;
+use ncollide3d::world::CollisionGroups;
+use nphysics3d::joint::{ConstraintHandle, MouseConstraint};
+use nphysics3d::object::BodyHandle;
+use nphysics3d::world::World;
+
+const GRAB_LIMIT: f32 = 1.0;
+
+/// Builds a world-space ray through the given window position.
+pub fn screen_ray(cursor: (f32, f32), window_size: [f32; 2], view: &Matrix4<f32>, projection: &Matrix4<f32>) -> Option<Ray<f32>> {
+    let inverse = match (projection * view).try_inverse() {
+        Some(inverse) => inverse,
+        None => return None
+    };
+    let x = cursor.0 / window_size[0] * 2.0 - 1.0;
+    let y = 1.0 - cursor.1 / window_size[1] * 2.0;
+    let near = unproject(&inverse, x, y, -1.0);
+    let far  = unproject(&inverse, x, y,  1.0);
+    Some(Ray::new(near, (far - near).normalize()))
+}
+
+fn unproject(inverse: &Matrix4<f32>, x: f32, y: f32, z: f32) -> Point3<f32> {
+    let p = inverse * Vector4::new(x, y, z, 1.0);
+    Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+}
+
+/// Returns the closest non-ground body hit by the ray, the hit point and its distance along the ray.
+pub fn pick_body(world: &World<f32>, ray: &Ray<f32>) -> Option<(BodyHandle, Point3<f32>, f32)> {
+    let groups = CollisionGroups::new();
+    let mut nearest: Option<(BodyHandle, Point3<f32>, f32)> = None;
+    for (collider, intersection) in world.collision_world().interferences_with_ray(ray, &groups) {
+        let body = collider.data().body();
+        if body.is_ground() {
+            continue;
+        }
+        if nearest.map_or(true, |(_, _, toi)| intersection.toi < toi) {
+            nearest = Some((body, ray.origin + ray.dir * intersection.toi, intersection.toi));
+        }
+    }
+    nearest
+}
+
+/// Drags a body around with a mouse constraint anchored to the ground.
+pub struct Grabber {
+    grabbed: Option<(BodyHandle, ConstraintHandle, f32)>
+}
+
+impl Grabber {
+    pub fn new() -> Self {
+        Grabber {
+            grabbed: None
+        }
+    }
+
+    pub fn get_grabbed(&self) -> Option<BodyHandle> {
+        self.grabbed.map(|(body, _, _)| body)
+    }
+
+    pub fn grab(&mut self, world: &mut World<f32>, ray: &Ray<f32>) {
+        self.release(world);
+        if let Some((body, hit, distance)) = pick_body(world, ray) {
+            let local_anchor = world.body_part(body).position().inverse() * hit;
+            let constraint = MouseConstraint::new(BodyHandle::ground(), body, hit, local_anchor, GRAB_LIMIT);
+            let handle = world.add_constraint(constraint);
+            self.grabbed = Some((body, handle, distance));
+        }
+    }
+
+    /// Moves the grab point so it stays at the same distance along the new ray.
+    pub fn drag(&mut self, world: &mut World<f32>, ray: &Ray<f32>) {
+        if let Some((_, handle, distance)) = self.grabbed {
+            if let Ok(constraint) = world.constraint_mut(handle).downcast_mut::<MouseConstraint<f32>>() {
+                constraint.set_anchor_1(ray.origin + ray.dir * distance);
+            }
+        }
+    }
+
+    /// Lets go of the body, which keeps whatever velocity the drag gave it.
+    pub fn release(&mut self, world: &mut World<f32>) {
+        if let Some((_, handle, _)) = self.grabbed.take() {
+            world.remove_constraint(handle);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,127 @@
+use rng::Rng;
+
+/// Gray-Scott reaction-diffusion on a square grid that wraps at its edges.
+///
+/// Chemical `u` is fed in everywhere and turned into `v` wherever the two meet; `v` is
+/// removed at the kill rate. Coordinates are texture coordinates running 0 to 1.
+pub struct GrayScott {
+    size: usize,
+    u: Vec<f32>,
+    v: Vec<f32>,
+    next_u: Vec<f32>,
+    next_v: Vec<f32>,
+    pub feed: f32,
+    pub kill: f32,
+    pub diffusion_u: f32,
+    pub diffusion_v: f32,
+    /// Unit time steps taken by every `update`.
+    pub steps_per_frame: usize
+}
+
+impl GrayScott {
+    pub fn new(size: usize) -> Self {
+        let size = usize::max(size, 3);
+        GrayScott {
+            size,
+            u: vec![1.0; size * size],
+            v: vec![0.0; size * size],
+            next_u: vec![1.0; size * size],
+            next_v: vec![0.0; size * size],
+            feed: 0.035,
+            kill: 0.065,
+            diffusion_u: 0.16,
+            diffusion_v: 0.08,
+            steps_per_frame: 8
+        }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// Concentration of `v` in every cell, row by row from v = 0.
+    pub fn get_v(&self) -> &[f32] {
+        &self.v
+    }
+
+    pub fn clear(&mut self) {
+        for u in self.u.iter_mut() {
+            *u = 1.0;
+        }
+        for v in self.v.iter_mut() {
+            *v = 0.0;
+        }
+    }
+
+    /// Drops a disc of `v` into the `u` bath, where the pattern starts growing.
+    pub fn seed(&mut self, x: f32, y: f32, radius: f32) {
+        let size = self.size as i32;
+        let cx = (x * self.size as f32) as i32;
+        let cy = (y * self.size as f32) as i32;
+        let r = f32::max(radius * self.size as f32, 1.0) as i32;
+        for dy in -r..r + 1 {
+            for dx in -r..r + 1 {
+                if dx * dx + dy * dy > r * r {
+                    continue;
+                }
+                let i = ((cx + dx) % size + size) % size;
+                let j = ((cy + dy) % size + size) % size;
+                let k = j as usize * self.size + i as usize;
+                self.u[k] = 0.5;
+                self.v[k] = 0.25;
+            }
+        }
+    }
+
+    /// Seeds `count` discs at random places.
+    pub fn seed_random(&mut self, rng: &mut Rng, count: usize, radius: f32) {
+        for _ in 0..count {
+            let x = rng.next_f32();
+            let y = rng.next_f32();
+            self.seed(x, y, radius);
+        }
+    }
+
+    pub fn update(&mut self) {
+        for _ in 0..self.steps_per_frame {
+            self.step();
+        }
+    }
+
+    /// One explicit Euler step of unit length with a five-point Laplacian.
+    pub fn step(&mut self) {
+        let n = self.size;
+        for j in 0..n {
+            let up = (j + 1) % n * n;
+            let down = (j + n - 1) % n * n;
+            let row = j * n;
+            for i in 0..n {
+                let right = (i + 1) % n;
+                let left = (i + n - 1) % n;
+                let k = row + i;
+                let (u, v) = (self.u[k], self.v[k]);
+                let laplacian_u = self.u[row + left] + self.u[row + right] + self.u[up + i] + self.u[down + i] - 4.0 * u;
+                let laplacian_v = self.v[row + left] + self.v[row + right] + self.v[up + i] + self.v[down + i] - 4.0 * v;
+                let reaction = u * v * v;
+                self.next_u[k] = u + self.diffusion_u * laplacian_u - reaction + self.feed * (1.0 - u);
+                self.next_v[k] = v + self.diffusion_v * laplacian_v + reaction - (self.feed + self.kill) * v;
+            }
+        }
+        ::std::mem::swap(&mut self.u, &mut self.next_u);
+        ::std::mem::swap(&mut self.v, &mut self.next_v);
+    }
+
+    /// RGBA bytes shading each cell from `low` to `high` by its `v`, with the same ramp in alpha.
+    pub fn get_image(&self, low: [f32; 3], high: [f32; 3]) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::with_capacity(self.v.len() * 4);
+        for v in &self.v {
+            // v rarely goes much above a third, so stretch that range over the whole ramp.
+            let t = f32::min(f32::max(v * 3.0, 0.0), 1.0);
+            for c in 0..3 {
+                data.push(((low[c] + (high[c] - low[c]) * t) * 255.0) as u8);
+            }
+            data.push((t * 255.0) as u8);
+        }
+        data
+    }
+}
@@ -0,0 +1,425 @@
+use std::env;
+use std::f32;
+use std::fmt;
+use std::fs;
+use std::process;
+use toml::Value;
+use toml::value::Table;
+
+/// Every section a scene file may have; each binary reads the ones it draws and ignores the rest.
+const SECTIONS: [&str; 10] = ["render", "camera", "light", "physics", "boids", "grids", "cubes", "fields", "sheets", "noise_world"];
+
+/// Something wrong with a scene file, naming the field it was found in, e.g. `fields[1].strength`.
+#[derive(Debug)]
+pub struct SceneError {
+    pub field: String,
+    pub message: String
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+/// A parsed scene file, checked only for syntax and unknown sections until its sections are read.
+pub struct SceneFile {
+    root: Table
+}
+
+impl SceneFile {
+    pub fn load(path: &str) -> Result<Self, SceneError> {
+        let text = fs::read_to_string(path).map_err(|err| SceneError { field: String::new(), message: err.to_string() })?;
+        SceneFile::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SceneError> {
+        let error = |message: String| SceneError { field: String::new(), message };
+        let root = match text.parse::<Value>().map_err(|err| error(err.to_string()))? {
+            Value::Table(root) => root,
+            _ => return Err(error(String::from("expected a table at the top level")))
+        };
+        let scene = SceneFile {
+            root
+        };
+        scene.get_root().expect_keys(&SECTIONS)?;
+        Ok(scene)
+    }
+
+    pub fn get_root(&self) -> SceneTable {
+        SceneTable {
+            path: String::new(),
+            table: &self.root
+        }
+    }
+
+    /// Overwrites whatever the file's `[render]`, `[camera]` and `[light]` set, keeping the rest.
+    pub fn read_view(&self, render: &mut RenderSettings, camera: &mut CameraSettings, light: &mut LightSettings) -> Result<(), SceneError> {
+        let root = self.get_root();
+        if let Some(table) = root.get_table("render")? {
+            render.read(&table)?;
+        }
+        if let Some(table) = root.get_table("camera")? {
+            camera.read(&table)?;
+        }
+        if let Some(table) = root.get_table("light")? {
+            light.read(&table)?;
+        }
+        Ok(())
+    }
+}
+
+/// One table of a scene file with its dotted path from the root, so every error can say which
+/// field was wrong.
+pub struct SceneTable<'a> {
+    path: String,
+    table: &'a Table
+}
+
+impl<'a> SceneTable<'a> {
+    fn field_path(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            String::from(key)
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    pub fn error(&self, key: &str, message: String) -> SceneError {
+        SceneError {
+            field: self.field_path(key),
+            message
+        }
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.table.contains_key(key)
+    }
+
+    /// Rejects keys outside `keys`, so a misspelt field fails loudly instead of being ignored.
+    pub fn expect_keys(&self, keys: &[&str]) -> Result<(), SceneError> {
+        match self.table.keys().find(|key| !keys.contains(&key.as_str())) {
+            Some(key) => Err(self.error(key, format!("unknown field, expected one of {}", keys.join(", ")))),
+            None => Ok(())
+        }
+    }
+
+    fn mismatch(&self, key: &str, expected: &str, found: &Value) -> SceneError {
+        self.error(key, format!("expected {}, found {}", expected, found.type_str()))
+    }
+
+    pub fn get_table(&self, key: &str) -> Result<Option<SceneTable<'a>>, SceneError> {
+        match self.table.get(key) {
+            Some(&Value::Table(ref table)) => Ok(Some(SceneTable { path: self.field_path(key), table })),
+            Some(value) => Err(self.mismatch(key, "a table", value)),
+            None => Ok(None)
+        }
+    }
+
+    /// An array of tables such as `[[fields]]`, each addressed as `fields[i]` in errors.
+    pub fn get_tables(&self, key: &str) -> Result<Option<Vec<SceneTable<'a>>>, SceneError> {
+        let values = match self.table.get(key) {
+            Some(&Value::Array(ref values)) => values,
+            Some(value) => return Err(self.mismatch(key, "an array of tables", value)),
+            None => return Ok(None)
+        };
+        let mut tables: Vec<SceneTable<'a>> = Vec::with_capacity(values.len());
+        for (i, value) in values.iter().enumerate() {
+            let path = format!("{}[{}]", self.field_path(key), i);
+            match *value {
+                Value::Table(ref table) => tables.push(SceneTable { path, table }),
+                _ => return Err(SceneError { field: path, message: format!("expected a table, found {}", value.type_str()) })
+            }
+        }
+        Ok(Some(tables))
+    }
+
+    pub fn get_f32(&self, key: &str) -> Result<Option<f32>, SceneError> {
+        match self.table.get(key) {
+            Some(value) => number(value).map(Some).ok_or_else(|| self.mismatch(key, "a number", value)),
+            None => Ok(None)
+        }
+    }
+
+    /// A number that must lie between `min` and `max` inclusive.
+    pub fn get_f32_in(&self, key: &str, min: f32, max: f32) -> Result<Option<f32>, SceneError> {
+        match self.get_f32(key)? {
+            Some(value) if value < min || value > max => {
+                let range = match (min == f32::MIN, max == f32::MAX) {
+                    (true, _) => format!("at most {}", max),
+                    (_, true) => format!("at least {}", min),
+                    _         => format!("between {} and {}", min, max)
+                };
+                Err(self.error(key, format!("{} is out of range, expected {}", value, range)))
+            },
+            value => Ok(value)
+        }
+    }
+
+    /// A whole number of at least `min`.
+    pub fn get_integer(&self, key: &str, min: i64) -> Result<Option<i64>, SceneError> {
+        match self.table.get(key) {
+            Some(&Value::Integer(value)) if value < min => Err(self.error(key, format!("{} is out of range, expected at least {}", value, min))),
+            Some(&Value::Integer(value)) => Ok(Some(value)),
+            Some(value) => Err(self.mismatch(key, "a whole number", value)),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, SceneError> {
+        match self.table.get(key) {
+            Some(&Value::Boolean(value)) => Ok(Some(value)),
+            Some(value) => Err(self.mismatch(key, "true or false", value)),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<&'a str>, SceneError> {
+        match self.table.get(key) {
+            Some(&Value::String(ref value)) => Ok(Some(value)),
+            Some(value) => Err(self.mismatch(key, "a string", value)),
+            None => Ok(None)
+        }
+    }
+
+    /// A string naming one of a set of options, turned into its value by `parse`.
+    pub fn get_name<T, F>(&self, key: &str, parse: F, names: &[&str]) -> Result<Option<T>, SceneError>
+        where F: Fn(&str) -> Option<T>
+    {
+        match self.get_str(key)? {
+            Some(name) => parse(name)
+                .map(Some)
+                .ok_or_else(|| self.error(key, format!("unknown value {:?}, expected one of {}", name, names.join(", ")))),
+            None => Ok(None)
+        }
+    }
+
+    fn get_numbers(&self, key: &str, count: usize) -> Result<Option<Vec<f32>>, SceneError> {
+        let expected = format!("an array of {} numbers", count);
+        match self.table.get(key) {
+            Some(&Value::Array(ref values)) if values.len() == count => values.iter()
+                .map(number)
+                .collect::<Option<Vec<f32>>>()
+                .map(Some)
+                .ok_or_else(|| self.error(key, expected)),
+            Some(&Value::Array(ref values)) => Err(self.error(key, format!("expected {}, found {}", expected, values.len()))),
+            Some(value) => Err(self.mismatch(key, &expected, value)),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_vec2(&self, key: &str) -> Result<Option<[f32; 2]>, SceneError> {
+        Ok(self.get_numbers(key, 2)?.map(|v| [v[0], v[1]]))
+    }
+
+    pub fn get_vec3(&self, key: &str) -> Result<Option<[f32; 3]>, SceneError> {
+        Ok(self.get_numbers(key, 3)?.map(|v| [v[0], v[1], v[2]]))
+    }
+
+    /// Red, green and blue, each between 0 and 1.
+    pub fn get_color(&self, key: &str) -> Result<Option<[f32; 3]>, SceneError> {
+        match self.get_vec3(key)? {
+            Some(color) if color.iter().any(|&c| c < 0.0 || c > 1.0) => {
+                Err(self.error(key, format!("{:?} is out of range, expected each channel between 0 and 1", color)))
+            },
+            color => Ok(color)
+        }
+    }
+}
+
+fn number(value: &Value) -> Option<f32> {
+    match *value {
+        Value::Float(value) => Some(value as f32),
+        Value::Integer(value) => Some(value as f32),
+        _ => None
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RenderSettings {
+    pub width: f32,
+    pub height: f32,
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub background: [f32; 3]
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: 800.0,
+            height: 600.0,
+            fov: 90.0,
+            near: 0.1,
+            far: 1000.0,
+            background: [0.0, 0.0, 0.0]
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn read(&mut self, table: &SceneTable) -> Result<(), SceneError> {
+        table.expect_keys(&["width", "height", "fov", "near", "far", "background"])?;
+        if let Some(width) = table.get_integer("width", 1)? {
+            self.width = width as f32;
+        }
+        if let Some(height) = table.get_integer("height", 1)? {
+            self.height = height as f32;
+        }
+        if let Some(fov) = table.get_f32_in("fov", 1.0, 179.0)? {
+            self.fov = fov;
+        }
+        if let Some(near) = table.get_f32_in("near", 1.0e-4, f32::MAX)? {
+            self.near = near;
+        }
+        if let Some(far) = table.get_f32_in("far", 1.0e-4, f32::MAX)? {
+            self.far = far;
+        }
+        if self.far <= self.near {
+            let key = if table.has("far") { "far" } else { "near" };
+            return Err(table.error(key, format!("the far plane ({}) must be beyond the near plane ({})", self.far, self.near)));
+        }
+        if let Some(background) = table.get_color("background")? {
+            self.background = background;
+        }
+        Ok(())
+    }
+
+    pub fn get_aspect(&self) -> f32 {
+        self.width / self.height
+    }
+
+    pub fn get_clear_color(&self) -> (f32, f32, f32, f32) {
+        (self.background[0], self.background[1], self.background[2], 1.0)
+    }
+}
+
+/// Where the fly camera starts; yaw and pitch are in degrees.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraSettings {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub speed: f32
+}
+
+impl CameraSettings {
+    pub fn new(position: [f32; 3], yaw: f32, pitch: f32, speed: f32) -> Self {
+        CameraSettings {
+            position,
+            yaw,
+            pitch,
+            speed
+        }
+    }
+
+    pub fn read(&mut self, table: &SceneTable) -> Result<(), SceneError> {
+        table.expect_keys(&["position", "yaw", "pitch", "speed"])?;
+        if let Some(position) = table.get_vec3("position")? {
+            self.position = position;
+        }
+        if let Some(yaw) = table.get_f32("yaw")? {
+            self.yaw = yaw;
+        }
+        if let Some(pitch) = table.get_f32_in("pitch", -89.0, 89.0)? {
+            self.pitch = pitch;
+        }
+        if let Some(speed) = table.get_f32_in("speed", 0.0, f32::MAX)? {
+            self.speed = speed;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LightSettings {
+    pub position: (f32, f32, f32),
+    pub color: [f32; 3]
+}
+
+impl LightSettings {
+    pub fn new(position: (f32, f32, f32), color: [f32; 3]) -> Self {
+        LightSettings {
+            position,
+            color
+        }
+    }
+
+    pub fn read(&mut self, table: &SceneTable) -> Result<(), SceneError> {
+        table.expect_keys(&["position", "color"])?;
+        if let Some(p) = table.get_vec3("position")? {
+            self.position = (p[0], p[1], p[2]);
+        }
+        if let Some(color) = table.get_color("color")? {
+            self.color = color;
+        }
+        Ok(())
+    }
+}
+
+/// Loads the file named by `--scene PATH`, if any, and hands it to `read`. Any error is printed
+/// with the file and field it points at and ends the program, rather than running some other scene.
+pub fn read_scene_from_args<F>(read: F) where F: FnOnce(&SceneFile) -> Result<(), SceneError> {
+    let args: Vec<String> = env::args().collect();
+    let path = match args.iter().position(|arg| arg == "--scene").and_then(|i| args.get(i + 1)) {
+        Some(path) => path,
+        None => return
+    };
+    if let Err(err) = SceneFile::load(path).and_then(|scene| read(&scene)) {
+        eprintln!("{}: {}", path, err);
+        process::exit(2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The field named by the error from reading `text`'s view sections.
+    fn view_error(text: &str) -> String {
+        let mut render = RenderSettings::default();
+        let mut camera = CameraSettings::new([0.0; 3], 0.0, 0.0, 1.0);
+        let mut light = LightSettings::new((0.0, 0.0, 0.0), [1.0; 3]);
+        SceneFile::parse(text)
+            .and_then(|scene| scene.read_view(&mut render, &mut camera, &mut light))
+            .err()
+            .expect("the scene should not read")
+            .field
+    }
+
+    #[test]
+    fn unknown_keys_are_named() {
+        assert_eq!(view_error("[physcs]\nseed = 1\n"), "physcs");
+        assert_eq!(view_error("[camera]\nzoom = 2.0\n"), "camera.zoom");
+    }
+
+    #[test]
+    fn out_of_range_numbers_are_named() {
+        assert_eq!(view_error("[render]\nfov = 180.0\n"), "render.fov");
+        assert_eq!(view_error("[render]\nnear = 10.0\nfar = 1.0\n"), "render.far");
+    }
+
+    #[test]
+    fn bad_colors_are_named() {
+        assert_eq!(view_error("[light]\ncolor = [1.0, 2.0, 0.0]\n"), "light.color");
+        assert_eq!(view_error("[render]\nbackground = [0.0, 0.0]\n"), "render.background");
+        assert_eq!(view_error("[light]\ncolor = \"red\"\n"), "light.color");
+    }
+
+    #[test]
+    fn valid_view_reads() {
+        let mut render = RenderSettings::default();
+        let mut camera = CameraSettings::new([0.0; 3], 0.0, 0.0, 1.0);
+        let mut light = LightSettings::new((0.0, 0.0, 0.0), [1.0; 3]);
+        let scene = SceneFile::parse("[render]\nwidth = 640\n[light]\ncolor = [0.5, 0.5, 0.5]\n").unwrap();
+        scene.read_view(&mut render, &mut camera, &mut light).unwrap();
+        assert_eq!(render.width, 640.0);
+        assert_eq!(light.color, [0.5, 0.5, 0.5]);
+    }
+}
@@ -1,12 +1,121 @@
 extern crate nalgebra as na;
 
-use na::{Matrix4, Isometry3};
-use vertex::Vertex;
+use glium::uniforms::{UniformValue, Uniforms};
+use na::{Matrix4, Isometry3, Point2, Point3, Vector2, Vector3};
+use std::f32;
+
+use scene_file::{SceneError, SceneTable};
+
+/// Size of the `sources` array in `sheet.vert`.
+pub const MAX_WAVE_SOURCES: usize = 16;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WaveSourceKind {
+    /// Circular ripples spreading from the source.
+    Point,
+    /// Straight fronts across the whole sheet, travelling along the direction.
+    Plane,
+    /// Circular ripples that fade away from the direction.
+    Directional
+}
+
+impl WaveSourceKind {
+    pub fn all() -> [WaveSourceKind; 3] {
+        [WaveSourceKind::Point, WaveSourceKind::Plane, WaveSourceKind::Directional]
+    }
+
+    pub fn from_name(name: &str) -> Option<WaveSourceKind> {
+        WaveSourceKind::all().iter().cloned().find(|kind| kind.get_name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            WaveSourceKind::Point       => "POINT",
+            WaveSourceKind::Plane       => "PLANE",
+            WaveSourceKind::Directional => "DIRECTIONAL"
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match *self {
+            WaveSourceKind::Point       => WaveSourceKind::Plane,
+            WaveSourceKind::Plane       => WaveSourceKind::Directional,
+            WaveSourceKind::Directional => WaveSourceKind::Point
+        }
+    }
+
+    fn get_index(&self) -> i32 {
+        match *self {
+            WaveSourceKind::Point       => 0,
+            WaveSourceKind::Plane       => 1,
+            WaveSourceKind::Directional => 2
+        }
+    }
+}
+
+/// One term of the analytic ripple, in the sheet's local xy-plane.
+#[derive(Copy, Clone, Debug)]
+pub struct WaveSource {
+    pub kind: WaveSourceKind,
+    pub position: Point2<f32>,
+    /// Unit direction of travel for plane sources and of the main lobe for directional ones.
+    pub direction: Vector2<f32>,
+    pub amplitude: f32,
+    /// Radians per unit of sheet time; negative values make the ripples run inwards.
+    pub frequency: f32,
+    pub phase: f32,
+    /// Exponential falloff per sheet width of distance from the source.
+    pub decay: f32
+}
+
+impl WaveSource {
+    pub fn new(kind: WaveSourceKind, position: Point2<f32>) -> Self {
+        WaveSource {
+            kind,
+            position,
+            direction: Vector2::x(),
+            amplitude: 0.01,
+            frequency: 1.0,
+            phase: 0.0,
+            decay: 0.0
+        }
+    }
+
+    /// Reads one `[[sheets.sources]]` entry; `position` is in sheet widths from the centre.
+    pub fn read(table: &SceneTable) -> Result<Self, SceneError> {
+        table.expect_keys(&["kind", "position", "direction", "amplitude", "frequency", "phase", "decay"])?;
+        let names: Vec<&str> = WaveSourceKind::all().iter().map(|kind| kind.get_name()).collect();
+        let kind = table.get_name("kind", WaveSourceKind::from_name, &names)?.unwrap_or(WaveSourceKind::Point);
+        let position = table.get_vec2("position")?.unwrap_or([0.0, 0.0]);
+        let mut source = WaveSource::new(kind, Point2::new(position[0], position[1]));
+        if let Some(d) = table.get_vec2("direction")? {
+            let direction = Vector2::new(d[0], d[1]);
+            if direction.norm() == 0.0 {
+                return Err(table.error("direction", String::from("must not be all zeros")));
+            }
+            source.direction = direction.normalize();
+        }
+        if let Some(amplitude) = table.get_f32("amplitude")? {
+            source.amplitude = amplitude;
+        }
+        if let Some(frequency) = table.get_f32("frequency")? {
+            source.frequency = frequency;
+        }
+        if let Some(phase) = table.get_f32("phase")? {
+            source.phase = phase;
+        }
+        if let Some(decay) = table.get_f32_in("decay", 0.0, f32::MAX)? {
+            source.decay = decay;
+        }
+        Ok(source)
+    }
+}
 
 pub struct Sheet {
     scale: Matrix4<f32>,
     color: [f32; 3],
-    position: Isometry3<f32>
+    position: Isometry3<f32>,
+    sources: Vec<WaveSource>
 }
 
 impl Sheet {
@@ -19,14 +128,42 @@ impl Sheet {
                 0.0, 0.0, size, 0.0,
                 0.0, 0.0, 0.0,  1.0
             ),
-            position
+            position,
+            sources: Vec::new()
         }
     }
 
+    /// Reads one `[[sheets]]` entry; `rotation` is an axis scaled by the angle, as for `Isometry3::new`.
+    pub fn read(table: &SceneTable) -> Result<Self, SceneError> {
+        table.expect_keys(&["position", "rotation", "size", "color", "sources"])?;
+        let p = table.get_vec3("position")?.unwrap_or([0.0; 3]);
+        let r = table.get_vec3("rotation")?.unwrap_or([0.0; 3]);
+        let size = table.get_f32_in("size", 1.0e-3, f32::MAX)?.unwrap_or(1.0);
+        let color = table.get_color("color")?.unwrap_or([1.0, 1.0, 1.0]);
+        let mut sheet = Sheet::new(Isometry3::new(Vector3::new(p[0], p[1], p[2]), Vector3::new(r[0], r[1], r[2])), color, size);
+        let sources = table.get_tables("sources")?.unwrap_or_default();
+        if sources.len() > MAX_WAVE_SOURCES {
+            return Err(table.error("sources", format!("{} sources given, the shader takes at most {}", sources.len(), MAX_WAVE_SOURCES)));
+        }
+        for source in &sources {
+            sheet.add_source(WaveSource::read(source)?);
+        }
+        Ok(sheet)
+    }
+
     pub fn get_color(&self) -> [f32; 3] {
         self.color
     }
 
+    pub fn get_position(&self) -> Isometry3<f32> {
+        self.position
+    }
+
+    /// Side length of the square the sheet covers.
+    pub fn get_size(&self) -> f32 {
+        self.scale[(0, 0)]
+    }
+
     pub fn get_scale(&self) -> Matrix4<f32> {
         self.scale
     }
@@ -34,26 +171,90 @@ impl Sheet {
     pub fn get_model_transform(&self) -> Matrix4<f32> {
         self.position.to_homogeneous() * self.get_scale()
     }
+
+    pub fn get_sources(&self) -> &[WaveSource] {
+        &self.sources
+    }
+
+    pub fn get_sources_mut(&mut self) -> &mut Vec<WaveSource> {
+        &mut self.sources
+    }
+
+    /// Adds a source unless the shader's array is already full.
+    pub fn add_source(&mut self, source: WaveSource) -> bool {
+        if self.sources.len() >= MAX_WAVE_SOURCES {
+            return false;
+        }
+        let mut source = source;
+        if source.direction.norm_squared() > 0.0 {
+            source.direction = source.direction.normalize();
+        }
+        self.sources.push(source);
+        true
+    }
+
+    /// Appends this sheet's sources to `base` as the `sources` array and `source_count`.
+    pub fn source_uniforms<U: Uniforms>(&self, base: U) -> SourceUniforms<U> {
+        SourceUniforms {
+            base,
+            sources: &self.sources
+        }
+    }
+
+    /// Where a ray meets the undisplaced sheet, in its local xy-plane, if it lands on the sheet.
+    pub fn intersect(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<Point2<f32>> {
+        let inverse = self.position.inverse();
+        let local_origin = inverse * origin;
+        let local_direction = inverse * direction;
+        if local_direction.z.abs() < 1.0e-6 {
+            return None;
+        }
+        let t = -local_origin.z / local_direction.z;
+        if t < 0.0 {
+            return None;
+        }
+        let hit = (local_origin + local_direction * t) / self.get_size();
+        if hit.x.abs() > 0.5 || hit.y.abs() > 0.5 {
+            return None;
+        }
+        Some(Point2::new(hit.x, hit.y))
+    }
 }
 
-pub fn get_sheet_verts(res: usize) -> Vec<Vertex> {
-    let mut shape: Vec<Vertex> = Vec::new();
-    let hres = ((res as f32)/2.0) as i32;
-    let resf: f32 = res as f32;
-    let unit: f32 = 1.0/resf;
-    for h in -hres..hres {
-        let hf: f32 = h as f32;
-        for w in -hres..hres {
-            let wf: f32 = w  as f32;
-            let x: f32 = wf * unit;
-            let y: f32 = hf * unit;
-            shape.push(Vertex::new(x, y, 0.0, (0.0, 0.0, 1.0)));
-            shape.push(Vertex::new(x+unit, y, 0.0, (0.0, 0.0, 1.0)));
-            shape.push(Vertex::new(x+unit, y+unit, 0.0, (0.0, 0.0, 1.0)));
-            shape.push(Vertex::new(x+unit, y+unit, 0.0, (0.0, 0.0, 1.0)));
-            shape.push(Vertex::new(x, y+unit, 0.0, (0.0, 0.0, 1.0)));
-            shape.push(Vertex::new(x, y, 0.0, (0.0, 0.0, 1.0)));
-        }
-    }
-    shape
-}
\ No newline at end of file
+pub struct SourceUniforms<'s, U> {
+    base: U,
+    sources: &'s [WaveSource]
+}
+
+impl<'s, U: Uniforms> Uniforms for SourceUniforms<'s, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+        self.base.visit_values(&mut f);
+        f("source_count", UniformValue::SignedInt(self.sources.len() as i32));
+        for (i, source) in self.sources.iter().enumerate() {
+            f(&format!("sources[{}].kind", i), UniformValue::SignedInt(source.kind.get_index()));
+            f(&format!("sources[{}].position", i), UniformValue::Vec2([source.position.x, source.position.y]));
+            f(&format!("sources[{}].direction", i), UniformValue::Vec2([source.direction.x, source.direction.y]));
+            f(&format!("sources[{}].amplitude", i), UniformValue::Float(source.amplitude));
+            f(&format!("sources[{}].frequency", i), UniformValue::Float(source.frequency));
+            f(&format!("sources[{}].phase", i), UniformValue::Float(source.phase));
+            f(&format!("sources[{}].decay", i), UniformValue::Float(source.decay));
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scene_file::SceneFile;
+
+    #[test]
+    fn zero_source_direction_is_named() {
+        let text = "[[sheets]]\n\
+            [[sheets.sources]]\nkind = \"point\"\n\
+            [[sheets.sources]]\nkind = \"plane\"\ndirection = [1.0, 0.0]\n\
+            [[sheets.sources]]\nkind = \"plane\"\ndirection = [0.0, 0.0]\n";
+        let scene = SceneFile::parse(text).unwrap();
+        let sheets = scene.get_root().get_tables("sheets").unwrap().unwrap();
+        let err = Sheet::read(&sheets[0]).err().expect("a zero direction should not read");
+        assert_eq!(err.field, "sheets[0].sources[2].direction");
+    }
+}
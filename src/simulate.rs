@@ -0,0 +1,209 @@
+#[macro_use]
+extern crate glium;
+extern crate nalgebra as na;
+extern crate ncollide3d;
+extern crate nphysics3d;
+extern crate rayon;
+extern crate noise;
+extern crate toml;
+
+mod vertex;
+mod cube;
+mod cubody;
+mod force_field;
+mod barnes_hut;
+mod boids;
+mod impulse;
+mod joints;
+mod voxel;
+mod fracture;
+mod contact_events;
+mod rng;
+mod physics_scene;
+mod scene_file;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use force_field::*;
+use physics_scene::*;
+use scene_file::SceneFile;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Format {
+    Csv,
+    Json
+}
+
+struct Args {
+    scene: SceneOptions,
+    steps: usize,
+    every: usize,
+    format: Format,
+    out: Option<String>
+}
+
+const USAGE: &str = "usage: simulate [--scene PATH] [--preset SWARM|PYRAMID|DOMINOES|JENGA|CONTAINER|STRUCTURES|BREAKABLE|EMPTY] [--steps N] [--dt SECONDS] [--seed N] [--grid N] [--jitter METRES] \
+[--fields ATTRACTOR,VORTEX,..] [--nbody] [--boids] [--every N] [--format csv|json] [--out PATH]";
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        scene: SceneOptions::default(),
+        steps: 600,
+        every: 1,
+        format: Format::Csv,
+        out: None
+    };
+    // Presets choose their own grid size and fields unless these are given explicitly.
+    let mut preset: Option<ScenePreset> = None;
+    let mut grid: Option<i32> = None;
+    let mut fields: Option<Vec<FieldSpec>> = None;
+    let mut iter = env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        match flag.as_str() {
+            "--nbody" => args.scene.nbody = true,
+            "--boids" => args.scene.boids = true,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => {
+                let value = iter.next().ok_or_else(|| format!("{} needs a value", flag))?;
+                match flag.as_str() {
+                    "--steps"  => args.steps = parse(&flag, &value)?,
+                    "--every"  => args.every = usize::max(parse(&flag, &value)?, 1),
+                    "--dt"     => args.scene.timestep = parse(&flag, &value)?,
+                    "--seed"   => args.scene.seed = parse(&flag, &value)?,
+                    "--grid"   => grid = Some(parse(&flag, &value)?),
+                    "--jitter" => args.scene.jitter = parse(&flag, &value)?,
+                    "--out"    => args.out = Some(value),
+                    "--preset" => preset = Some(ScenePreset::from_name(&value).ok_or_else(|| format!("unknown preset {}", value))?),
+                    // The file overrides flags before it and later flags override the file, except that
+                    // --preset, --grid and --fields always win.
+                    "--scene"  => {
+                        let scene = SceneFile::load(&value).map_err(|err| format!("{}: {}", value, err))?;
+                        args.scene.read(&scene.get_root()).map_err(|err| format!("{}: {}", value, err))?;
+                    },
+                    "--format" => args.format = match value.as_str() {
+                        "csv"  => Format::Csv,
+                        "json" => Format::Json,
+                        _      => return Err(format!("unknown format {}", value))
+                    },
+                    "--fields" => {
+                        let mut kinds = Vec::new();
+                        for name in value.split(',').filter(|name| !name.is_empty()) {
+                            kinds.push(FieldSpec::new(FieldKind::from_name(name).ok_or_else(|| format!("unknown field {}", name))?));
+                        }
+                        fields = Some(kinds);
+                    },
+                    _ => return Err(format!("unknown flag {}\n{}", flag, USAGE))
+                }
+            }
+        }
+    }
+    // Without --preset the scene file, or the swarm, keeps its own grid and fields.
+    if let Some(preset) = preset {
+        args.scene.set_preset(preset);
+    }
+    if let Some(grid) = grid {
+        args.scene.cube_iter = grid;
+    }
+    if let Some(fields) = fields {
+        args.scene.fields = fields;
+    }
+    Ok(args)
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("bad value {} for {}", value, flag))
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+
+    let writer: Box<Write> = match args.out {
+        Some(ref path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("could not create {}: {}", path, err);
+                process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout())
+    };
+    if let Err(err) = run(&args, &mut BufWriter::new(writer)) {
+        eprintln!("write failed: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Steps the scene at a fixed dt and writes every `every`th frame, including the initial one.
+///
+/// Floats are written with `{}`, which round-trips exactly, so identical seeds give identical files.
+fn run<W: Write>(args: &Args, out: &mut W) -> io::Result<()> {
+    let mut scene = PhysicsScene::new(&args.scene);
+    match args.format {
+        Format::Csv  => writeln!(out, "step,body,x,y,z,qw,qx,qy,qz,vx,vy,vz")?,
+        Format::Json => write!(out, "{{\"seed\":{},\"dt\":{},\"frames\":[", args.scene.seed, args.scene.timestep)?
+    }
+    for step in 0..(args.steps + 1) {
+        if step > 0 {
+            scene.step();
+        }
+        if step % args.every != 0 {
+            continue;
+        }
+        let states = capture_body_states(&scene.world, &scene.cubes);
+        match args.format {
+            Format::Csv => {
+                for (body, state) in states.iter().enumerate() {
+                    let (p, q, v) = components(state);
+                    writeln!(out, "{},{},{},{},{},{},{},{},{},{},{},{}",
+                        step, body, p[0], p[1], p[2], q[0], q[1], q[2], q[3], v[0], v[1], v[2])?;
+                }
+            },
+            Format::Json => {
+                if step > 0 {
+                    write!(out, ",")?;
+                }
+                write!(out, "{{\"step\":{},\"bodies\":[", step)?;
+                for (body, state) in states.iter().enumerate() {
+                    let (p, q, v) = components(state);
+                    if body > 0 {
+                        write!(out, ",")?;
+                    }
+                    write!(out, "{{\"position\":[{},{},{}],\"rotation\":[{},{},{},{}],\"velocity\":[{},{},{}]}}",
+                        json(p[0]), json(p[1]), json(p[2]),
+                        json(q[0]), json(q[1]), json(q[2]), json(q[3]),
+                        json(v[0]), json(v[1]), json(v[2]))?;
+                }
+                write!(out, "]}}")?;
+            }
+        }
+    }
+    if args.format == Format::Json {
+        writeln!(out, "]}}")?;
+    }
+    out.flush()
+}
+
+/// Position, rotation as (w, x, y, z) and linear velocity.
+fn components(state: &BodyState) -> ([f32; 3], [f32; 4], [f32; 3]) {
+    let p = state.position.translation.vector;
+    let q = state.position.rotation.quaternion().coords;
+    let v = state.velocity.linear;
+    ([p.x, p.y, p.z], [q[3], q[0], q[1], q[2]], [v.x, v.y, v.z])
+}
+
+fn json(value: f32) -> String {
+    if value.is_finite() {
+        format!("{}", value)
+    } else {
+        "null".to_string()
+    }
+}
@@ -0,0 +1,301 @@
+use na::{Isometry3, Point3, Quaternion, Translation3, UnitQuaternion, Vector3};
+use nphysics3d::force_generator::ForceGeneratorHandle;
+use nphysics3d::math::Velocity;
+use nphysics3d::world::World;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+use cubody::*;
+use force_field::*;
+use physics_scene::*;
+
+/// What can change about a force field while the scene runs.
+///
+/// A field is known by its kind and how many fields of that kind come before it, so toggling
+/// other kinds on or off does not move its state onto the wrong field.
+#[derive(Copy, Clone, Debug)]
+pub struct FieldState {
+    pub kind: FieldKind,
+    pub index: usize,
+    pub strength: f32,
+    /// Where an attractor's orbit or a pulse's inner field has got to.
+    pub center: Option<Point3<f32>>
+}
+
+/// Body states plus the clock, strengths and centres of the force fields; a pulse's phase
+/// follows from the clock. Contacts and solver caches are not recorded, and neither is which
+/// bodies exist: a snapshot only fits the scene until something fractures, so callers drop
+/// older snapshots whenever `PhysicsScene::step` reports a break.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub step: u64,
+    pub field_time: f32,
+    pub fields: Vec<FieldState>,
+    pub bodies: Vec<BodyState>
+}
+
+impl Snapshot {
+    pub fn capture(step: u64, world: &World<f32>, cubes: &[Cubody], fields_handle: ForceGeneratorHandle) -> Self {
+        let (field_time, fields) = match world.force_generator(fields_handle).downcast_ref::<ForceFieldSet>() {
+            Ok(set) => {
+                let states = set.get_fields().iter()
+                    .zip(field_keys(set.get_fields()))
+                    .map(|(field, (kind, index))| FieldState {
+                        kind,
+                        index,
+                        strength: field.get_strength(),
+                        center: field.get_center()
+                    })
+                    .collect();
+                (set.get_time(), states)
+            },
+            Err(_) => (0.0, Vec::new())
+        };
+        Snapshot {
+            step,
+            field_time,
+            fields,
+            bodies: capture_body_states(world, cubes)
+        }
+    }
+
+    /// Puts every recorded body back where it was and moving as it was, and gives every field
+    /// that still exists its recorded strength and centre. Fields added since are left alone.
+    pub fn restore(&self, world: &mut World<f32>, fields_handle: ForceGeneratorHandle) {
+        for state in &self.bodies {
+            if let Some(rigid_body) = world.rigid_body_mut(state.handle) {
+                rigid_body.set_position(state.position);
+                rigid_body.set_velocity(state.velocity);
+                rigid_body.activate();
+            }
+        }
+        if let Ok(set) = world.force_generator_mut(fields_handle).downcast_mut::<ForceFieldSet>() {
+            set.set_time(self.field_time);
+            let keys = field_keys(set.get_fields());
+            for (field, (kind, index)) in set.get_fields_mut().iter_mut().zip(keys) {
+                if let Some(state) = self.fields.iter().find(|state| state.kind == kind && state.index == index) {
+                    field.set_strength(state.strength);
+                    if let Some(center) = state.center {
+                        field.set_center(center);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the snapshot as text; floats use `{}` so they read back bit for bit.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "snapshot {} {}", self.step, self.field_time)?;
+        for field in &self.fields {
+            write!(out, "field {} {} {}", field.kind.get_name(), field.index, field.strength)?;
+            match field.center {
+                Some(c) => writeln!(out, " {} {} {}", c.x, c.y, c.z)?,
+                None    => writeln!(out, " none")?
+            }
+        }
+        for state in &self.bodies {
+            let p = state.position.translation.vector;
+            let q = state.position.rotation.quaternion().coords;
+            let v = state.velocity.linear;
+            let w = state.velocity.angular;
+            writeln!(out, "body {} {} {} {} {} {} {} {} {} {} {} {} {}",
+                p.x, p.y, p.z, q[3], q[0], q[1], q[2], v.x, v.y, v.z, w.x, w.y, w.z)?;
+        }
+        out.flush()
+    }
+
+    /// Reads a snapshot written by `save`, matching bodies to those behind `cubes` by order.
+    pub fn load(path: &str, cubes: &[Cubody]) -> io::Result<Self> {
+        let handles = body_handles(cubes);
+        let reader = BufReader::new(File::open(path)?);
+        let mut snapshot = Snapshot {
+            step: 0,
+            field_time: 0.0,
+            fields: Vec::new(),
+            bodies: Vec::new()
+        };
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let bad_line = || io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: bad line", path, number + 1));
+            match words.first() {
+                Some(&"snapshot") if words.len() == 3 => {
+                    snapshot.step = words[1].parse().map_err(|_| bad_line())?;
+                    snapshot.field_time = words[2].parse().map_err(|_| bad_line())?;
+                },
+                Some(&"field") if words.len() == 5 || words.len() == 7 => {
+                    let center = match words.len() {
+                        5 if words[4] == "none" => None,
+                        7 => {
+                            let c = parse_floats(&words[4..]).ok_or_else(bad_line)?;
+                            Some(Point3::new(c[0], c[1], c[2]))
+                        },
+                        _ => return Err(bad_line())
+                    };
+                    snapshot.fields.push(FieldState {
+                        kind: FieldKind::from_name(words[1]).ok_or_else(bad_line)?,
+                        index: words[2].parse().map_err(|_| bad_line())?,
+                        strength: words[3].parse().map_err(|_| bad_line())?,
+                        center
+                    });
+                },
+                Some(&"body") if words.len() == 14 => {
+                    let handle = *handles.get(snapshot.bodies.len()).ok_or_else(|| io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} has more bodies than the scene", path)
+                    ))?;
+                    let f = parse_floats(&words[1..]).ok_or_else(bad_line)?;
+                    let rotation = UnitQuaternion::new_unchecked(Quaternion::new(f[3], f[4], f[5], f[6]));
+                    snapshot.bodies.push(BodyState {
+                        handle,
+                        position: Isometry3::from_parts(Translation3::new(f[0], f[1], f[2]), rotation),
+                        velocity: Velocity::new(Vector3::new(f[7], f[8], f[9]), Vector3::new(f[10], f[11], f[12]))
+                    });
+                },
+                None => (),
+                _ => return Err(bad_line())
+            }
+        }
+        if snapshot.bodies.len() != handles.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} has {} bodies, the scene has {}", path, snapshot.bodies.len(), handles.len())
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Each field's kind and how many fields of that kind come before it.
+fn field_keys(fields: &[Box<ForceField>]) -> Vec<(FieldKind, usize)> {
+    fields.iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let kind = field.get_kind();
+            (kind, fields[..i].iter().filter(|other| other.get_kind() == kind).count())
+        })
+        .collect()
+}
+
+fn parse_floats(words: &[&str]) -> Option<Vec<f32>> {
+    words.iter().map(|word| word.parse().ok()).collect()
+}
+
+/// A bounded history of snapshots with a cursor for scrubbing back and replaying forward.
+pub struct Timeline {
+    frames: VecDeque<Snapshot>,
+    capacity: usize,
+    cursor: usize,
+    pub paused: bool
+}
+
+impl Timeline {
+    pub fn new(capacity: usize) -> Self {
+        Timeline {
+            frames: VecDeque::with_capacity(capacity),
+            capacity: usize::max(capacity, 1),
+            cursor: 0,
+            paused: false
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn get_cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn current(&self) -> Option<&Snapshot> {
+        self.frames.get(self.cursor)
+    }
+
+    /// True when the cursor sits behind the newest frame, i.e. there is recorded history to replay.
+    pub fn is_rewound(&self) -> bool {
+        self.cursor + 1 < self.frames.len()
+    }
+
+    /// Appends a frame after the cursor, dropping any recorded future and the oldest frame when full.
+    pub fn record(&mut self, snapshot: Snapshot) {
+        self.truncate();
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(snapshot);
+        self.cursor = self.frames.len() - 1;
+    }
+
+    /// Forgets every frame after the cursor so the simulation can branch from here.
+    pub fn truncate(&mut self) {
+        self.frames.truncate(self.cursor + 1);
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.cursor = 0;
+    }
+
+    /// Moves the cursor back up to `frames` steps and returns the frame it lands on.
+    pub fn rewind(&mut self, frames: usize) -> Option<&Snapshot> {
+        self.cursor -= usize::min(frames, self.cursor);
+        self.frames.get(self.cursor)
+    }
+
+    /// Moves the cursor one recorded frame forward, or returns `None` at the newest frame.
+    pub fn forward(&mut self) -> Option<&Snapshot> {
+        if !self.is_rewound() {
+            return None;
+        }
+        self.cursor += 1;
+        self.frames.get(self.cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn save_then_load_is_exact() {
+        let options = SceneOptions {
+            cube_iter: 2,
+            jitter: 0.2,
+            .. SceneOptions::default()
+        };
+        let mut scene = PhysicsScene::new(&options);
+        for _ in 0..20 {
+            scene.step();
+        }
+        let saved = Snapshot::capture(20, &scene.world, &scene.cubes, scene.fields_handle);
+        assert!(!saved.fields.is_empty() && !saved.bodies.is_empty());
+
+        let path = env::temp_dir().join(format!("snapshot-round-trip-{}.txt", process::id()));
+        let path = path.to_str().unwrap();
+        saved.save(path).unwrap();
+        let loaded = Snapshot::load(path, &scene.cubes);
+        fs::remove_file(path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.step, saved.step);
+        assert_eq!(loaded.field_time.to_bits(), saved.field_time.to_bits());
+        assert_eq!(loaded.fields.len(), saved.fields.len());
+        for (a, b) in loaded.fields.iter().zip(saved.fields.iter()) {
+            assert!(a.kind == b.kind && a.index == b.index);
+            assert_eq!(a.strength.to_bits(), b.strength.to_bits());
+            assert_eq!(a.center, b.center);
+        }
+        assert_eq!(loaded.bodies.len(), saved.bodies.len());
+        for (a, b) in loaded.bodies.iter().zip(saved.bodies.iter()) {
+            assert_eq!(a.handle, b.handle);
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.velocity.linear, b.velocity.linear);
+            assert_eq!(a.velocity.angular, b.velocity.angular);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use overlay::*;
+
+const PANEL_WIDTH: f32 = 360.0;
+const ROW_HEIGHT:  f32 = 24.0;
+const LABEL_WIDTH: f32 = 150.0;
+const TEXT_SCALE:  f32 = 2.0;
+
+const BACKGROUND:  [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const TRACK:       [f32; 4] = [0.3, 0.3, 0.3, 0.9];
+const FILL:        [f32; 4] = [0.2, 0.5, 1.0, 0.9];
+const HOT:         [f32; 4] = [0.4, 0.7, 1.0, 0.9];
+const TEXT:        [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// A small immediate-mode panel drawn into an `Overlay`.
+///
+/// Widgets are declared every frame between `begin` and `end`; the panel only
+/// remembers which widget is being dragged.
+pub struct Ui {
+    pub visible: bool,
+    mouse: (f32, f32),
+    mouse_down: bool,
+    mouse_pressed: bool,
+    active: Option<usize>,
+    next_id: usize,
+    x: f32,
+    y: f32
+}
+
+impl Ui {
+    pub fn new() -> Self {
+        Ui {
+            visible: false,
+            mouse: (0.0, 0.0),
+            mouse_down: false,
+            mouse_pressed: false,
+            active: None,
+            next_id: 0,
+            x: 0.0,
+            y: 0.0
+        }
+    }
+
+    pub fn handle_cursor(&mut self, x: f32, y: f32) {
+        self.mouse = (x, y);
+    }
+
+    /// Clicks are only latched while the panel is shown; `end` never runs to clear one while it is
+    /// hidden, and a stale click would fire the first widget under the cursor when it reopens.
+    pub fn handle_button(&mut self, down: bool) {
+        if down && !self.mouse_down && self.visible {
+            self.mouse_pressed = true;
+        }
+        if !down {
+            self.active = None;
+        }
+        self.mouse_down = down;
+    }
+
+    /// Whether the cursor is over the panel, so clicks should not reach the scene.
+    pub fn is_hovered(&self, window_size: [f32; 2]) -> bool {
+        self.visible && self.mouse.0 >= window_size[0] - PANEL_WIDTH - 8.0
+    }
+
+    pub fn begin(&mut self, overlay: &mut Overlay, title: &str, window_size: [f32; 2]) {
+        self.next_id = 0;
+        self.x = window_size[0] - PANEL_WIDTH - 8.0;
+        self.y = 8.0;
+        self.add_row(overlay);
+        overlay.add_text(self.x + 4.0, self.y + 4.0, TEXT_SCALE, title, HOT);
+        self.y += ROW_HEIGHT;
+    }
+
+    pub fn end(&mut self) {
+        self.mouse_pressed = false;
+    }
+
+    pub fn label(&mut self, overlay: &mut Overlay, text: &str) {
+        self.add_row(overlay);
+        overlay.add_text(self.x + 4.0, self.y + 4.0, TEXT_SCALE, text, TEXT);
+        self.y += ROW_HEIGHT;
+    }
+
+    /// Returns true when the value was changed this frame.
+    pub fn slider(&mut self, overlay: &mut Overlay, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        let id = self.next_widget_id();
+        self.add_row(overlay);
+        overlay.add_text(self.x + 4.0, self.y + 4.0, TEXT_SCALE, label, TEXT);
+
+        let track_x = self.x + LABEL_WIDTH;
+        let track_w = PANEL_WIDTH - LABEL_WIDTH - 4.0;
+        let hovered = self.is_inside(track_x, self.y, track_w, ROW_HEIGHT);
+        if hovered && self.mouse_pressed {
+            self.active = Some(id);
+        }
+
+        let mut changed = false;
+        if self.active == Some(id) && self.mouse_down {
+            let t = ((self.mouse.0 - track_x) / track_w).max(0.0).min(1.0);
+            let new_value = min + t * (max - min);
+            changed = new_value != *value;
+            *value = new_value;
+        }
+
+        let t = ((*value - min) / (max - min)).max(0.0).min(1.0);
+        let fill = if hovered || self.active == Some(id) { HOT } else { FILL };
+        overlay.add_rect(track_x, self.y + 2.0, track_w, ROW_HEIGHT - 4.0, TRACK);
+        overlay.add_rect(track_x, self.y + 2.0, track_w * t, ROW_HEIGHT - 4.0, fill);
+        overlay.add_text(track_x + 4.0, self.y + 4.0, TEXT_SCALE, &format!("{:.3}", value), TEXT);
+        self.y += ROW_HEIGHT;
+        changed
+    }
+
+    /// Returns true when the value was toggled this frame.
+    pub fn checkbox(&mut self, overlay: &mut Overlay, label: &str, value: &mut bool) -> bool {
+        self.next_widget_id();
+        self.add_row(overlay);
+        overlay.add_text(self.x + 4.0, self.y + 4.0, TEXT_SCALE, label, TEXT);
+
+        let box_x = self.x + LABEL_WIDTH;
+        let box_size = ROW_HEIGHT - 4.0;
+        let hovered = self.is_inside(box_x, self.y, box_size, ROW_HEIGHT);
+        let changed = hovered && self.mouse_pressed;
+        if changed {
+            *value = !*value;
+        }
+
+        overlay.add_rect(box_x, self.y + 2.0, box_size, box_size, TRACK);
+        if *value {
+            let fill = if hovered { HOT } else { FILL };
+            overlay.add_rect(box_x + 4.0, self.y + 6.0, box_size - 8.0, box_size - 8.0, fill);
+        }
+        self.y += ROW_HEIGHT;
+        changed
+    }
+
+    fn next_widget_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn add_row(&self, overlay: &mut Overlay) {
+        overlay.add_rect(self.x, self.y, PANEL_WIDTH, ROW_HEIGHT, BACKGROUND);
+    }
+
+    fn is_inside(&self, x: f32, y: f32, w: f32, h: f32) -> bool {
+        self.mouse.0 >= x && self.mouse.0 <= x + w && self.mouse.1 >= y && self.mouse.1 <= y + h
+    }
+}
\ No newline at end of file
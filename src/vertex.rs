@@ -1,16 +1,118 @@
-
 #[derive(Copy, Clone)]
 pub struct Vertex {
-    position: (f32, f32, f32),
-    normal:   (f32, f32, f32)
+    position:   (f32, f32, f32),
+    normal:     (f32, f32, f32),
+    tex_coords: (f32, f32)
 }
 impl Vertex {
     pub fn new(x: f32, y: f32, z: f32, normal: (f32, f32, f32)) -> Self {
+        Vertex::textured(x, y, z, normal, (0.0, 0.0))
+    }
+
+    pub fn textured(x: f32, y: f32, z: f32, normal: (f32, f32, f32), tex_coords: (f32, f32)) -> Self {
         Vertex {
             position: (x, y, z),
-            normal   
+            normal,
+            tex_coords
+        }
+    }
+
+    pub fn get_position(&self) -> (f32, f32, f32) {
+        self.position
+    }
+
+    pub fn get_normal(&self) -> (f32, f32, f32) {
+        self.normal
+    }
+
+    pub fn get_tex_coords(&self) -> (f32, f32) {
+        self.tex_coords
+    }
+}
+
+implement_vertex!(Vertex, position, normal, tex_coords);
+
+#[derive(Copy, Clone)]
+pub struct LineVertex {
+    position: (f32, f32, f32),
+    color:    (f32, f32, f32)
+}
+impl LineVertex {
+    pub fn new(x: f32, y: f32, z: f32, color: [f32; 3]) -> Self {
+        LineVertex {
+            position: (x, y, z),
+            color:    (color[0], color[1], color[2])
+        }
+    }
+}
+
+implement_vertex!(LineVertex, position, color);
+
+#[derive(Copy, Clone)]
+pub struct OverlayVertex {
+    position:   (f32, f32),
+    tex_coords: (f32, f32),
+    color:      (f32, f32, f32, f32)
+}
+impl OverlayVertex {
+    pub fn new(x: f32, y: f32, u: f32, v: f32, color: [f32; 4]) -> Self {
+        OverlayVertex {
+            position:   (x, y),
+            tex_coords: (u, v),
+            color:      (color[0], color[1], color[2], color[3])
+        }
+    }
+}
+
+implement_vertex!(OverlayVertex, position, tex_coords, color);
+
+/// One corner of the unit quad every particle is drawn with.
+#[derive(Copy, Clone)]
+pub struct ParticleCorner {
+    corner: (f32, f32)
+}
+impl ParticleCorner {
+    pub fn new(x: f32, y: f32) -> Self {
+        ParticleCorner {
+            corner: (x, y)
+        }
+    }
+}
+
+implement_vertex!(ParticleCorner, corner);
+
+/// Per-instance data for a billboarded particle.
+#[derive(Copy, Clone)]
+pub struct ParticleInstance {
+    center: (f32, f32, f32),
+    color:  (f32, f32, f32, f32),
+    size:   f32
+}
+impl ParticleInstance {
+    pub fn new(center: [f32; 3], color: [f32; 4], size: f32) -> Self {
+        ParticleInstance {
+            center: (center[0], center[1], center[2]),
+            color:  (color[0], color[1], color[2], color[3]),
+            size
+        }
+    }
+}
+
+implement_vertex!(ParticleInstance, center, color, size);
+
+/// Simulated height and slope of a sheet vertex, uploaded alongside the flat grid each frame.
+#[derive(Copy, Clone)]
+pub struct SheetHeight {
+    height:   f32,
+    gradient: (f32, f32)
+}
+impl SheetHeight {
+    pub fn new(height: f32, gradient: (f32, f32)) -> Self {
+        SheetHeight {
+            height,
+            gradient
         }
     }
 }
 
-implement_vertex!(Vertex, position, normal);
\ No newline at end of file
+implement_vertex!(SheetHeight, height, gradient);
\ No newline at end of file
@@ -0,0 +1,61 @@
+use noise::{Fbm, NoiseFn};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Voxel {
+    pub coords: (i32, i32, i32),
+    pub color: [f32; 3]
+}
+
+/// The filled cells of a noise-carved block, as `cube_world` draws it.
+pub struct VoxelGrid {
+    pub cell_size: f32,
+    pub voxels: Vec<Voxel>
+}
+
+impl VoxelGrid {
+    /// Fills every cell of a `size`³ block where fractal noise is non-negative.
+    pub fn from_noise(size: i32, cell_size: f32, seed: f32, frequency: f32) -> Self {
+        let noise = Fbm::new();
+        let mut voxels = Vec::new();
+        for x in 0..size {
+            let fx = x as f32;
+            for y in 0..size {
+                let fy = y as f32;
+                for z in 0..size {
+                    let fz = z as f32;
+
+                    let val = noise.get([(seed+fx*cell_size*frequency) as f64, (seed+fy*cell_size*frequency) as f64, (seed+fz*cell_size*frequency) as f64]);
+                    if val >= 0.0 {
+                        voxels.push(Voxel {
+                            coords: (x, y, z),
+                            color: [(val*8.0) as f32, (val*5.0) as f32, (val*3.0) as f32]
+                        });
+                    }
+                }
+            }
+        }
+        VoxelGrid {
+            cell_size,
+            voxels
+        }
+    }
+
+    /// A solid box of cells in a single colour.
+    pub fn solid(size: (i32, i32, i32), cell_size: f32, color: [f32; 3]) -> Self {
+        let mut voxels = Vec::new();
+        for x in 0..size.0 {
+            for y in 0..size.1 {
+                for z in 0..size.2 {
+                    voxels.push(Voxel {
+                        coords: (x, y, z),
+                        color
+                    });
+                }
+            }
+        }
+        VoxelGrid {
+            cell_size,
+            voxels
+        }
+    }
+}
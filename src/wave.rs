@@ -0,0 +1,205 @@
+use std::mem;
+
+/// What happens to waves reaching the edge of the grid.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Boundary {
+    /// The edge is clamped at zero and reflects waves inverted.
+    Fixed,
+    /// The edge moves with its neighbours and reflects waves upright.
+    Free,
+    /// The edge lets waves leave with as little reflection as a first-order condition allows.
+    Absorbing
+}
+
+impl Boundary {
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            Boundary::Fixed     => "FIXED",
+            Boundary::Free      => "FREE",
+            Boundary::Absorbing => "ABSORBING"
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match *self {
+            Boundary::Fixed     => Boundary::Free,
+            Boundary::Free      => Boundary::Absorbing,
+            Boundary::Absorbing => Boundary::Fixed
+        }
+    }
+}
+
+/// Damped 2D wave equation solved by finite differences on the vertices of `Mesh::rect(1.0, 1.0, res, res)`.
+///
+/// Positions are in the sheet's local xy-plane, which spans -0.5 to 0.5, and heights are along z.
+pub struct WaveSolver {
+    size: usize,
+    spacing: f32,
+    heights: Vec<f32>,
+    previous: Vec<f32>,
+    next: Vec<f32>,
+    /// Propagation speed in sheet widths per second.
+    pub wave_speed: f32,
+    /// Fraction of velocity lost per second.
+    pub damping: f32,
+    pub boundary: Boundary
+}
+
+impl WaveSolver {
+    pub fn new(res: usize) -> Self {
+        let res = usize::max(res, 2);
+        let size = res + 1;
+        WaveSolver {
+            size,
+            spacing: 1.0 / res as f32,
+            heights: vec![0.0; size * size],
+            previous: vec![0.0; size * size],
+            next: vec![0.0; size * size],
+            wave_speed: 0.3,
+            damping: 0.3,
+            boundary: Boundary::Absorbing
+        }
+    }
+
+    /// Nodes along each side of the grid.
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// Heights of every node, row by row along x.
+    pub fn get_heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    pub fn get_height(&self, i: usize, j: usize) -> f32 {
+        self.heights[j * self.size + i]
+    }
+
+    /// Slope of the heightfield at every node along x and y, by central differences inside the
+    /// grid and one-sided ones on its edges.
+    pub fn get_gradients(&self) -> Vec<(f32, f32)> {
+        let n = self.size;
+        let difference = |low: usize, high: usize, steps: usize| {
+            (self.heights[high] - self.heights[low]) / (steps as f32 * self.spacing)
+        };
+        let mut gradients: Vec<(f32, f32)> = Vec::with_capacity(n * n);
+        for j in 0..n {
+            for i in 0..n {
+                let (left, right) = (usize::max(i, 1) - 1, usize::min(i + 1, n - 1));
+                let (down, up) = (usize::max(j, 1) - 1, usize::min(j + 1, n - 1));
+                gradients.push((
+                    difference(j * n + left, j * n + right, right - left),
+                    difference(down * n + i, up * n + i, up - down)
+                ));
+            }
+        }
+        gradients
+    }
+
+    pub fn clear(&mut self) {
+        for height in self.heights.iter_mut().chain(self.previous.iter_mut()) {
+            *height = 0.0;
+        }
+    }
+
+    /// Index of the node nearest to `(x, y)`, clamped to the grid.
+    pub fn node_at(&self, x: f32, y: f32) -> usize {
+        let last = self.size as i32 - 1;
+        let to_node = |v: f32| {
+            let node = ((v + 0.5) / self.spacing).round() as i32;
+            i32::min(i32::max(node, 0), last) as usize
+        };
+        to_node(y) * self.size + to_node(x)
+    }
+
+    fn node_position(&self, i: usize, j: usize) -> (f32, f32) {
+        (i as f32 * self.spacing - 0.5, j as f32 * self.spacing - 0.5)
+    }
+
+    /// Adds a Gaussian bump of height `amount` at rest, so it spreads out as a ring.
+    pub fn perturb(&mut self, x: f32, y: f32, radius: f32, amount: f32) {
+        for j in 0..self.size {
+            for i in 0..self.size {
+                let (px, py) = self.node_position(i, j);
+                let d2 = (px - x) * (px - x) + (py - y) * (py - y);
+                let bump = amount * f32::exp(-d2 / (radius * radius));
+                let k = j * self.size + i;
+                self.heights[k] += bump;
+                self.previous[k] += bump;
+            }
+        }
+    }
+
+    /// Holds every node within `radius` of `(x, y)` at `value`, e.g. for an oscillating source.
+    pub fn drive(&mut self, x: f32, y: f32, radius: f32, value: f32) {
+        for j in 0..self.size {
+            for i in 0..self.size {
+                let (px, py) = self.node_position(i, j);
+                if (px - x) * (px - x) + (py - y) * (py - y) <= radius * radius {
+                    self.heights[j * self.size + i] = value;
+                }
+            }
+        }
+    }
+
+    /// Advances by `dt`, split into as many substeps as the stability limit needs.
+    pub fn step(&mut self, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        // The explicit scheme is stable while c·dt/h stays under 1/√2.
+        let substeps = f32::max(f32::ceil(self.wave_speed * dt / (self.spacing * 0.7)), 1.0) as usize;
+        let sub_dt = dt / substeps as f32;
+        for _ in 0..substeps {
+            self.substep(sub_dt);
+        }
+    }
+
+    fn substep(&mut self, dt: f32) {
+        let n = self.size;
+        let r = self.wave_speed * dt / self.spacing;
+        let keep = f32::max(1.0 - self.damping * dt, 0.0);
+        {
+            let heights = &self.heights;
+            let previous = &self.previous;
+            let next = &mut self.next;
+            for j in 1..n - 1 {
+                for i in 1..n - 1 {
+                    let k = j * n + i;
+                    let laplacian = heights[k - 1] + heights[k + 1] + heights[k - n] + heights[k + n] - 4.0 * heights[k];
+                    next[k] = heights[k] + keep * (heights[k] - previous[k]) + r * r * laplacian;
+                }
+            }
+
+            // Each edge node paired with its neighbour one step inwards.
+            let mut edges: Vec<(usize, usize)> = Vec::with_capacity(4 * n);
+            for t in 1..n - 1 {
+                edges.push((t * n, t * n + 1));
+                edges.push((t * n + n - 1, t * n + n - 2));
+                edges.push((t, n + t));
+                edges.push(((n - 1) * n + t, (n - 2) * n + t));
+            }
+            for (edge, inner) in edges {
+                next[edge] = match self.boundary {
+                    Boundary::Fixed     => 0.0,
+                    Boundary::Free      => next[inner],
+                    // Mur's first-order condition for a wave leaving along the edge normal.
+                    Boundary::Absorbing => heights[inner] + (r - 1.0) / (r + 1.0) * (next[inner] - heights[edge])
+                };
+            }
+            for &(corner, a, b) in &[
+                (0, 1, n),
+                (n - 1, n - 2, 2 * n - 1),
+                ((n - 1) * n, (n - 1) * n + 1, (n - 2) * n),
+                (n * n - 1, n * n - 2, (n - 1) * n - 1)
+            ] {
+                next[corner] = match self.boundary {
+                    Boundary::Fixed => 0.0,
+                    _               => (next[a] + next[b]) * 0.5
+                };
+            }
+        }
+        mem::swap(&mut self.previous, &mut self.heights);
+        mem::swap(&mut self.heights, &mut self.next);
+    }
+}
@@ -71,6 +71,15 @@ impl Attractor {
     pub fn add_body_part(&mut self, body: BodyHandle) {
         self.parts.push(body);
     }
+    pub fn get_center(&self) -> Point3<f32> {
+        self.center
+    }
+    pub fn get_strength(&self) -> f32 {
+        self.strength
+    }
+    pub fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+    }
 }
 
 impl ForceGenerator<f32> for Attractor {